@@ -8,4 +8,23 @@ public class ConnectionSettings(string controllerAddress, ushort controllerPort)
     public string ControllerAddress { get; set; } = controllerAddress;
 
     [JsonPropertyName("ControllerPort")] public ushort ControllerPort { get; set; } = controllerPort;
+
+    /// <summary>
+    /// Key shared with the controller as hex string, datagrams are sent unsigned without one.
+    /// </summary>
+    [JsonPropertyName("AuthKey")]
+    public string? AuthKey { get; set; }
+
+    /// <summary>
+    /// Id the controller knows <see cref="AuthKey"/> by, 0 for the key compiled into the firmware and the id of the
+    /// pairing otherwise.
+    /// </summary>
+    [JsonPropertyName("KeyId")]
+    public byte KeyId { get; set; }
+
+    /// <summary>
+    /// Encrypts the datagrams instead of only signing them, requires <see cref="AuthKey"/>.
+    /// </summary>
+    [JsonPropertyName("Encrypt")]
+    public bool Encrypt { get; set; }
 }
\ No newline at end of file
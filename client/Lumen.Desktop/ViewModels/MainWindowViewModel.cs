@@ -114,9 +114,21 @@ public partial class MainWindowViewModel : StorageBasedViewModel
 
         Logging.Logger?.LogInformation("Creating new StripRunner.");
         _stripRunner?.Dispose();
+        var connectionSettings = _settings.StripSettings.ConnectionSettings;
+        DatagramEnvelope? envelope = null;
+        if (!string.IsNullOrEmpty(connectionSettings.AuthKey))
+        {
+            var authKey = Convert.FromHexString(connectionSettings.AuthKey);
+            var keyId = connectionSettings.KeyId;
+            envelope = connectionSettings.Encrypt
+                ? new DatagramEncryptor(authKey, keyId)
+                : new DatagramSigner(authKey, keyId);
+        }
+
         _stripRunner = new StripRunner(new UdpConnection(
-            _settings.StripSettings.ConnectionSettings.ControllerAddress,
-            _settings.StripSettings.ConnectionSettings.ControllerPort)
+            connectionSettings.ControllerAddress,
+            connectionSettings.ControllerPort,
+            envelope)
         );
 
 
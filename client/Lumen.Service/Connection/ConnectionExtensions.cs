@@ -0,0 +1,52 @@
+﻿using Lumen.Service.ControllerMessages;
+
+namespace Lumen.Service.Connection;
+
+public static class ConnectionExtensions
+{
+    /// <summary>
+    /// Replies usually arrive within a few milliseconds on the local network.
+    /// </summary>
+    public static readonly TimeSpan DefaultReplyTimeout = TimeSpan.FromSeconds(2);
+
+    /// <summary>
+    /// Sends <paramref name="kind"/> and waits for the reply to it, replies to other messages are skipped. Returns null
+    /// if none arrives within <paramref name="timeout"/>, the controller doesn't answer datagrams it discards before
+    /// reading them.
+    /// </summary>
+    public static async Task<ControllerReply?> Request(this IConnection connection, MessageKind kind,
+        TimeSpan timeout, CancellationToken cts = default)
+    {
+        var message = new ControllerMessage(DateTimeOffset.Now, kind);
+        await connection.SendMessage(message, cts);
+
+        using var timeoutCts = CancellationTokenSource.CreateLinkedTokenSource(cts);
+        timeoutCts.CancelAfter(timeout);
+        try
+        {
+            while (true)
+            {
+                var reply = await connection.ReceiveReply(timeoutCts.Token);
+                if (reply.InReplyTo.ToUnixTimeMilliseconds() == message.Ts.ToUnixTimeMilliseconds())
+                    return reply;
+            }
+        }
+        catch (OperationCanceledException) when (!cts.IsCancellationRequested)
+        {
+            return null;
+        }
+    }
+
+    /// <summary>
+    /// Returns true if the controller answers a <see cref="PingMessage"/>. A controller that requires signed datagrams
+    /// doesn't answer a client without the right key either.
+    /// </summary>
+    public static async Task<bool> Ping(this IConnection connection, CancellationToken cts = default) =>
+        await connection.Request(new PingMessage(), DefaultReplyTimeout, cts) is PongReply;
+
+    /// <summary>
+    /// Asks the controller for its state, null if it doesn't answer.
+    /// </summary>
+    public static async Task<StatusReply?> GetStatus(this IConnection connection, CancellationToken cts = default) =>
+        await connection.Request(new GetStatusMessage(), DefaultReplyTimeout, cts) as StatusReply;
+}
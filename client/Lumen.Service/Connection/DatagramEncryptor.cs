@@ -0,0 +1,70 @@
+﻿using System.Buffers.Binary;
+using System.Security.Cryptography;
+
+namespace Lumen.Service.Connection;
+
+/// <summary>
+/// Encrypts datagrams with ChaCha20-Poly1305, so nobody on the network can tell what the strip shows.
+/// </summary>
+public class DatagramEncryptor : DatagramEnvelope, IDisposable
+{
+    private readonly ChaCha20Poly1305 _cipher;
+    private readonly ChaCha20Poly1305 _replyCipher;
+
+    public DatagramEncryptor(byte[] key, byte keyId = 0) : base(key, keyId)
+    {
+        // Signing, encryption and replies never share a key, see lumen_proto::auth::Keys
+        _cipher = new ChaCha20Poly1305(HMACSHA256.HashData(key, "lumen encryption"u8));
+        _replyCipher = new ChaCha20Poly1305(HMACSHA256.HashData(key, "lumen reply encryption"u8));
+    }
+
+    protected override ReadOnlySpan<byte> Magic => "LMEN"u8;
+
+    protected override void SealBody(ReadOnlySpan<byte> header, ulong nonce, ReadOnlySpan<byte> datagram,
+        Span<byte> body, Span<byte> tag)
+    {
+        Span<byte> aeadNonce = stackalloc byte[12];
+        WriteAeadNonce(aeadNonce, nonce);
+
+        lock (_cipher)
+        {
+            _cipher.Encrypt(aeadNonce, datagram, body, tag, header);
+        }
+    }
+
+    protected override bool OpenBody(ReadOnlySpan<byte> header, ulong nonce, ReadOnlySpan<byte> body,
+        ReadOnlySpan<byte> tag, Span<byte> datagram)
+    {
+        Span<byte> aeadNonce = stackalloc byte[12];
+        WriteAeadNonce(aeadNonce, nonce);
+
+        try
+        {
+            lock (_replyCipher)
+            {
+                _replyCipher.Decrypt(aeadNonce, body, tag, datagram, header);
+            }
+        }
+        catch (AuthenticationTagMismatchException)
+        {
+            return false;
+        }
+
+        return true;
+    }
+
+    /// <summary>
+    /// The 96 bit nonce of ChaCha20-Poly1305, the nonce of the envelope padded with zeros.
+    /// </summary>
+    private static void WriteAeadNonce(Span<byte> aeadNonce, ulong nonce)
+    {
+        aeadNonce.Clear();
+        BinaryPrimitives.WriteUInt64LittleEndian(aeadNonce[4..], nonce);
+    }
+
+    public void Dispose()
+    {
+        _cipher.Dispose();
+        _replyCipher.Dispose();
+    }
+}
@@ -0,0 +1,177 @@
+﻿using System.Security.Cryptography;
+using Lumen.Service.Utilities;
+
+namespace Lumen.Service.Connection;
+
+/// <summary>
+/// Wraps datagrams with a pre-shared key, so the controller can reject forged and replayed ones.
+/// </summary>
+public abstract class DatagramEnvelope
+{
+    private const int KeyLength = 32;
+    private const int HeaderLength = 4 + 1 + 8;
+    protected const int TagLength = 16;
+
+    /// <summary>
+    /// Bytes an envelope adds to the plain datagram.
+    /// </summary>
+    internal const int Overhead = HeaderLength + TagLength;
+
+    /// <summary>
+    /// Bits of the nonces that count the datagrams of a run, see lumen_proto::auth.
+    /// </summary>
+    private const int CounterBits = 20;
+
+    /// <summary>
+    /// Random bits of a run, below its start time.
+    /// </summary>
+    private const int RunRandomBits = 12;
+
+    private const ulong CounterMax = (1UL << CounterBits) - 1;
+
+    private readonly byte _keyId;
+    private readonly byte[] _runClosedKey;
+    private readonly object _nonceLock = new();
+
+    /// <summary>
+    /// Upper bits of the nonces. The start time keeps them increasing across restarts of the client, the random bits
+    /// keep clients sharing the key apart.
+    /// </summary>
+    private ulong _run;
+
+    private ulong _counter;
+
+    protected DatagramEnvelope(byte[] key, byte keyId)
+    {
+        if (key.Length != KeyLength)
+            throw new ArgumentException($"The key must be {KeyLength} bytes long", nameof(key));
+
+        _keyId = keyId;
+        // RunClosed replies are signed with a key of their own, see lumen_proto::auth::seal_reply
+        _runClosedKey = HMACSHA256.HashData(key, "lumen run closed"u8);
+        StartRun();
+    }
+
+    protected abstract ReadOnlySpan<byte> Magic { get; }
+
+    /// <summary>
+    /// Magic of signed datagrams, RunClosed replies are signed no matter how the request was wrapped.
+    /// </summary>
+    protected static ReadOnlySpan<byte> SignedMagic => "LMAC"u8;
+
+    /// <summary>
+    /// Writes the wrapped datagram into <paramref name="destination"/> and returns its length.
+    /// </summary>
+    public int Seal(ReadOnlySpan<byte> datagram, Span<byte> destination)
+    {
+        var span = destination;
+        BinarySerializer.WriteBlock(ref span, Magic);
+        BinarySerializer.WriteByte(ref span, _keyId);
+        var nonce = NextNonce();
+        BinarySerializer.WriteULong(ref span, nonce);
+
+        SealBody(destination[..HeaderLength], nonce, datagram, span[..datagram.Length],
+            span.Slice(datagram.Length, TagLength));
+        return Overhead + datagram.Length;
+    }
+
+    /// <summary>
+    /// Checks a reply the controller wrapped like the request it answers, under the reply keys derived from the same
+    /// key, writes the plain reply into <paramref name="destination"/> and returns its length. Returns -1 if the reply
+    /// is forged or wasn't sealed by the controller with this key. <paramref name="nonce"/> is the one of the request.
+    /// RunClosed replies are signed with a key of their own instead.
+    /// </summary>
+    public int Open(ReadOnlySpan<byte> datagram, Span<byte> destination, out ulong nonce)
+    {
+        nonce = 0;
+        if (datagram.Length < Overhead)
+            return -1;
+
+        var span = datagram[Magic.Length..];
+        var keyId = BinarySerializer.ReadByte(ref span);
+        nonce = BinarySerializer.ReadULong(ref span);
+        if (keyId != _keyId)
+            return -1;
+
+        var length = datagram.Length - Overhead;
+        var header = datagram[..HeaderLength];
+        var body = span[..length];
+        var tag = span[length..];
+        if (datagram.StartsWith(Magic) && OpenBody(header, nonce, body, tag, destination[..length]))
+            return length;
+
+        if (!datagram.StartsWith(SignedMagic))
+            return -1;
+
+        Span<byte> computed = stackalloc byte[TagLength];
+        ComputeTag(_runClosedKey, header, body, computed);
+        if (!CryptographicOperations.FixedTimeEquals(computed, tag))
+            return -1;
+
+        body.CopyTo(destination);
+        return length;
+    }
+
+    /// <summary>
+    /// Writes the datagram into <paramref name="body"/> and the tag into <paramref name="tag"/>.
+    /// </summary>
+    protected abstract void SealBody(ReadOnlySpan<byte> header, ulong nonce, ReadOnlySpan<byte> datagram,
+        Span<byte> body, Span<byte> tag);
+
+    /// <summary>
+    /// Checks <paramref name="tag"/> with the reply key and writes the plain datagram into <paramref name="datagram"/>.
+    /// Returns false if the tag doesn't match.
+    /// </summary>
+    protected abstract bool OpenBody(ReadOnlySpan<byte> header, ulong nonce, ReadOnlySpan<byte> body,
+        ReadOnlySpan<byte> tag, Span<byte> datagram);
+
+    /// <summary>
+    /// Writes the truncated HMAC-SHA256 of <paramref name="header"/> and <paramref name="datagram"/> under
+    /// <paramref name="key"/> into <paramref name="tag"/>.
+    /// </summary>
+    protected static void ComputeTag(byte[] key, ReadOnlySpan<byte> header, ReadOnlySpan<byte> datagram,
+        Span<byte> tag)
+    {
+        using var hmac = IncrementalHash.CreateHMAC(HashAlgorithmName.SHA256, key);
+        hmac.AppendData(header);
+        hmac.AppendData(datagram);
+        Span<byte> hash = stackalloc byte[HMACSHA256.HashSizeInBytes];
+        hmac.GetHashAndReset(hash);
+        hash[..TagLength].CopyTo(tag);
+    }
+
+    /// <summary>
+    /// Starts a new run if the controller closed the one of the request with <paramref name="nonce"/>, e.g. after it
+    /// rebooted. Replies to requests of earlier runs are ignored, so replayed datagrams can't make the client start run
+    /// after run.
+    /// </summary>
+    public void CloseRun(ulong nonce)
+    {
+        lock (_nonceLock)
+        {
+            if (nonce >> CounterBits == _run)
+                StartRun();
+        }
+    }
+
+    private ulong NextNonce()
+    {
+        lock (_nonceLock)
+        {
+            if (_counter == CounterMax)
+                StartRun();
+
+            _counter++;
+            return (_run << CounterBits) | _counter;
+        }
+    }
+
+    private void StartRun()
+    {
+        var seconds = (ulong)DateTimeOffset.UtcNow.ToUnixTimeSeconds();
+        var run = (seconds << RunRandomBits) | (ulong)RandomNumberGenerator.GetInt32(1 << RunRandomBits);
+        // Runs have to increase, even within the same second
+        _run = Math.Max(run, _run + 1);
+        _counter = 0;
+    }
+}
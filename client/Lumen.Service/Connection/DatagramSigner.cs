@@ -0,0 +1,40 @@
+﻿using System.Security.Cryptography;
+
+namespace Lumen.Service.Connection;
+
+/// <summary>
+/// Signs datagrams with a truncated HMAC-SHA256, the content stays readable.
+/// </summary>
+public class DatagramSigner : DatagramEnvelope
+{
+    private readonly byte[] _key;
+    private readonly byte[] _replyKey;
+
+    public DatagramSigner(byte[] key, byte keyId = 0) : base(key, keyId)
+    {
+        _key = key;
+        // Replies are signed with a key of their own, see lumen_proto::auth::Keys
+        _replyKey = HMACSHA256.HashData(key, "lumen reply signing"u8);
+    }
+
+    protected override ReadOnlySpan<byte> Magic => SignedMagic;
+
+    protected override void SealBody(ReadOnlySpan<byte> header, ulong nonce, ReadOnlySpan<byte> datagram,
+        Span<byte> body, Span<byte> tag)
+    {
+        datagram.CopyTo(body);
+        ComputeTag(_key, header, datagram, tag);
+    }
+
+    protected override bool OpenBody(ReadOnlySpan<byte> header, ulong nonce, ReadOnlySpan<byte> body,
+        ReadOnlySpan<byte> tag, Span<byte> datagram)
+    {
+        Span<byte> computed = stackalloc byte[TagLength];
+        ComputeTag(_replyKey, header, body, computed);
+        if (!CryptographicOperations.FixedTimeEquals(computed, tag))
+            return false;
+
+        body.CopyTo(datagram);
+        return true;
+    }
+}
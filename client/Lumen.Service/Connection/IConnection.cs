@@ -5,4 +5,9 @@ namespace Lumen.Service.Connection;
 public interface IConnection : IDisposable
 {
     Task SendMessage(ControllerMessage controllerMessage, CancellationToken cts);
+
+    /// <summary>
+    /// Waits for the next reply of the controller. Datagrams that are no valid reply are skipped.
+    /// </summary>
+    Task<ControllerReply> ReceiveReply(CancellationToken cts);
 }
\ No newline at end of file
@@ -0,0 +1,127 @@
+﻿using System.Security.Cryptography;
+using Lumen.Service.ControllerMessages;
+using Lumen.Service.Utilities;
+
+namespace Lumen.Service.Connection;
+
+/// <summary>
+/// The colors of a pairing code, in the order of their symbols.
+/// </summary>
+public enum PairingColor : byte
+{
+    Red = 0,
+    Green = 1,
+    Blue = 2,
+    Yellow = 3,
+    Cyan = 4,
+    Magenta = 5,
+    White = 6,
+    Orange = 7,
+}
+
+/// <summary>
+/// The key the controller stored for this client, to be used with <see cref="DatagramSigner"/> or
+/// <see cref="DatagramEncryptor"/>.
+/// </summary>
+public record PairedKey(byte KeyId, byte[] Key);
+
+public class PairingException(string message) : Exception(message);
+
+/// <summary>
+/// Agrees on a key with the controller, so none has to be compiled into the firmware. The controller has to be in
+/// pairing mode and the connection must send plain datagrams.
+/// </summary>
+/// <remarks>
+/// The keys are exchanged with X25519. The controller then shows a code of colors on the strip, which the user enters
+/// here. Both sides commit to every color before revealing it, so someone in between can't learn the code from one
+/// side and pass it on to the other. Someone in between still learns a color with every failed attempt, so a code is
+/// only entered once. Holding the pairing button on the controller again shows a new one.
+/// </remarks>
+public class Pairing(IConnection connection)
+{
+    public const int CodeLength = 5;
+    private const int NonceLength = 16;
+
+    /// <summary>
+    /// Codes that were entered before, across all connections.
+    /// </summary>
+    private static readonly HashSet<string> EnteredCodes = [];
+
+    /// <summary>
+    /// Pairs with the controller. <paramref name="readCode"/> is called once the controller shows the code and returns
+    /// the colors the user sees, from the start of the strip on.
+    /// </summary>
+    /// <exception cref="PairingException">The controller didn't answer, the code didn't match or was entered before.
+    /// </exception>
+    public async Task<PairedKey> Pair(Func<CancellationToken, Task<PairingColor[]>> readCode, CancellationToken cts)
+    {
+        var secret = X25519.GenerateSecret();
+        var publicKey = X25519.PublicKey(secret);
+        var response = await Exchange<PairResponseReply>(new PairRequestMessage(publicKey), cts);
+
+        var key = DeriveKey(secret, publicKey, response.PublicKey);
+
+        var code = await readCode(cts);
+        if (code.Length != CodeLength)
+            throw new ArgumentException($"The code has {CodeLength} colors", nameof(readCode));
+
+        lock (EnteredCodes)
+        {
+            if (!EnteredCodes.Add(string.Join(",", code)))
+                throw new PairingException(
+                    "This code was entered before, hold the pairing button on the controller to get a new one");
+        }
+
+        for (var round = 0; round < CodeLength; round++)
+        {
+            var nonce = RandomNumberGenerator.GetBytes(NonceLength);
+            var commitment = Commitment(key, "lumen client commitment"u8, round, code[round], nonce);
+            var committed = await Exchange<PairCommittedReply>(new PairCommitMessage(commitment), cts);
+
+            var revealed = await Exchange<PairRevealedReply>(new PairRevealMessage(nonce), cts);
+            var expected = Commitment(key, "lumen controller commitment"u8, round, code[round], revealed.Nonce);
+            if (!CryptographicOperations.FixedTimeEquals(expected, committed.Commitment))
+                throw new PairingException(
+                    $"Color {round + 1} of the code doesn't match, hold the pairing button on the controller to get a new code");
+
+            if (revealed.KeyId is { } keyId)
+                return new PairedKey(keyId, key);
+        }
+
+        throw new PairingException("The controller didn't confirm the pairing");
+    }
+
+    /// <summary>
+    /// HMAC-SHA256 over both public keys under the shared secret, as computed by the controller.
+    /// </summary>
+    private static byte[] DeriveKey(byte[] secret, byte[] publicKey, byte[] controllerPublicKey)
+    {
+        var shared = X25519.ScalarMult(secret, controllerPublicKey);
+        // Public keys of low order make the shared secret zero, no matter the own secret
+        if (shared.All(b => b == 0))
+            throw new PairingException("The controller sent a public key of low order");
+
+        byte[] data = [.."lumen pairing"u8, ..publicKey, ..controllerPublicKey];
+        return HMACSHA256.HashData(shared, data);
+    }
+
+    /// <summary>
+    /// HMAC-SHA256 over the color of the code in <paramref name="round"/> and a nonce, as computed by the controller.
+    /// </summary>
+    private static byte[] Commitment(byte[] key, ReadOnlySpan<byte> label, int round, PairingColor color,
+        byte[] nonce)
+    {
+        byte[] data = [..label, (byte)round, (byte)color, ..nonce];
+        return HMACSHA256.HashData(key, data);
+    }
+
+    /// <summary>
+    /// Sends <paramref name="kind"/> and waits for its reply. The controller doesn't answer messages it discards, so a
+    /// missing reply ends the pairing.
+    /// </summary>
+    private async Task<T> Exchange<T>(MessageKind kind, CancellationToken cts) where T : ControllerReply
+    {
+        var reply = await connection.Request(kind, ConnectionExtensions.DefaultReplyTimeout, cts);
+        return reply as T ?? throw new PairingException($"The controller didn't answer {kind.Descriminator()}");
+    }
+}
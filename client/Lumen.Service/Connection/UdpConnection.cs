@@ -1,4 +1,5 @@
 ﻿using System.Net.Sockets;
+using System.Threading.Channels;
 using Lumen.Service.ControllerMessages;
 using Microsoft.Extensions.Logging;
 using static Lumen.Service.Logging;
@@ -7,12 +8,26 @@ namespace Lumen.Service.Connection;
 
 public class UdpConnection : IConnection, IDisposable
 {
+    /// <summary>
+    /// Replies nobody waits for are dropped, oldest first.
+    /// </summary>
+    private const int ReplyQueueLength = 16;
+
     private readonly UdpClient _connection;
+    private readonly MessageSequence _sequence = new();
+    private readonly DatagramEnvelope? _envelope;
+    private readonly CancellationTokenSource _receiveCts = new();
+
+    private readonly Channel<ControllerReply> _replies = Channel.CreateBounded<ControllerReply>(
+        new BoundedChannelOptions(ReplyQueueLength) { FullMode = BoundedChannelFullMode.DropOldest });
 
-    public UdpConnection(string hostname, int port)
+    /// <param name="envelope">Signs or encrypts the datagrams, they are sent as they are without one.</param>
+    public UdpConnection(string hostname, int port, DatagramEnvelope? envelope = null)
     {
+        _envelope = envelope;
         _connection = new UdpClient(port);
         _connection.Connect(hostname, port);
+        _ = ReceiveReplies(_receiveCts.Token);
     }
 
 
@@ -22,7 +37,7 @@ public class UdpConnection : IConnection, IDisposable
         var span = buffer.AsSpan();
         try
         {
-            controllerMessage.SerializeAsBytes(ref span);
+            (controllerMessage with { Sequence = _sequence.Next() }).SerializeAsBytes(ref span);
         }
         catch (ArgumentOutOfRangeException e)
         {
@@ -31,11 +46,85 @@ public class UdpConnection : IConnection, IDisposable
         }
 
         var writtenBytes = buffer.Length - span.Length;
-        return _connection.SendAsync(buffer, writtenBytes);
+        if (_envelope == null)
+            return _connection.SendAsync(buffer, writtenBytes);
+
+        var sealedDatagram = new byte[writtenBytes + DatagramEnvelope.Overhead];
+        var sealedBytes = _envelope.Seal(buffer.AsSpan(0, writtenBytes), sealedDatagram);
+        return _connection.SendAsync(sealedDatagram, sealedBytes);
+    }
+
+    public async Task<ControllerReply> ReceiveReply(CancellationToken cts = default) =>
+        await _replies.Reader.ReadAsync(cts);
+
+    /// <summary>
+    /// Reads the replies as long as the connection is open, so a <see cref="RunClosedReply"/> starts a new run of nonces
+    /// even while nobody waits for a reply.
+    /// </summary>
+    private async Task ReceiveReplies(CancellationToken cts)
+    {
+        while (!cts.IsCancellationRequested)
+        {
+            UdpReceiveResult result;
+            try
+            {
+                result = await _connection.ReceiveAsync(cts);
+            }
+            catch (Exception e) when (e is OperationCanceledException or ObjectDisposedException)
+            {
+                return;
+            }
+            catch (SocketException e)
+            {
+                // E.g. the port of the controller was unreachable for an earlier datagram
+                Logger?.LogDebug(e, "Error receiving reply");
+                continue;
+            }
+
+            var reply = OpenReply(result.Buffer);
+            if (reply != null)
+                _replies.Writer.TryWrite(reply);
+        }
+    }
+
+    private ControllerReply? OpenReply(byte[] datagram)
+    {
+        ulong nonce = 0;
+        if (_envelope != null)
+        {
+            // The controller seals replies like the requests, plain ones can be forged by anybody
+            var opened = new byte[datagram.Length];
+            var openedBytes = _envelope.Open(datagram, opened, out nonce);
+            if (openedBytes < 0)
+            {
+                Logger?.LogWarning("Discarding {0} bytes that are no sealed reply", datagram.Length);
+                return null;
+            }
+
+            datagram = opened[..openedBytes];
+        }
+
+        var reply = ControllerReply.Parse(datagram);
+        if (reply == null)
+        {
+            Logger?.LogWarning("Discarding {0} bytes that are no valid reply", datagram.Length);
+            return null;
+        }
+
+        if (reply is RunClosedReply && _envelope != null)
+        {
+            Logger?.LogInformation("The controller closed the run of nonces, starting a new one");
+            _envelope.CloseRun(nonce);
+        }
+
+        return reply;
     }
 
     public void Dispose()
     {
+        _receiveCts.Cancel();
+        _receiveCts.Dispose();
         _connection.Dispose();
+        (_envelope as IDisposable)?.Dispose();
     }
 }
\ No newline at end of file
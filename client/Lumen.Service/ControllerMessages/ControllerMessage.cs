@@ -4,11 +4,25 @@ namespace Lumen.Service.ControllerMessages;
 
 public record ControllerMessage(DateTimeOffset Ts, MessageKind MessageKind) : IByteSerializable
 {
+    /// <summary>
+    /// Every datagram starts with this magic so the controller can drop foreign traffic.
+    /// </summary>
+    internal static ReadOnlySpan<byte> Magic => "LUMN"u8;
+
+    internal const byte ProtocolVersionMajor = 1;
+    private const byte ProtocolVersionMinor = 21;
+
+    public SequenceNumber Sequence { get; init; }
+
     public void SerializeAsBytes(ref Span<byte> span)
     {
         BinarySerializer.ThrowForBigEndian();
 
+        BinarySerializer.WriteBlock(ref span, Magic);
+        BinarySerializer.WriteByte(ref span, ProtocolVersionMajor);
+        BinarySerializer.WriteByte(ref span, ProtocolVersionMinor);
         BinarySerializer.WriteLong(ref span, Ts.ToUnixTimeMilliseconds());
+        Sequence.SerializeAsBytes(ref span);
         BinarySerializer.WriteUShort(ref span, (ushort)MessageKind.Descriminator());
         MessageKind.SerializeAsBytes(ref span);
     }
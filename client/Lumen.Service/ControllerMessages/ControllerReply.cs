@@ -0,0 +1,209 @@
+﻿using Lumen.Service.Utilities;
+
+namespace Lumen.Service.ControllerMessages;
+
+public enum ReplyDescriminator : ushort
+{
+    EffectParams = 0,
+    PairResponse = 1,
+    PairCommitted = 2,
+    PairRevealed = 3,
+    PairedClients = 4,
+    Pong = 5,
+    Status = 6,
+    Ack = 7,
+    Nack = 8,
+    RunClosed = 9,
+}
+
+/// <summary>
+/// A datagram the controller sends back to answer a message.
+/// </summary>
+public abstract record ControllerReply(DateTimeOffset InReplyTo)
+{
+    /// <summary>
+    /// Parses a datagram received from the controller. Returns null for anything that is not a known reply.
+    /// </summary>
+    public static ControllerReply? Parse(ReadOnlySpan<byte> span)
+    {
+        BinarySerializer.ThrowForBigEndian();
+
+        try
+        {
+            if (!BinarySerializer.ReadBlock(ref span, ControllerMessage.Magic.Length).AsSpan()
+                    .SequenceEqual(ControllerMessage.Magic))
+                return null;
+
+            var major = BinarySerializer.ReadByte(ref span);
+            BinarySerializer.ReadByte(ref span);
+            if (major != ControllerMessage.ProtocolVersionMajor)
+                return null;
+
+            var inReplyTo = DateTimeOffset.FromUnixTimeMilliseconds(BinarySerializer.ReadLong(ref span));
+            return (ReplyDescriminator)BinarySerializer.ReadUShort(ref span) switch
+            {
+                ReplyDescriminator.EffectParams => EffectParamsReply.Parse(inReplyTo, ref span),
+                ReplyDescriminator.PairResponse => new PairResponseReply(inReplyTo,
+                    BinarySerializer.ReadBlock(ref span, PairResponseReply.PublicKeyLength)),
+                ReplyDescriminator.PairCommitted => new PairCommittedReply(inReplyTo,
+                    BinarySerializer.ReadBlock(ref span, PairCommittedReply.CommitmentLength)),
+                ReplyDescriminator.PairRevealed => PairRevealedReply.Parse(inReplyTo, ref span),
+                ReplyDescriminator.PairedClients => PairedClientsReply.Parse(inReplyTo, ref span),
+                ReplyDescriminator.Pong => new PongReply(inReplyTo),
+                ReplyDescriminator.Status => StatusReply.Parse(inReplyTo, ref span),
+                ReplyDescriminator.Ack => new AckReply(inReplyTo),
+                ReplyDescriminator.Nack => new NackReply(inReplyTo, (NackReason)BinarySerializer.ReadByte(ref span)),
+                ReplyDescriminator.RunClosed => new RunClosedReply(inReplyTo),
+                _ => null,
+            };
+        }
+        catch (ArgumentOutOfRangeException)
+        {
+            return null;
+        }
+    }
+}
+
+/// <summary>
+/// The running effect and its current parameters, both null if no effect runs.
+/// </summary>
+public record EffectParamsReply(DateTimeOffset InReplyTo, EffectId? Effect, EffectParams? Params)
+    : ControllerReply(InReplyTo)
+{
+    internal static EffectParamsReply Parse(DateTimeOffset inReplyTo, ref ReadOnlySpan<byte> span)
+    {
+        if (!BinarySerializer.ReadBool(ref span))
+            return new EffectParamsReply(inReplyTo, null, null);
+
+        var effect = (EffectId)BinarySerializer.ReadByte(ref span);
+        var speed = BinarySerializer.ReadByte(ref span);
+        var intensity = BinarySerializer.ReadByte(ref span);
+        var size = BinarySerializer.ReadByte(ref span);
+        var color = ReadRgb8(ref span);
+        var background = ReadRgb8(ref span);
+        return new EffectParamsReply(inReplyTo, effect, new EffectParams(speed, intensity, size, color, background));
+    }
+
+    private static Rgb8 ReadRgb8(ref ReadOnlySpan<byte> span) =>
+        new(BinarySerializer.ReadByte(ref span), BinarySerializer.ReadByte(ref span),
+            BinarySerializer.ReadByte(ref span));
+}
+
+/// <summary>
+/// The X25519 public key of the controller, in answer to <see cref="PairRequestMessage"/>.
+/// </summary>
+public record PairResponseReply(DateTimeOffset InReplyTo, byte[] PublicKey) : ControllerReply(InReplyTo)
+{
+    public const int PublicKeyLength = 32;
+}
+
+/// <summary>
+/// The commitment of the controller to the current color of the pairing code.
+/// </summary>
+public record PairCommittedReply(DateTimeOffset InReplyTo, byte[] Commitment) : ControllerReply(InReplyTo)
+{
+    public const int CommitmentLength = 32;
+}
+
+/// <summary>
+/// The nonce of the commitment of the controller. <paramref name="KeyId"/> is set after the last color, the client is
+/// paired then.
+/// </summary>
+public record PairRevealedReply(DateTimeOffset InReplyTo, byte[] Nonce, byte? KeyId) : ControllerReply(InReplyTo)
+{
+    public const int NonceLength = 16;
+
+    internal static PairRevealedReply Parse(DateTimeOffset inReplyTo, ref ReadOnlySpan<byte> span)
+    {
+        var nonce = BinarySerializer.ReadBlock(ref span, NonceLength);
+        byte? keyId = BinarySerializer.ReadBool(ref span) ? BinarySerializer.ReadByte(ref span) : null;
+        return new PairRevealedReply(inReplyTo, nonce, keyId);
+    }
+}
+
+/// <summary>
+/// The key ids of the clients paired with the controller.
+/// </summary>
+public record PairedClientsReply(DateTimeOffset InReplyTo, byte[] KeyIds) : ControllerReply(InReplyTo)
+{
+    internal static PairedClientsReply Parse(DateTimeOffset inReplyTo, ref ReadOnlySpan<byte> span)
+    {
+        var count = BinarySerializer.ReadByte(ref span);
+        return new PairedClientsReply(inReplyTo, BinarySerializer.ReadBlock(ref span, count));
+    }
+}
+
+/// <summary>
+/// The controller is alive, in answer to <see cref="PingMessage"/>.
+/// </summary>
+public record PongReply(DateTimeOffset InReplyTo) : ControllerReply(InReplyTo);
+
+/// <summary>
+/// The session that drives the strip. <paramref name="ClientId"/> is set if the client opened it with
+/// <see cref="OpenSessionMessage"/>, <paramref name="IsSender"/> if it is the client that asked.
+/// </summary>
+public record ActiveSession(uint? ClientId, byte Priority, bool IsSender);
+
+/// <summary>
+/// The state of the controller, in answer to <see cref="GetStatusMessage"/>. <paramref name="FreeMemory"/> is the RAM
+/// neither taken by statics nor by the stack, in bytes.
+/// </summary>
+public record StatusReply(
+    DateTimeOffset InReplyTo,
+    Version FirmwareVersion,
+    ushort LedCount,
+    ActiveSession? ActiveSession,
+    byte SessionCount,
+    TimeSpan Uptime,
+    uint FreeMemory) : ControllerReply(InReplyTo)
+{
+    internal static StatusReply Parse(DateTimeOffset inReplyTo, ref ReadOnlySpan<byte> span)
+    {
+        var version = new Version(BinarySerializer.ReadByte(ref span), BinarySerializer.ReadByte(ref span),
+            BinarySerializer.ReadByte(ref span));
+        var ledCount = BinarySerializer.ReadUShort(ref span);
+
+        ActiveSession? activeSession = null;
+        if (BinarySerializer.ReadBool(ref span))
+        {
+            uint? clientId = BinarySerializer.ReadBool(ref span) ? BinarySerializer.ReadUInt(ref span) : null;
+            activeSession = new ActiveSession(clientId, BinarySerializer.ReadByte(ref span),
+                BinarySerializer.ReadBool(ref span));
+        }
+
+        var sessionCount = BinarySerializer.ReadByte(ref span);
+        var uptime = TimeSpan.FromMilliseconds(BinarySerializer.ReadLong(ref span));
+        var freeMemory = BinarySerializer.ReadUInt(ref span);
+        return new StatusReply(inReplyTo, version, ledCount, activeSession, sessionCount, uptime, freeMemory);
+    }
+}
+
+/// <summary>
+/// The control message was handled, sent after <see cref="SetAcksMessage"/> enabled it.
+/// </summary>
+public record AckReply(DateTimeOffset InReplyTo) : ControllerReply(InReplyTo);
+
+/// <summary>
+/// Reasons why the controller discarded a control message. With <see cref="NotDriver"/> settings of the session are
+/// kept until the client drives the strip. <see cref="NotPermitted"/> answers a <see cref="RevokeClientMessage"/> for
+/// the key of another client.
+/// </summary>
+public enum NackReason : byte
+{
+    Outdated = 0,
+    PreviousEpoch = 1,
+    NotDriver = 2,
+    InvalidValue = 3,
+    NoEffect = 4,
+    NotPermitted = 5,
+}
+
+/// <summary>
+/// The control message was discarded, sent after <see cref="SetAcksMessage"/> enabled it.
+/// </summary>
+public record NackReply(DateTimeOffset InReplyTo, NackReason Reason) : ControllerReply(InReplyTo);
+
+/// <summary>
+/// The controller closed the run of nonces of the message, e.g. because it rebooted. The connection starts a new one.
+/// </summary>
+public record RunClosedReply(DateTimeOffset InReplyTo) : ControllerReply(InReplyTo);
@@ -13,6 +13,37 @@ public enum MessageDescriminator : ushort
     Empty = 0,
     KeepAlive = 1,
     LedState = 2,
+    LedRange = 3,
+    LedStateRle = 4,
+    LedStateDelta = 5,
+    LedFragment = 6,
+    SetBrightness = 7,
+    SetGamma = 8,
+    SetGammaTable = 9,
+    SetWhiteBalance = 10,
+    SetColorMatrix = 11,
+    SetColorTemperature = 12,
+    LedStateRgbw = 13,
+    SetPixelFormat = 14,
+    SetColorOrder = 15,
+    SetCurrentLimit = 16,
+    RunEffect = 17,
+    UpdateEffectParams = 18,
+    GetEffectParams = 19,
+    SetTransition = 20,
+    SetSmoothing = 21,
+    SetIdleBehavior = 22,
+    SaveScene = 23,
+    OpenSession = 24,
+    PairRequest = 25,
+    PairCommit = 26,
+    PairReveal = 27,
+    RevokeClient = 28,
+    GetPairedClients = 29,
+    Ping = 30,
+    GetStatus = 31,
+    SetAcks = 32,
+    LedFragmentRgbw = 33,
 }
 
 public record KeepAliveMessage(uint Milliseconds) : MessageKind
@@ -25,7 +56,10 @@ public record KeepAliveMessage(uint Milliseconds) : MessageKind
     }
 }
 
-public record LedStateMessage(Rgb8[] LedValues) : MessageKind
+/// <summary>
+/// A whole frame. With <paramref name="Transition"/> the controller fades to it as configured by <see cref="SetTransitionMessage"/>.
+/// </summary>
+public record LedStateMessage(Rgb8[] LedValues, bool Transition = false) : MessageKind
 {
     public override MessageDescriminator Descriminator() => MessageDescriminator.LedState;
 
@@ -37,5 +71,731 @@ public record LedStateMessage(Rgb8[] LedValues) : MessageKind
         {
             ledValue.SerializeAsBytes(ref span);
         }
+
+        BinarySerializer.WriteBool(ref span, Transition);
+    }
+}
+
+public record LedRangeMessage(ushort Offset, Rgb8[] LedValues) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.LedRange;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUShort(ref span, Offset);
+        BinarySerializer.WriteUShort(ref span, (ushort)LedValues.Length);
+
+        foreach (var ledValue in LedValues)
+        {
+            ledValue.SerializeAsBytes(ref span);
+        }
+    }
+}
+
+/// <summary>
+/// A whole frame, run-length encoded. The controller uses it as base for following deltas.
+/// </summary>
+public record LedStateRleMessage(ushort Sequence, Rgb8[] LedValues) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.LedStateRle;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUShort(ref span, Sequence);
+        RunLengthEncoding.Write(ref span, LedValues);
+    }
+}
+
+/// <summary>
+/// The XOR of the frame <see cref="BaseSequence"/> and the new frame <see cref="Sequence"/>, run-length encoded.
+/// </summary>
+public record LedStateDeltaMessage(ushort Sequence, ushort BaseSequence, Rgb8[] Delta) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.LedStateDelta;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUShort(ref span, Sequence);
+        BinarySerializer.WriteUShort(ref span, BaseSequence);
+        RunLengthEncoding.Write(ref span, Delta);
+    }
+
+    public static LedStateDeltaMessage Between(ushort sequence, ushort baseSequence, Rgb8[] baseFrame, Rgb8[] next)
+    {
+        if (baseFrame.Length != next.Length)
+            throw new ArgumentException("Delta frames must have the same length as their base frame.");
+
+        var delta = new Rgb8[next.Length];
+        for (var i = 0; i < next.Length; i++)
+        {
+            delta[i] = new Rgb8(
+                (byte)(baseFrame[i].R ^ next[i].R),
+                (byte)(baseFrame[i].G ^ next[i].G),
+                (byte)(baseFrame[i].B ^ next[i].B)
+            );
+        }
+
+        return new LedStateDeltaMessage(sequence, baseSequence, delta);
+    }
+}
+
+/// <summary>
+/// A piece of a frame that is too large for a single datagram. The controller applies the frame
+/// once all fragments with the same <see cref="FrameId"/> arrived.
+/// </summary>
+public record LedFragmentMessage(
+    ushort FrameId,
+    byte FragmentIndex,
+    byte FragmentCount,
+    ushort LedCount,
+    ushort Offset,
+    Rgb8[] LedValues
+) : MessageKind
+{
+    /// <summary>
+    /// Maximum number of LEDs per fragment, chosen so a fragment fits into a 1024 byte datagram.
+    /// </summary>
+    public const int FragmentLedMax = 300;
+
+    public override MessageDescriminator Descriminator() => MessageDescriminator.LedFragment;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUShort(ref span, FrameId);
+        BinarySerializer.WriteByte(ref span, FragmentIndex);
+        BinarySerializer.WriteByte(ref span, FragmentCount);
+        BinarySerializer.WriteUShort(ref span, LedCount);
+        BinarySerializer.WriteUShort(ref span, Offset);
+        BinarySerializer.WriteUShort(ref span, (ushort)LedValues.Length);
+
+        foreach (var ledValue in LedValues)
+        {
+            ledValue.SerializeAsBytes(ref span);
+        }
+    }
+
+    public static IEnumerable<LedFragmentMessage> Split(ushort frameId, Rgb8[] ledValues)
+    {
+        var fragmentCount = Math.Max(1, (ledValues.Length + FragmentLedMax - 1) / FragmentLedMax);
+        for (var index = 0; index < fragmentCount; index++)
+        {
+            var offset = index * FragmentLedMax;
+            var length = Math.Min(FragmentLedMax, ledValues.Length - offset);
+            yield return new LedFragmentMessage(
+                frameId,
+                (byte)index,
+                (byte)fragmentCount,
+                (ushort)ledValues.Length,
+                (ushort)offset,
+                ledValues.AsSpan(offset, length).ToArray()
+            );
+        }
+    }
+}
+
+/// <summary>
+/// Sets the master brightness of the strip. The controller fades to it over <see cref="FadeMilliseconds"/>.
+/// </summary>
+public record SetBrightnessMessage(byte Brightness, ushort FadeMilliseconds) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetBrightness;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteByte(ref span, Brightness);
+        BinarySerializer.WriteUShort(ref span, FadeMilliseconds);
+    }
+}
+
+/// <summary>
+/// Sets a gamma curve per channel. Exponents are given in hundredths, 220 is a gamma of 2.2.
+/// </summary>
+public record SetGammaMessage(ushort Red, ushort Green, ushort Blue) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetGamma;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUShort(ref span, Red);
+        BinarySerializer.WriteUShort(ref span, Green);
+        BinarySerializer.WriteUShort(ref span, Blue);
+    }
+}
+
+public enum ColorChannel : byte
+{
+    Red = 0,
+    Green = 1,
+    Blue = 2,
+    White = 3,
+}
+
+/// <summary>
+/// Replaces the gamma lookup table of a single channel. The table must have 256 entries.
+/// </summary>
+public record SetGammaTableMessage(ColorChannel Channel, byte[] Table) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetGammaTable;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        if (Table.Length != 256)
+            throw new ArgumentException("Gamma tables must have 256 entries.");
+
+        BinarySerializer.WriteByte(ref span, (byte)Channel);
+        BinarySerializer.WriteBlock(ref span, Table);
+    }
+}
+
+/// <summary>
+/// Sets per-channel gains of the output, 255 leaves a channel unchanged.
+/// </summary>
+public record SetWhiteBalanceMessage(byte Red, byte Green, byte Blue) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetWhiteBalance;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteByte(ref span, Red);
+        BinarySerializer.WriteByte(ref span, Green);
+        BinarySerializer.WriteByte(ref span, Blue);
+    }
+}
+
+/// <summary>
+/// Sets a row-major 3x3 color correction matrix in 8.8 fixed point, 256 is 1.0. Null disables the matrix.
+/// </summary>
+public record SetColorMatrixMessage(short[]? Matrix) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetColorMatrix;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        if (Matrix == null)
+        {
+            BinarySerializer.WriteBool(ref span, false);
+            return;
+        }
+
+        if (Matrix.Length != 9)
+            throw new ArgumentException("Color matrices must have 9 entries.");
+
+        BinarySerializer.WriteBool(ref span, true);
+        foreach (var value in Matrix)
+        {
+            BinarySerializer.WriteShort(ref span, value);
+        }
+    }
+}
+
+/// <summary>
+/// Sets the white balance gains to match a color temperature.
+/// </summary>
+public record SetColorTemperatureMessage(ushort Kelvin) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetColorTemperature;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUShort(ref span, Kelvin);
+    }
+}
+
+public record LedStateRgbwMessage(Rgbw8[] LedValues) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.LedStateRgbw;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUShort(ref span, (ushort)LedValues.Length);
+
+        foreach (var ledValue in LedValues)
+        {
+            ledValue.SerializeAsBytes(ref span);
+        }
+    }
+}
+
+public enum PixelFormat : byte
+{
+    Rgb = 0,
+    Rgbw = 1,
+}
+
+/// <summary>
+/// Configures the kind of LEDs on the strip. With <paramref name="ExtractWhite"/> the controller derives
+/// the white channel of RGBW strips from the common part of red, green and blue.
+/// </summary>
+public record SetPixelFormatMessage(PixelFormat Format, bool ExtractWhite) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetPixelFormat;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteByte(ref span, (byte)Format);
+        BinarySerializer.WriteBool(ref span, ExtractWhite);
+    }
+}
+
+/// <summary>
+/// Order in which a strip expects the color channels. The white channel of RGBW strips always comes last.
+/// </summary>
+public enum ColorOrder : byte
+{
+    Rgb = 0,
+    Rbg = 1,
+    Grb = 2,
+    Gbr = 3,
+    Brg = 4,
+    Bgr = 5,
+}
+
+public record SetColorOrderMessage(ColorOrder Order) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetColorOrder;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteByte(ref span, (byte)Order);
+    }
+}
+
+/// <summary>
+/// Limits the estimated current draw of the strip to <paramref name="BudgetMilliamps"/>, 0 disables the limit.
+/// <paramref name="ChannelMicroamps"/> holds the draw of a red, green, blue and white LED at full intensity,
+/// <paramref name="IdleMicroamps"/> the draw of an LED that is off.
+/// </summary>
+public record SetCurrentLimitMessage(uint BudgetMilliamps, ushort[] ChannelMicroamps, ushort IdleMicroamps)
+    : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetCurrentLimit;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        if (ChannelMicroamps.Length != 4)
+            throw new ArgumentException("The current model must have 4 channels.");
+
+        BinarySerializer.WriteUInt(ref span, BudgetMilliamps);
+        foreach (var value in ChannelMicroamps)
+        {
+            BinarySerializer.WriteUShort(ref span, value);
+        }
+
+        BinarySerializer.WriteUShort(ref span, IdleMicroamps);
+    }
+}
+
+public enum EffectId : byte
+{
+    Rainbow = 0,
+    Breathing = 1,
+    ColorWipe = 2,
+    Chase = 3,
+    Twinkle = 4,
+    Fire = 5,
+}
+
+/// <summary>
+/// Parameters of the built-in effects. <paramref name="Speed"/> is in 1/64, 64 plays an effect at its normal speed.
+/// </summary>
+public readonly record struct EffectParams(byte Speed, byte Intensity, byte Size, Rgb8 Color, Rgb8 Background)
+    : IByteSerializable
+{
+    public void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteByte(ref span, Speed);
+        BinarySerializer.WriteByte(ref span, Intensity);
+        BinarySerializer.WriteByte(ref span, Size);
+        Color.SerializeAsBytes(ref span);
+        Background.SerializeAsBytes(ref span);
+    }
+}
+
+/// <summary>
+/// Starts a built-in effect on the controller. It runs until the next frame is sent.
+/// </summary>
+public record RunEffectMessage(EffectId Id, ushort LedCount, EffectParams Params) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.RunEffect;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteByte(ref span, (byte)Id);
+        BinarySerializer.WriteUShort(ref span, LedCount);
+        Params.SerializeAsBytes(ref span);
+    }
+}
+
+public enum EffectParamId : byte
+{
+    Speed = 0,
+    Intensity = 1,
+    Size = 2,
+    Color = 3,
+    Background = 4,
+}
+
+/// <summary>
+/// A single parameter of a running effect. <see cref="Color"/> is only sent for color parameters,
+/// <see cref="Value"/> for all others.
+/// </summary>
+public readonly record struct EffectParam(EffectParamId Id, byte Value, Rgb8 Color) : IByteSerializable
+{
+    public static EffectParam Speed(byte speed) => new(EffectParamId.Speed, speed, default);
+    public static EffectParam Intensity(byte intensity) => new(EffectParamId.Intensity, intensity, default);
+    public static EffectParam Size(byte size) => new(EffectParamId.Size, size, default);
+    public static EffectParam ForegroundColor(Rgb8 color) => new(EffectParamId.Color, 0, color);
+    public static EffectParam BackgroundColor(Rgb8 color) => new(EffectParamId.Background, 0, color);
+
+    public void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteByte(ref span, (byte)Id);
+        if (Id is EffectParamId.Color or EffectParamId.Background)
+            Color.SerializeAsBytes(ref span);
+        else
+            BinarySerializer.WriteByte(ref span, Value);
+    }
+}
+
+/// <summary>
+/// Changes parameters of the running effect without restarting it.
+/// </summary>
+public record UpdateEffectParamsMessage(EffectParam[] Params) : MessageKind
+{
+    public const int ParamsMax = 8;
+
+    public override MessageDescriminator Descriminator() => MessageDescriminator.UpdateEffectParams;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        if (Params.Length > ParamsMax)
+            throw new ArgumentException($"At most {ParamsMax} parameters can be updated at once.");
+
+        BinarySerializer.WriteByte(ref span, (byte)Params.Length);
+        foreach (var param in Params)
+        {
+            param.SerializeAsBytes(ref span);
+        }
+    }
+}
+
+/// <summary>
+/// Asks the controller to reply with the running effect and its parameters, see <see cref="EffectParamsReply"/>.
+/// </summary>
+public record GetEffectParamsMessage : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.GetEffectParams;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+    }
+}
+
+/// <summary>
+/// Easing curve of the controller side transitions.
+/// </summary>
+public enum Easing : byte
+{
+    Linear = 0,
+    EaseIn = 1,
+    EaseOut = 2,
+    EaseInOut = 3,
+}
+
+/// <summary>
+/// Configures the fades of frames sent with the transition flag, effect changes and the blackout after a keep alive timeout.
+/// A duration of 0 switches without a fade.
+/// </summary>
+public record SetTransitionMessage(ushort DurationMillis, Easing Easing) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetTransition;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUShort(ref span, DurationMillis);
+        BinarySerializer.WriteByte(ref span, (byte)Easing);
+    }
+}
+
+/// <summary>
+/// Smooths the output toward the newest frame, for streams with low or uneven frame rates.
+/// <paramref name="Factor"/> is the share of the remaining difference that is kept every 10 ms, in 1/256.
+/// A factor of 0 shows every frame exactly as sent.
+/// </summary>
+public record SetSmoothingMessage(byte Factor) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetSmoothing;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteByte(ref span, Factor);
+    }
+}
+
+public enum IdleActionId : byte
+{
+    Black = 0,
+    HoldLastFrame = 1,
+    RunEffect = 2,
+    RestoreScene = 3,
+}
+
+/// <summary>
+/// What the strip shows once the keep alives stopped. <see cref="Effect"/>, <see cref="LedCount"/> and
+/// <see cref="Params"/> are only sent for <see cref="IdleActionId.RunEffect"/>.
+/// </summary>
+public readonly record struct IdleAction(IdleActionId Id, EffectId Effect, ushort LedCount, EffectParams Params)
+    : IByteSerializable
+{
+    public static IdleAction Black() => new(IdleActionId.Black, default, 0, default);
+    public static IdleAction HoldLastFrame() => new(IdleActionId.HoldLastFrame, default, 0, default);
+    public static IdleAction RunEffect(EffectId effect, ushort ledCount, EffectParams @params) =>
+        new(IdleActionId.RunEffect, effect, ledCount, @params);
+    public static IdleAction RestoreScene() => new(IdleActionId.RestoreScene, default, 0, default);
+
+    public void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteByte(ref span, (byte)Id);
+        if (Id is not IdleActionId.RunEffect)
+            return;
+
+        BinarySerializer.WriteByte(ref span, (byte)Effect);
+        BinarySerializer.WriteUShort(ref span, LedCount);
+        Params.SerializeAsBytes(ref span);
+    }
+}
+
+/// <summary>
+/// Configures what happens when the keep alives stop: the strip waits <paramref name="GraceMillis"/> after the
+/// last keep alive ran out, then fades to <paramref name="Action"/> over <paramref name="FadeMillis"/>.
+/// The controller keeps the behavior across reboots.
+/// </summary>
+public record SetIdleBehaviorMessage(ushort GraceMillis, ushort FadeMillis, IdleAction Action) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetIdleBehavior;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUShort(ref span, GraceMillis);
+        BinarySerializer.WriteUShort(ref span, FadeMillis);
+        Action.SerializeAsBytes(ref span);
+    }
+}
+
+/// <summary>
+/// Saves the last frame sent as the scene shown by <see cref="IdleAction.RestoreScene"/>, kept across reboots.
+/// </summary>
+public record SaveSceneMessage : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SaveScene;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+    }
+}
+
+/// <summary>
+/// Opens a session on the controller. The live session with the highest <paramref name="Priority"/> drives the strip,
+/// the others take over once its lease runs out. Keep alives renew the lease for <paramref name="LeaseMillis"/>.
+/// <paramref name="ClientId"/> identifies the client across restarts that change its address.
+/// </summary>
+public record OpenSessionMessage(uint ClientId, byte Priority, ushort LeaseMillis) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.OpenSession;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUInt(ref span, ClientId);
+        BinarySerializer.WriteByte(ref span, Priority);
+        BinarySerializer.WriteUShort(ref span, LeaseMillis);
+    }
+}
+
+/// <summary>
+/// Starts pairing with the X25519 public key of the client, see <see cref="Connection.Pairing"/>.
+/// The controller answers with <see cref="PairResponseReply"/> and shows the code on the strip.
+/// </summary>
+public record PairRequestMessage(byte[] PublicKey) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.PairRequest;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteBlock(ref span, PublicKey);
+    }
+}
+
+/// <summary>
+/// Commits to the current color of the pairing code, answered with <see cref="PairCommittedReply"/>.
+/// </summary>
+public record PairCommitMessage(byte[] Commitment) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.PairCommit;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteBlock(ref span, Commitment);
+    }
+}
+
+/// <summary>
+/// Reveals the nonce of the last commitment, answered with <see cref="PairRevealedReply"/>.
+/// </summary>
+public record PairRevealMessage(byte[] Nonce) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.PairReveal;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteBlock(ref span, Nonce);
+    }
+}
+
+/// <summary>
+/// Removes the key of a paired client. Has to be signed or encrypted with the pre-shared key or the key that is
+/// removed, the controller answers with <see cref="AckReply"/> or <see cref="NackReply"/>.
+/// </summary>
+public record RevokeClientMessage(byte KeyId) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.RevokeClient;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteByte(ref span, KeyId);
+    }
+}
+
+/// <summary>
+/// Asks the controller for the key ids of the paired clients, see <see cref="PairedClientsReply"/>.
+/// Has to be signed or encrypted.
+/// </summary>
+public record GetPairedClientsMessage : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.GetPairedClients;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+    }
+}
+
+/// <summary>
+/// Asks the controller whether it is alive, answered with <see cref="PongReply"/>. A controller that requires signed
+/// datagrams only answers signed or encrypted ones, in the same envelope.
+/// </summary>
+public record PingMessage : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.Ping;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+    }
+}
+
+/// <summary>
+/// Asks the controller for its state, answered with <see cref="StatusReply"/>. A controller that requires signed
+/// datagrams only answers signed or encrypted ones, in the same envelope.
+/// </summary>
+public record GetStatusMessage : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.GetStatus;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+    }
+}
+
+/// <summary>
+/// Makes the controller answer control messages with <see cref="AckReply"/> or <see cref="NackReply"/>. Frames are
+/// never acknowledged.
+/// </summary>
+public record SetAcksMessage(bool Enabled) : MessageKind
+{
+    public override MessageDescriminator Descriminator() => MessageDescriminator.SetAcks;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteBool(ref span, Enabled);
+    }
+}
+
+/// <summary>
+/// A piece of an RGBW frame that is too large for a single datagram, the RGBW counterpart of
+/// <see cref="LedFragmentMessage"/>.
+/// </summary>
+public record LedFragmentRgbwMessage(
+    ushort FrameId,
+    byte FragmentIndex,
+    byte FragmentCount,
+    ushort LedCount,
+    ushort Offset,
+    Rgbw8[] LedValues
+) : MessageKind
+{
+    /// <summary>
+    /// Maximum number of LEDs per fragment, the same number of bytes as
+    /// <see cref="LedFragmentMessage.FragmentLedMax"/> RGB values.
+    /// </summary>
+    public const int FragmentLedMax = 225;
+
+    public override MessageDescriminator Descriminator() => MessageDescriminator.LedFragmentRgbw;
+
+    public override void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUShort(ref span, FrameId);
+        BinarySerializer.WriteByte(ref span, FragmentIndex);
+        BinarySerializer.WriteByte(ref span, FragmentCount);
+        BinarySerializer.WriteUShort(ref span, LedCount);
+        BinarySerializer.WriteUShort(ref span, Offset);
+        BinarySerializer.WriteUShort(ref span, (ushort)LedValues.Length);
+
+        foreach (var ledValue in LedValues)
+        {
+            ledValue.SerializeAsBytes(ref span);
+        }
+    }
+
+    public static IEnumerable<LedFragmentRgbwMessage> Split(ushort frameId, Rgbw8[] ledValues)
+    {
+        var fragmentCount = Math.Max(1, (ledValues.Length + FragmentLedMax - 1) / FragmentLedMax);
+        for (var index = 0; index < fragmentCount; index++)
+        {
+            var offset = index * FragmentLedMax;
+            var length = Math.Min(FragmentLedMax, ledValues.Length - offset);
+            yield return new LedFragmentRgbwMessage(
+                frameId,
+                (byte)index,
+                (byte)fragmentCount,
+                (ushort)ledValues.Length,
+                (ushort)offset,
+                ledValues.AsSpan(offset, length).ToArray()
+            );
+        }
+    }
+}
+
+internal static class RunLengthEncoding
+{
+    /// <summary>
+    /// Writes the LED count followed by (run length, color) pairs.
+    /// </summary>
+    public static void Write(ref Span<byte> span, Rgb8[] ledValues)
+    {
+        BinarySerializer.WriteUShort(ref span, (ushort)ledValues.Length);
+
+        var index = 0;
+        while (index < ledValues.Length)
+        {
+            var value = ledValues[index];
+            var runLength = 1;
+            while (runLength < byte.MaxValue && index + runLength < ledValues.Length &&
+                   ledValues[index + runLength] == value)
+            {
+                runLength++;
+            }
+
+            BinarySerializer.WriteByte(ref span, (byte)runLength);
+            value.SerializeAsBytes(ref span);
+            index += runLength;
+        }
     }
 }
\ No newline at end of file
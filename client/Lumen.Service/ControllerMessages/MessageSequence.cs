@@ -0,0 +1,28 @@
+﻿using System.Security.Cryptography;
+using Lumen.Service.Utilities;
+
+namespace Lumen.Service.ControllerMessages;
+
+/// <summary>
+/// Position of a message in the stream of this client. An epoch of 0 marks a message without a sequence.
+/// </summary>
+public readonly record struct SequenceNumber(uint Epoch, uint Number) : IByteSerializable
+{
+    public void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteUInt(ref span, Epoch);
+        BinarySerializer.WriteUInt(ref span, Number);
+    }
+}
+
+/// <summary>
+/// Numbers the messages of a connection. The epoch is picked at random, so the controller can tell a
+/// restart of the client from reordered datagrams.
+/// </summary>
+public class MessageSequence
+{
+    private readonly uint _epoch = (uint)RandomNumberGenerator.GetInt32(1, int.MaxValue);
+    private int _number = -1;
+
+    public SequenceNumber Next() => new(_epoch, unchecked((uint)Interlocked.Increment(ref _number)));
+}
@@ -0,0 +1,14 @@
+﻿using Lumen.Service.Utilities;
+
+namespace Lumen.Service.ControllerMessages;
+
+public readonly record struct Rgbw8(byte R, byte G, byte B, byte W) : IByteSerializable
+{
+    public void SerializeAsBytes(ref Span<byte> span)
+    {
+        BinarySerializer.WriteByte(ref span, R);
+        BinarySerializer.WriteByte(ref span, G);
+        BinarySerializer.WriteByte(ref span, B);
+        BinarySerializer.WriteByte(ref span, W);
+    }
+}
\ No newline at end of file
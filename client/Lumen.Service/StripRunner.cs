@@ -9,6 +9,7 @@ namespace Lumen.Service;
 public class StripRunner : IDisposable
 {
     private readonly IConnection _connection;
+    private ushort _frameId;
 
     public StripRunner(IConnection connection)
     {
@@ -42,14 +43,32 @@ public class StripRunner : IDisposable
             var stripFrame = effect.GetEffectValue();
             if (stripFrame != null)
             {
-                var msg = new ControllerMessage(DateTimeOffset.Now, new LedStateMessage(stripFrame.Leds));
-                _ = _connection.SendMessage(msg, cts);
+                PostFrame(stripFrame.Leds, cts);
             }
 
             await periodicTimer.WaitForNextTickAsync(cts);
         }
     }
 
+    /// <summary>
+    /// Sends small frames as a single message and splits larger ones into fragments.
+    /// </summary>
+    private void PostFrame(Rgb8[] leds, CancellationToken cts)
+    {
+        var now = DateTimeOffset.Now;
+        if (leds.Length <= LedFragmentMessage.FragmentLedMax)
+        {
+            _ = _connection.SendMessage(new ControllerMessage(now, new LedStateMessage(leds)), cts);
+            return;
+        }
+
+        _frameId++;
+        foreach (var fragment in LedFragmentMessage.Split(_frameId, leds))
+        {
+            _ = _connection.SendMessage(new ControllerMessage(now, fragment), cts);
+        }
+    }
+
     private async Task PostKeepalive(TimeSpan postEvery, TimeSpan keepAliveFor, CancellationToken cts)
     {
         var periodicTimer = new PeriodicTimer(postEvery);
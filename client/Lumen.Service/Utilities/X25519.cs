@@ -0,0 +1,85 @@
+﻿using System.Numerics;
+using System.Security.Cryptography;
+
+namespace Lumen.Service.Utilities;
+
+/// <summary>
+/// X25519 as in RFC 7748, .NET has no implementation of its own. The ladder runs on <see cref="BigInteger"/> and is
+/// not constant time, which is acceptable for keys that only live for one pairing.
+/// </summary>
+public static class X25519
+{
+    public const int KeyLength = 32;
+
+    private static readonly BigInteger P = BigInteger.Pow(2, 255) - 19;
+    private static readonly BigInteger A24 = 121665;
+    private static readonly byte[] BasePoint = [9, ..new byte[KeyLength - 1]];
+
+    /// <summary>
+    /// A random secret key.
+    /// </summary>
+    public static byte[] GenerateSecret() => RandomNumberGenerator.GetBytes(KeyLength);
+
+    public static byte[] PublicKey(byte[] secret) => ScalarMult(secret, BasePoint);
+
+    /// <summary>
+    /// Multiplies the point <paramref name="u"/> with the clamped <paramref name="scalar"/>.
+    /// </summary>
+    public static byte[] ScalarMult(byte[] scalar, byte[] u)
+    {
+        if (scalar.Length != KeyLength || u.Length != KeyLength)
+            throw new ArgumentException($"Keys must be {KeyLength} bytes long");
+
+        var k = (byte[])scalar.Clone();
+        k[0] &= 248;
+        k[31] &= 127;
+        k[31] |= 64;
+        var kInt = new BigInteger(k, isUnsigned: true);
+
+        var uBytes = (byte[])u.Clone();
+        uBytes[31] &= 127;
+        var x1 = new BigInteger(uBytes, isUnsigned: true) % P;
+
+        BigInteger x2 = 1, z2 = 0, x3 = x1, z3 = 1;
+        var swap = false;
+        for (var t = 254; t >= 0; t--)
+        {
+            var bit = !(kInt >> t).IsEven;
+            if (swap != bit)
+            {
+                (x2, x3) = (x3, x2);
+                (z2, z3) = (z3, z2);
+            }
+
+            swap = bit;
+
+            var a = x2 + z2;
+            var aa = a * a % P;
+            var b = x2 - z2;
+            var bb = b * b % P;
+            var e = aa - bb;
+            var c = x3 + z3;
+            var d = x3 - z3;
+            var da = d * a % P;
+            var cb = c * b % P;
+            x3 = Mod((da + cb) * (da + cb));
+            z3 = Mod(x1 * Mod((da - cb) * (da - cb)));
+            x2 = Mod(aa * bb);
+            z2 = Mod(e * (aa + A24 * e));
+        }
+
+        if (swap)
+            (x2, z2) = (x3, z3);
+
+        var result = Mod(x2 * BigInteger.ModPow(Mod(z2), P - 2, P));
+        var bytes = new byte[KeyLength];
+        result.TryWriteBytes(bytes, out _, isUnsigned: true);
+        return bytes;
+    }
+
+    private static BigInteger Mod(BigInteger value)
+    {
+        var result = value % P;
+        return result.Sign < 0 ? result + P : result;
+    }
+}
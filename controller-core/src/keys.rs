@@ -0,0 +1,17 @@
+//! Who may manage the keys the controller accepts datagrams with.
+
+/// Id of the key compiled into the firmware. Paired clients get the ids above it.
+pub const PRE_SHARED_KEY_ID: u8 = 0;
+
+/// Whether a request signed with `key_id` may revoke the key `revoked`.
+///
+/// The pre-shared key revokes any key, paired clients only their own. While `pairing_open`, the
+/// pairing button was held at the controller, so any client with a key may revoke any other one.
+/// That way the key of a lost client can be revoked without a pre-shared key.
+pub fn may_revoke(key_id: Option<u8>, revoked: u8, pairing_open: bool) -> bool {
+    match key_id {
+        None => false,
+        Some(_) if pairing_open => true,
+        Some(key_id) => key_id == PRE_SHARED_KEY_ID || key_id == revoked,
+    }
+}
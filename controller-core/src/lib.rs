@@ -0,0 +1,11 @@
+//! Logic of the Lumen controller that doesn't depend on the hardware, kept apart from the
+//! firmware so it can be tested on the host with `cargo test`.
+//!
+//! The wire format lives in `lumen-proto`, this crate only holds what the controller does with
+//! the messages.
+
+#![no_std]
+
+pub mod keys;
+pub mod output;
+pub mod session;
@@ -0,0 +1,107 @@
+use super::Rgbw16;
+
+/// Estimated current draw of a frame before and after limiting, in milliamps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CurrentEstimate {
+    pub requested_ma: u32,
+    pub output_ma: u32,
+}
+
+/// Scales frames down whose estimated current draw exceeds the budget of the power supply.
+#[derive(Debug, Clone)]
+pub struct CurrentLimit {
+    /// Maximum current of the whole strip, 0 disables the limit.
+    budget_ma: u32,
+    /// Draw of each channel at full intensity in the order red, green, blue, white.
+    channel_ua: [u16; 4],
+    /// Draw of an LED that is off.
+    idle_ua: u16,
+}
+
+impl CurrentLimit {
+    /// Starts without a budget and a model of typical 5050 LEDs.
+    pub const fn new() -> Self {
+        Self {
+            budget_ma: 0,
+            channel_ua: [20_000; 4],
+            idle_ua: 1000,
+        }
+    }
+
+    /// Replaces the budget and the model of the LEDs. A budget is rejected if no channel draws
+    /// any current, frames couldn't be scaled to fit into it.
+    pub fn configure(
+        &mut self,
+        budget_ma: u32,
+        channel_ua: [u16; 4],
+        idle_ua: u16,
+    ) -> Result<(), InvalidCurrentLimit> {
+        if budget_ma != 0 && channel_ua == [0; 4] {
+            return Err(InvalidCurrentLimit);
+        }
+        self.budget_ma = budget_ma;
+        self.channel_ua = channel_ua;
+        self.idle_ua = idle_ua;
+        Ok(())
+    }
+
+    /// Estimates the current draw of the frame and scales it down to fit into the budget.
+    pub fn limit(&self, frame: &mut [Rgbw16]) -> CurrentEstimate {
+        let idle_ua = self.idle_ua as u64 * frame.len() as u64;
+        let color_ua = frame.iter().map(|&rgbw| self.color_ua(rgbw)).sum::<u64>();
+        let requested_ma = to_ma(idle_ua + color_ua);
+
+        let budget_ua = self.budget_ma as u64 * 1000;
+        if self.budget_ma == 0 || idle_ua + color_ua <= budget_ua {
+            return CurrentEstimate {
+                requested_ma,
+                output_ma: requested_ma,
+            };
+        }
+
+        // The idle draw can't be reduced, only the part of the budget left over is spread on the colors
+        let available_ua = budget_ua.saturating_sub(idle_ua);
+        // The idle draw alone exceeds the budget. A frame without color draw only gets here this
+        // way, so the division below can't be by zero.
+        if available_ua == 0 {
+            frame.fill(Rgbw16::default());
+            return CurrentEstimate {
+                requested_ma,
+                output_ma: to_ma(idle_ua),
+            };
+        }
+        let factor = available_ua * 0x10000 / color_ua;
+        for rgbw in frame.iter_mut() {
+            *rgbw = rgbw.map(|c| ((c as u64 * factor) >> 16) as u16);
+        }
+
+        let color_ua = frame.iter().map(|&rgbw| self.color_ua(rgbw)).sum::<u64>();
+        CurrentEstimate {
+            requested_ma,
+            output_ma: to_ma(idle_ua + color_ua),
+        }
+    }
+
+    fn color_ua(&self, Rgbw16 { r, g, b, w }: Rgbw16) -> u64 {
+        [r, g, b, w]
+            .iter()
+            .zip(self.channel_ua)
+            .map(|(&c, ua)| c as u64 * ua as u64 / u16::MAX as u64)
+            .sum()
+    }
+}
+
+impl Default for CurrentLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Settings of [`CurrentLimit::configure`] the limit can't work with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidCurrentLimit;
+
+fn to_ma(ua: u64) -> u32 {
+    (ua / 1000).min(u32::MAX as u64) as u32
+}
@@ -0,0 +1,39 @@
+//! Stages of the controller's output pipeline that don't depend on the hardware.
+
+pub mod current_limit;
+pub mod smoothing;
+
+use lumen_proto::rgbw8::Rgbw8;
+
+/// A color with 16 bits per channel, the precision the pipeline works at until it is dithered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rgbw16 {
+    pub r: u16,
+    pub g: u16,
+    pub b: u16,
+    pub w: u16,
+}
+
+impl Rgbw16 {
+    /// Applies `f` to every channel.
+    pub fn map(self, f: impl Fn(u16) -> u16) -> Self {
+        Self {
+            r: f(self.r),
+            g: f(self.g),
+            b: f(self.b),
+            w: f(self.w),
+        }
+    }
+}
+
+impl From<Rgbw8> for Rgbw16 {
+    fn from(Rgbw8 { r, g, b, w }: Rgbw8) -> Self {
+        let widen = |c: u8| c as u16 * 257;
+        Self {
+            r: widen(r),
+            g: widen(g),
+            b: widen(b),
+            w: widen(w),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use arrayvec::ArrayVec;
+
+use lumen_proto::LED_MAX;
+
+use super::Rgbw16;
+
+/// Smoothing applied until a client configures its own. Off, so frames are shown exactly as sent
+/// unless the client asks for smoothing.
+pub const DEFAULT_SMOOTHING_FACTOR: u8 = 0;
+
+/// The interval the smoothing factor is given for.
+const REFERENCE_INTERVAL_MICROS: u64 = 10_000;
+/// Refreshes further apart than this are smoothed as if they were this far apart,
+/// so a frame arriving after a pause doesn't skip the smoothing.
+const MAX_STEP_MICROS: u64 = 50_000;
+
+/// Exponential smoothing of the output toward the newest frame.
+/// The output is refreshed at a fixed rate while it moves, so frames that arrive at a low or
+/// uneven rate blend into each other instead of stepping.
+pub struct Smoothing {
+    /// Share of the remaining difference kept every 10 ms, in 1/256.
+    factor: u8,
+    state: ArrayVec<Rgbw16, LED_MAX>,
+    last_update_micros: u64,
+    settled: bool,
+}
+
+impl Smoothing {
+    pub const fn new() -> Self {
+        Self {
+            factor: DEFAULT_SMOOTHING_FACTOR,
+            state: ArrayVec::new_const(),
+            last_update_micros: 0,
+            settled: true,
+        }
+    }
+
+    /// Sets the smoothing factor, 0 shows every frame exactly as it is.
+    pub fn configure(&mut self, factor: u8) {
+        self.factor = factor;
+    }
+
+    /// Returns true while the output hasn't reached the newest frame.
+    pub fn is_active(&self) -> bool {
+        !self.settled
+    }
+
+    /// Moves the smoothed output toward `frame` by the time since the last call at
+    /// `now_micros` and replaces `frame` with it. Frames with a different length are taken over
+    /// immediately.
+    pub fn apply(&mut self, frame: &mut [Rgbw16], now_micros: u64) {
+        let elapsed = now_micros
+            .saturating_sub(self.last_update_micros)
+            .min(MAX_STEP_MICROS);
+        self.last_update_micros = now_micros;
+
+        if self.factor == 0 || self.state.len() != frame.len() {
+            self.state.clear();
+            self.state.extend(frame.iter().copied());
+            self.settled = true;
+            return;
+        }
+
+        // The kept share for the elapsed time, so the result doesn't depend on the refresh rate
+        let intervals = elapsed as f32 / REFERENCE_INTERVAL_MICROS as f32;
+        let keep = libm::powf(self.factor as f32 / 256.0, intervals);
+        let keep = (keep * 65536.0) as i64;
+
+        let mut settled = true;
+        for (current, target) in self.state.iter_mut().zip(frame.iter_mut()) {
+            let step = |current: u16, target: u16| {
+                let difference = target as i64 - current as i64;
+                let remaining = (difference * keep) >> 16;
+                // Rounding would keep small differences forever, step at least once
+                let remaining = match remaining == difference && difference != 0 {
+                    true => difference - difference.signum(),
+                    false => remaining,
+                };
+                (target as i64 - remaining) as u16
+            };
+            *current = Rgbw16 {
+                r: step(current.r, target.r),
+                g: step(current.g, target.g),
+                b: step(current.b, target.b),
+                w: step(current.w, target.w),
+            };
+            settled &= *current == *target;
+            *target = *current;
+        }
+        self.settled = settled;
+    }
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
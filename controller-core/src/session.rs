@@ -0,0 +1,352 @@
+//! The clients of the controller and which of them drives the strip.
+//!
+//! Sessions are kept by the address of the client, which is generic so they can be tested on the
+//! host. Times are milliseconds since an arbitrary start, e.g. the boot of the controller.
+
+use core::num::NonZeroU32;
+
+use arrayvec::ArrayVec;
+
+use lumen_proto::{message_id::MessageId, reply::NackReason, sequence::Sequence, Timestamp};
+
+/// Number of clients the controller keeps track of at once.
+pub const SESSION_MAX: usize = 4;
+/// Priority of clients that never opened a session explicitly.
+const DEFAULT_PRIORITY: u8 = 0;
+/// Lease of clients that never opened a session explicitly.
+const DEFAULT_LEASE_MS: u64 = 2000;
+/// The last position of a message id is forgotten after this time, so a client whose clock
+/// jumped back is not locked out for good.
+const STALE_AFTER_MS: u64 = 10_000;
+/// Number of earlier epochs a session remembers, late datagrams of any of them are discarded.
+const RETIRED_EPOCH_MAX: usize = 4;
+/// Number of message ids a session remembers the last position of, more than there are ids.
+const LAST_SEEN_MAX: usize = 64;
+
+/// Reasons why a message was discarded before it was handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Rejection {
+    /// The sender already sent a newer message of the same kind.
+    Outdated,
+    /// The message was sent before the sender restarted.
+    PreviousEpoch,
+    /// All sessions are taken by other clients.
+    SessionsFull,
+    /// Another session drives the strip.
+    NotDriver,
+}
+
+impl Rejection {
+    /// The reason told to clients that enabled acks, `None` if the client has no session to
+    /// remember that it did.
+    pub fn nack_reason(self) -> Option<NackReason> {
+        match self {
+            Rejection::Outdated => Some(NackReason::Outdated),
+            Rejection::PreviousEpoch => Some(NackReason::PreviousEpoch),
+            Rejection::SessionsFull => None,
+            Rejection::NotDriver => Some(NackReason::NotDriver),
+        }
+    }
+}
+
+/// Running count of discarded messages, one counter per reason.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RejectionCounters {
+    pub outdated: u32,
+    pub previous_epoch: u32,
+    pub sessions_full: u32,
+    pub not_driver: u32,
+}
+
+impl RejectionCounters {
+    /// Increments the counter belonging to the given rejection.
+    pub fn record(&mut self, rejection: Rejection) {
+        let counter = match rejection {
+            Rejection::Outdated => &mut self.outdated,
+            Rejection::PreviousEpoch => &mut self.previous_epoch,
+            Rejection::SessionsFull => &mut self.sessions_full,
+            Rejection::NotDriver => &mut self.not_driver,
+        };
+        *counter = counter.saturating_add(1);
+    }
+}
+
+/// Where a message stands in the stream of its sender. Clients that don't number their messages
+/// are ordered by their timestamps.
+#[derive(Debug, Clone, Copy)]
+enum Position {
+    Sequence(u32),
+    Timestamp(Timestamp),
+}
+
+impl Position {
+    fn is_after(self, last: Position) -> bool {
+        match (self, last) {
+            (Position::Sequence(number), Position::Sequence(last)) => {
+                Sequence::is_after(number, last)
+            }
+            (Position::Timestamp(timestamp), Position::Timestamp(last)) => timestamp > last,
+            // The client switched between sequence numbers and timestamps, so there is no order
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LastSeen {
+    position: Position,
+    /// When the message was accepted, old entries are treated as absent.
+    at_ms: u64,
+}
+
+/// How [`Session::accept`] took a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Accepted {
+    InOrder,
+    /// The message is the first of a new epoch, the client restarted.
+    Restarted,
+}
+
+/// A client sending messages to the controller.
+#[derive(Debug, Clone)]
+pub struct Session<A> {
+    pub address: A,
+    /// Set by clients that opened the session explicitly.
+    pub client_id: Option<u32>,
+    pub priority: u8,
+    lease_ms: u64,
+    expires_ms: u64,
+    opened_ms: u64,
+    /// Epoch of the sequence numbers the client currently sends.
+    epoch: Option<NonZeroU32>,
+    /// Epochs of the runs before the last restarts of the client, oldest first. Late datagrams
+    /// of them are discarded.
+    retired_epochs: ArrayVec<NonZeroU32, RETIRED_EPOCH_MAX>,
+    last_seen: ArrayVec<(MessageId, LastSeen), LAST_SEEN_MAX>,
+    /// Smoothing factor the client configured, applied while it drives the strip.
+    pub smoothing: u8,
+    /// Set if the client wants its control messages answered with `Ack` or `Nack`.
+    pub acks: bool,
+}
+
+impl<A: Copy + PartialEq> Session<A> {
+    fn new(address: A, smoothing: u8, now_ms: u64) -> Self {
+        Self {
+            address,
+            client_id: None,
+            priority: DEFAULT_PRIORITY,
+            lease_ms: DEFAULT_LEASE_MS,
+            expires_ms: now_ms + DEFAULT_LEASE_MS,
+            opened_ms: now_ms,
+            epoch: None,
+            retired_epochs: ArrayVec::new_const(),
+            last_seen: ArrayVec::new(),
+            smoothing,
+            acks: false,
+        }
+    }
+
+    /// Extends the lease of the session.
+    pub fn renew(&mut self, now_ms: u64) {
+        self.expires_ms = now_ms.saturating_add(self.lease_ms);
+    }
+
+    /// Checks that a message is newer than the last one of its kind and remembers its position.
+    /// A new epoch means the client restarted, so its history starts over.
+    pub fn accept(
+        &mut self,
+        message_id: MessageId,
+        timestamp: Timestamp,
+        sequence: Option<Sequence>,
+        now_ms: u64,
+    ) -> Result<Accepted, Rejection> {
+        let mut accepted = Accepted::InOrder;
+        if let Some(Sequence { epoch, .. }) = sequence {
+            if self.retired_epochs.contains(&epoch) {
+                return Err(Rejection::PreviousEpoch);
+            }
+            if self.epoch != Some(epoch) {
+                if let Some(current) = self.epoch {
+                    if self.retired_epochs.is_full() {
+                        self.retired_epochs.remove(0);
+                    }
+                    self.retired_epochs.push(current);
+                    accepted = Accepted::Restarted;
+                }
+                self.epoch = Some(epoch);
+                self.last_seen.clear();
+            }
+        }
+
+        // Fragments of one frame arrive in any order, they are ordered by frame id instead
+        if matches!(
+            message_id,
+            MessageId::LedFragment | MessageId::LedFragmentRgbw
+        ) {
+            return Ok(accepted);
+        }
+
+        let position = match sequence {
+            Some(sequence) => Position::Sequence(sequence.number),
+            None => Position::Timestamp(timestamp),
+        };
+        let seen = LastSeen {
+            position,
+            at_ms: now_ms,
+        };
+        match self.last_seen.iter_mut().find(|(id, _)| *id == message_id) {
+            Some((_, last)) => {
+                let is_stale = now_ms.saturating_sub(last.at_ms) >= STALE_AFTER_MS;
+                if !is_stale && !position.is_after(last.position) {
+                    return Err(Rejection::Outdated);
+                }
+                *last = seen;
+            }
+            // There are fewer message ids than entries
+            None => self.last_seen.push((message_id, seen)),
+        }
+        Ok(accepted)
+    }
+}
+
+/// The clients of the controller. Only the live session with the highest priority drives the
+/// strip, on equal priority the one that drives it already keeps doing so.
+#[derive(Debug, Clone)]
+pub struct Sessions<A> {
+    sessions: ArrayVec<Session<A>, SESSION_MAX>,
+    /// Address of the session that drives the strip.
+    driver: Option<A>,
+    /// Smoothing factor of new sessions.
+    default_smoothing: u8,
+}
+
+impl<A: Copy + PartialEq> Sessions<A> {
+    pub const fn new(default_smoothing: u8) -> Self {
+        Self {
+            sessions: ArrayVec::new_const(),
+            driver: None,
+            default_smoothing,
+        }
+    }
+
+    /// Closes the sessions whose lease ran out and returns their addresses.
+    pub fn expire(&mut self, now_ms: u64) -> ArrayVec<A, SESSION_MAX> {
+        let mut expired = ArrayVec::new();
+        self.sessions.retain(|session| {
+            let live = session.expires_ms > now_ms;
+            if !live {
+                expired.push(session.address);
+            }
+            live
+        });
+        expired
+    }
+
+    /// Returns the session of `address`, opening one with the default priority if there is none.
+    /// Returns `None` if all sessions are taken.
+    pub fn get_or_open(&mut self, address: A, now_ms: u64) -> Option<&mut Session<A>> {
+        let index = match self.position(address) {
+            Some(index) => index,
+            None => {
+                let session = Session::new(address, self.default_smoothing, now_ms);
+                self.sessions.try_push(session).ok()?;
+                self.sessions.len() - 1
+            }
+        };
+        Some(&mut self.sessions[index])
+    }
+
+    /// Opens a session explicitly. A client that restarted with a different address gets its
+    /// session back by its id.
+    pub fn open(
+        &mut self,
+        address: A,
+        client_id: u32,
+        priority: u8,
+        lease_ms: u64,
+        now_ms: u64,
+    ) -> Option<&mut Session<A>> {
+        if let Some(previous) = self.client_position(client_id) {
+            // The implicit session of the new address is replaced by the previous one
+            if let Some(implicit) = self.position(address).filter(|&i| i != previous) {
+                self.sessions.swap_remove(implicit);
+            }
+            let index = self.client_position(client_id)?;
+            let session = &mut self.sessions[index];
+            if self.driver == Some(session.address) {
+                self.driver = Some(address);
+            }
+            session.address = address;
+        }
+
+        let session = self.get_or_open(address, now_ms)?;
+        session.client_id = Some(client_id);
+        session.priority = priority;
+        session.lease_ms = lease_ms;
+        session.renew(now_ms);
+        Some(session)
+    }
+
+    /// Picks the session that drives the strip.
+    /// Returns the new driver if it changed.
+    pub fn elect(&mut self) -> Option<&Session<A>> {
+        let current = self.driver.and_then(|driver| self.position(driver));
+        let best = self
+            .sessions
+            .iter()
+            .enumerate()
+            .max_by_key(|(index, session)| {
+                // Ties go to the current driver, then to the oldest session
+                let is_current = current == Some(*index);
+                (
+                    session.priority,
+                    is_current,
+                    core::cmp::Reverse(session.opened_ms),
+                )
+            })
+            .map(|(index, _)| index);
+
+        if best == current {
+            // The driver is still the same or there is no session at all
+            if best.is_none() {
+                self.driver = None;
+            }
+            return None;
+        }
+
+        let session = &self.sessions[best?];
+        self.driver = Some(session.address);
+        Some(session)
+    }
+
+    /// Returns true if the session of `address` drives the strip.
+    pub fn is_driver(&self, address: A) -> bool {
+        self.driver == Some(address)
+    }
+
+    /// The session that drives the strip.
+    pub fn driver(&self) -> Option<&Session<A>> {
+        self.sessions
+            .iter()
+            .find(|session| Some(session.address) == self.driver)
+    }
+
+    /// Number of open sessions.
+    pub fn count(&self) -> usize {
+        self.sessions.len()
+    }
+
+    fn position(&self, address: A) -> Option<usize> {
+        self.sessions
+            .iter()
+            .position(|session| session.address == address)
+    }
+
+    fn client_position(&self, client_id: u32) -> Option<usize> {
+        self.sessions
+            .iter()
+            .position(|session| session.client_id == Some(client_id))
+    }
+}
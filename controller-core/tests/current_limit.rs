@@ -0,0 +1,79 @@
+use controller_core::output::current_limit::{CurrentEstimate, CurrentLimit, InvalidCurrentLimit};
+use controller_core::output::Rgbw16;
+
+const WHITE: Rgbw16 = Rgbw16 {
+    r: u16::MAX,
+    g: u16::MAX,
+    b: u16::MAX,
+    w: 0,
+};
+
+/// 20 mA per channel and 1 mA per LED that is off.
+fn limit(budget_ma: u32) -> CurrentLimit {
+    let mut limit = CurrentLimit::new();
+    limit.configure(budget_ma, [20_000; 4], 1000).unwrap();
+    limit
+}
+
+#[test]
+fn zero_budget_disables_the_limit() {
+    let mut frame = [WHITE; 10];
+    let estimate = limit(0).limit(&mut frame);
+    assert_eq!(
+        estimate,
+        CurrentEstimate {
+            requested_ma: 610,
+            output_ma: 610,
+        }
+    );
+    assert_eq!(frame, [WHITE; 10]);
+}
+
+#[test]
+fn frame_within_budget_is_unchanged() {
+    let mut frame = [WHITE; 10];
+    let estimate = limit(610).limit(&mut frame);
+    assert_eq!(estimate.output_ma, 610);
+    assert_eq!(frame, [WHITE; 10]);
+}
+
+#[test]
+fn scales_frame_into_budget() {
+    let mut frame = [WHITE; 10];
+    let estimate = limit(310).limit(&mut frame);
+    assert_eq!(estimate.requested_ma, 610);
+    assert!(estimate.output_ma <= 310);
+    assert!(estimate.output_ma >= 300);
+    // Scaled evenly, to about half of the colors
+    assert!(frame.iter().all(|&rgbw| rgbw == frame[0]));
+    assert!(frame[0].r <= u16::MAX / 2);
+}
+
+#[test]
+fn black_frame_over_budget_stays_black() {
+    let mut frame = [Rgbw16::default(); 10];
+    let estimate = limit(5).limit(&mut frame);
+    assert_eq!(
+        estimate,
+        CurrentEstimate {
+            requested_ma: 10,
+            output_ma: 10,
+        }
+    );
+    assert_eq!(frame, [Rgbw16::default(); 10]);
+}
+
+#[test]
+fn budget_below_idle_draw_turns_frame_off() {
+    let mut frame = [WHITE; 10];
+    let estimate = limit(5).limit(&mut frame);
+    assert_eq!(estimate.output_ma, 10);
+    assert_eq!(frame, [Rgbw16::default(); 10]);
+}
+
+#[test]
+fn rejects_budget_without_channel_currents() {
+    let mut limit = limit(500);
+    assert_eq!(limit.configure(500, [0; 4], 1000), Err(InvalidCurrentLimit));
+    assert_eq!(limit.configure(0, [0; 4], 1000), Ok(()));
+}
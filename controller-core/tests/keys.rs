@@ -0,0 +1,27 @@
+use controller_core::keys::{may_revoke, PRE_SHARED_KEY_ID};
+
+#[test]
+fn pre_shared_key_revokes_any_key() {
+    assert!(may_revoke(Some(PRE_SHARED_KEY_ID), 1, false));
+    assert!(may_revoke(Some(PRE_SHARED_KEY_ID), 2, false));
+}
+
+#[test]
+fn paired_clients_revoke_only_their_own_key() {
+    assert!(may_revoke(Some(1), 1, false));
+    assert!(!may_revoke(Some(1), 2, false));
+    assert!(!may_revoke(Some(1), PRE_SHARED_KEY_ID, false));
+}
+
+#[test]
+fn paired_clients_revoke_other_keys_while_pairing_is_open() {
+    // Without a pre-shared key, the key of a lost client is revoked after holding the button
+    assert!(may_revoke(Some(2), 1, true));
+    assert!(!may_revoke(Some(2), 1, false));
+}
+
+#[test]
+fn unsigned_requests_revoke_nothing() {
+    assert!(!may_revoke(None, 1, false));
+    assert!(!may_revoke(None, 1, true));
+}
@@ -0,0 +1,226 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::num::NonZeroU32;
+
+use controller_core::session::{Accepted, Rejection, Session, Sessions, SESSION_MAX};
+use lumen_proto::message_id::MessageId;
+use lumen_proto::sequence::Sequence;
+use lumen_proto::Timestamp;
+
+const START_MS: u64 = 1000;
+
+fn address(port: u16) -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), port))
+}
+
+fn sequence(epoch: u32, number: u32) -> Option<Sequence> {
+    Some(Sequence {
+        epoch: NonZeroU32::new(epoch).unwrap(),
+        number,
+    })
+}
+
+fn driver(sessions: &Sessions<SocketAddr>) -> Option<SocketAddr> {
+    sessions.driver().map(|session| session.address)
+}
+
+#[test]
+fn first_client_drives_the_strip() {
+    let mut sessions = Sessions::new(0);
+    sessions.get_or_open(address(1), START_MS).unwrap();
+    assert_eq!(
+        sessions.elect().map(|session| session.address),
+        Some(address(1))
+    );
+
+    // An equal priority doesn't take over
+    sessions.get_or_open(address(2), START_MS + 10).unwrap();
+    assert!(sessions.elect().is_none());
+    assert!(sessions.is_driver(address(1)));
+    assert_eq!(sessions.count(), 2);
+}
+
+#[test]
+fn higher_priority_preempts_the_driver() {
+    let mut sessions = Sessions::new(0);
+    sessions.get_or_open(address(1), START_MS).unwrap();
+    sessions.elect();
+
+    sessions.open(address(2), 7, 5, 10_000, START_MS).unwrap();
+    assert_eq!(
+        sessions.elect().map(|session| session.address),
+        Some(address(2))
+    );
+    assert!(!sessions.is_driver(address(1)));
+
+    // The lower priority keeps its session, but doesn't get the strip back
+    sessions.get_or_open(address(1), START_MS + 10).unwrap();
+    assert!(sessions.elect().is_none());
+    assert_eq!(driver(&sessions), Some(address(2)));
+}
+
+#[test]
+fn client_keeps_its_session_across_addresses() {
+    let mut sessions = Sessions::new(0);
+    sessions.open(address(1), 7, 5, 10_000, START_MS).unwrap();
+    sessions.elect();
+
+    // Client 7 restarted on another port
+    sessions.get_or_open(address(2), START_MS).unwrap();
+    sessions.open(address(2), 7, 5, 10_000, START_MS).unwrap();
+    assert!(sessions.elect().is_none());
+    assert!(sessions.is_driver(address(2)));
+    assert_eq!(sessions.count(), 1);
+}
+
+#[test]
+fn expired_lease_hands_the_strip_over() {
+    let mut sessions = Sessions::new(0);
+    sessions.open(address(1), 7, 5, 10_000, START_MS).unwrap();
+    sessions.get_or_open(address(2), START_MS).unwrap();
+    sessions.elect();
+
+    // The default lease of the implicit session runs out first
+    assert_eq!(sessions.expire(START_MS + 3000).as_slice(), [address(2)]);
+    assert!(sessions.is_driver(address(1)));
+
+    // Keep alives renew the lease
+    sessions
+        .get_or_open(address(1), START_MS + 9000)
+        .unwrap()
+        .renew(START_MS + 9000);
+    assert!(sessions.expire(START_MS + 11_000).is_empty());
+
+    assert_eq!(sessions.expire(START_MS + 19_000).as_slice(), [address(1)]);
+    assert!(sessions.elect().is_none());
+    assert_eq!(driver(&sessions), None);
+
+    sessions.get_or_open(address(2), START_MS + 20_000).unwrap();
+    assert_eq!(
+        sessions.elect().map(|session| session.address),
+        Some(address(2))
+    );
+}
+
+#[test]
+fn sessions_are_limited() {
+    let mut sessions = Sessions::new(0);
+    for port in 0..SESSION_MAX as u16 {
+        assert!(sessions.get_or_open(address(port), START_MS).is_some());
+    }
+    assert!(sessions.get_or_open(address(100), START_MS).is_none());
+}
+
+#[test]
+fn orders_messages_by_sequence_number() {
+    let mut sessions = Sessions::new(0);
+    let session = sessions.get_or_open(address(1), START_MS).unwrap();
+    let id = MessageId::LedState;
+    let ts = Timestamp::new(0);
+
+    let accept =
+        |session: &mut Session<SocketAddr>, sequence| session.accept(id, ts, sequence, START_MS);
+    assert_eq!(
+        accept(session, sequence(1, u32::MAX - 1)),
+        Ok(Accepted::InOrder)
+    );
+    assert_eq!(
+        accept(session, sequence(1, u32::MAX)),
+        Ok(Accepted::InOrder)
+    );
+    // Wraps around
+    assert_eq!(accept(session, sequence(1, 0)), Ok(Accepted::InOrder));
+    assert_eq!(
+        accept(session, sequence(1, u32::MAX)),
+        Err(Rejection::Outdated)
+    );
+
+    // The client restarted and counts from the start again
+    assert_eq!(accept(session, sequence(2, 5)), Ok(Accepted::Restarted));
+    assert_eq!(
+        accept(session, sequence(1, 1)),
+        Err(Rejection::PreviousEpoch)
+    );
+    assert_eq!(accept(session, sequence(2, 4)), Err(Rejection::Outdated));
+}
+
+#[test]
+fn orders_messages_by_timestamp() {
+    let mut sessions = Sessions::new(0);
+    let session = sessions.get_or_open(address(1), START_MS).unwrap();
+    let id = MessageId::LedState;
+
+    assert!(session
+        .accept(id, Timestamp::new(100), None, START_MS)
+        .is_ok());
+    assert_eq!(
+        session.accept(id, Timestamp::new(50), None, START_MS + 1000),
+        Err(Rejection::Outdated)
+    );
+    // The clock of the client jumped back, the old position is forgotten after a while
+    assert!(session
+        .accept(id, Timestamp::new(50), None, START_MS + 10_000)
+        .is_ok());
+
+    // Fragments are ordered by their frame id instead
+    let fragment = MessageId::LedFragment;
+    assert!(session
+        .accept(fragment, Timestamp::new(1), None, START_MS)
+        .is_ok());
+    assert!(session
+        .accept(fragment, Timestamp::new(1), None, START_MS)
+        .is_ok());
+}
+
+#[test]
+fn remembers_several_restarts() {
+    let mut sessions = Sessions::new(0);
+    let session = sessions.get_or_open(address(1), START_MS).unwrap();
+    let id = MessageId::LedState;
+    let ts = Timestamp::new(0);
+
+    for epoch in 1..=4 {
+        assert!(session
+            .accept(id, ts, sequence(epoch, 10), START_MS)
+            .is_ok());
+    }
+    // Late datagrams of every earlier run are discarded, not only of the one before
+    for epoch in 1..=3 {
+        assert_eq!(
+            session.accept(id, ts, sequence(epoch, 11), START_MS),
+            Err(Rejection::PreviousEpoch)
+        );
+    }
+    assert_eq!(
+        session.accept(id, ts, sequence(4, 11), START_MS),
+        Ok(Accepted::InOrder)
+    );
+}
+
+#[test]
+fn interleaved_clients_keep_their_own_order() {
+    let mut sessions = Sessions::new(0);
+    sessions.get_or_open(address(1), START_MS).unwrap();
+    sessions.get_or_open(address(2), START_MS).unwrap();
+    let id = MessageId::LedState;
+    let ts = Timestamp::new(0);
+    let mut accept = |port, sequence| {
+        sessions
+            .get_or_open(address(port), START_MS)
+            .unwrap()
+            .accept(id, ts, sequence, START_MS)
+    };
+
+    // Both clients started with different epochs and counters, neither restarts the other
+    assert_eq!(accept(1, sequence(7, 100)), Ok(Accepted::InOrder));
+    assert_eq!(accept(2, sequence(9, 5)), Ok(Accepted::InOrder));
+    assert_eq!(accept(1, sequence(7, 101)), Ok(Accepted::InOrder));
+    assert_eq!(accept(2, sequence(9, 6)), Ok(Accepted::InOrder));
+    assert_eq!(accept(1, sequence(7, 100)), Err(Rejection::Outdated));
+    assert_eq!(accept(2, sequence(9, 6)), Err(Rejection::Outdated));
+
+    // A restart of one client doesn't affect the other
+    assert_eq!(accept(2, sequence(3, 0)), Ok(Accepted::Restarted));
+    assert_eq!(accept(1, sequence(7, 102)), Ok(Accepted::InOrder));
+    assert_eq!(accept(2, sequence(9, 7)), Err(Rejection::PreviousEpoch));
+    assert_eq!(accept(2, sequence(3, 1)), Ok(Accepted::InOrder));
+}
@@ -0,0 +1,81 @@
+use controller_core::output::smoothing::Smoothing;
+use controller_core::output::Rgbw16;
+
+const BLACK: Rgbw16 = Rgbw16 {
+    r: 0,
+    g: 0,
+    b: 0,
+    w: 0,
+};
+
+const WHITE: Rgbw16 = Rgbw16 {
+    r: u16::MAX,
+    g: u16::MAX,
+    b: u16::MAX,
+    w: u16::MAX,
+};
+
+#[test]
+fn is_off_by_default() {
+    let mut smoothing = Smoothing::new();
+    let mut frame = [BLACK; 4];
+    smoothing.apply(&mut frame, 0);
+
+    frame = [WHITE; 4];
+    smoothing.apply(&mut frame, 10_000);
+    assert_eq!(frame, [WHITE; 4]);
+    assert!(!smoothing.is_active());
+}
+
+#[test]
+fn eases_toward_newest_frame() {
+    let mut smoothing = Smoothing::new();
+    smoothing.configure(128);
+    let mut frame = [BLACK; 4];
+    smoothing.apply(&mut frame, 0);
+
+    // Half of the difference is kept every 10 ms
+    frame = [WHITE; 4];
+    smoothing.apply(&mut frame, 10_000);
+    assert!(smoothing.is_active());
+    assert!(frame[0].r.abs_diff(u16::MAX / 2) < 2);
+
+    let mut now = 10_000;
+    while smoothing.is_active() {
+        now += 10_000;
+        frame = [WHITE; 4];
+        smoothing.apply(&mut frame, now);
+    }
+    assert_eq!(frame, [WHITE; 4]);
+}
+
+#[test]
+fn result_does_not_depend_on_refresh_rate() {
+    let mut slow = Smoothing::new();
+    let mut fast = Smoothing::new();
+    slow.configure(200);
+    fast.configure(200);
+    slow.apply(&mut [BLACK], 0);
+    fast.apply(&mut [BLACK], 0);
+
+    let mut slow_frame = [WHITE];
+    slow.apply(&mut slow_frame, 20_000);
+    let mut fast_frame = [WHITE];
+    for now in (2_000..=20_000).step_by(2_000) {
+        fast_frame = [WHITE];
+        fast.apply(&mut fast_frame, now);
+    }
+    assert!(slow_frame[0].r.abs_diff(fast_frame[0].r) < 16);
+}
+
+#[test]
+fn takes_over_frames_of_different_length() {
+    let mut smoothing = Smoothing::new();
+    smoothing.configure(255);
+    smoothing.apply(&mut [BLACK; 4], 0);
+
+    let mut frame = [WHITE; 5];
+    smoothing.apply(&mut frame, 10_000);
+    assert_eq!(frame, [WHITE; 5]);
+    assert!(!smoothing.is_active());
+}
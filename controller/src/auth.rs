@@ -0,0 +1,373 @@
+use crate::storage::{Slot, Storage};
+use arrayvec::ArrayVec;
+use controller_core::keys::PRE_SHARED_KEY_ID;
+use defmt::error;
+use defmt::info;
+use lumen_proto::auth::{nonce_run, seal_reply, Envelope, Key, Keys, Replay, ReplayGuard};
+use lumen_proto::bytestreamreader::{ByteStreamReader, MessageDeserializer};
+use lumen_proto::bytestreamwriter::{ByteStreamWriter, MessageSerializer};
+use lumen_proto::pairing::PAIRED_CLIENTS_MAX;
+use lumen_proto::reply::ControllerReply;
+use lumen_proto::{DeserializationError, DeserializationResult, SerializationResult};
+
+/// Reasons why a datagram was discarded before it was parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum AuthFailure {
+    /// The datagram is not signed, but signatures are required for it.
+    Unsigned,
+    /// The datagram was signed with a key the controller doesn't have.
+    UnknownKey(u8),
+    /// The tag doesn't match, the datagram was forged or corrupted.
+    BadTag,
+    /// The encrypted datagram doesn't decrypt, it was forged or corrupted.
+    BadCiphertext,
+    /// The nonce was used before or is too old.
+    Replayed,
+    /// The run of the nonce ended, the client is asked to start a new one.
+    RunClosed,
+    /// The datagram is too short to hold a signature.
+    Malformed,
+}
+
+/// Running count of discarded datagrams, one counter per reason.
+#[derive(Debug, Clone, Copy, Default, defmt::Format)]
+pub struct AuthFailureCounters {
+    pub unsigned: u32,
+    pub unknown_key: u32,
+    pub bad_tag: u32,
+    pub bad_ciphertext: u32,
+    pub replayed: u32,
+    pub run_closed: u32,
+    pub malformed: u32,
+}
+
+impl AuthFailureCounters {
+    /// Increments the counter belonging to the given failure.
+    pub fn record(&mut self, failure: AuthFailure) {
+        let counter = match failure {
+            AuthFailure::Unsigned => &mut self.unsigned,
+            AuthFailure::UnknownKey(_) => &mut self.unknown_key,
+            AuthFailure::BadTag => &mut self.bad_tag,
+            AuthFailure::BadCiphertext => &mut self.bad_ciphertext,
+            AuthFailure::Replayed => &mut self.replayed,
+            AuthFailure::RunClosed => &mut self.run_closed,
+            AuthFailure::Malformed => &mut self.malformed,
+        };
+        *counter = counter.saturating_add(1);
+    }
+}
+
+/// Lowest nonce that is accepted after a reboot, the [`ReplayGuard::mark`] of the key.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NonceFloor(pub u64);
+
+impl MessageDeserializer for NonceFloor {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        Ok(NonceFloor(reader.u64()?))
+    }
+}
+
+impl MessageSerializer for NonceFloor {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u64(self.0)
+    }
+}
+
+/// The key of a paired client.
+#[derive(Debug, Clone)]
+pub struct PairedClient {
+    pub key_id: u8,
+    pub key: Key,
+    /// Lowest nonce of the client that is accepted after a reboot.
+    pub floor: NonceFloor,
+}
+
+/// The keys of the paired clients, stored in the flash.
+#[derive(Debug, Clone, Default)]
+pub struct PairedClients(pub ArrayVec<PairedClient, PAIRED_CLIENTS_MAX>);
+
+impl MessageDeserializer for PairedClients {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let count = reader.u8()?;
+        if count as usize > PAIRED_CLIENTS_MAX {
+            return Err(DeserializationError::InvalidValue);
+        }
+
+        let mut clients = ArrayVec::new();
+        for _ in 0..count {
+            clients.push(PairedClient {
+                key_id: reader.u8()?,
+                key: reader.array()?,
+                floor: NonceFloor::deserialize_from(reader)?,
+            });
+        }
+        Ok(PairedClients(clients))
+    }
+}
+
+impl MessageSerializer for PairedClients {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u8(self.0.len() as u8)?;
+        for client in &self.0 {
+            writer.u8(client.key_id)?;
+            writer.bytes(&client.key)?;
+            client.floor.serialize_into(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// A datagram that passed the checks of the [`Authenticator`].
+pub struct Authenticated<'a> {
+    /// How the datagram was wrapped, `None` for plain datagrams.
+    pub seal: Option<Seal>,
+    pub datagram: &'a [u8],
+    /// The datagram is authentic, but its run of nonces ended, so it may be replayed. It is only
+    /// answered with `RunClosed`, never handled.
+    pub run_closed: bool,
+}
+
+impl Authenticated<'_> {
+    /// Id of the key the datagram was signed or encrypted with, `None` for plain datagrams.
+    pub fn key_id(&self) -> Option<u8> {
+        self.seal.as_ref().map(|seal| seal.key_id)
+    }
+}
+
+/// The envelope of an authenticated datagram. The reply to the datagram is wrapped the same
+/// way. The keys are copied, so a reply can still be sealed after its key was revoked.
+#[derive(Clone)]
+pub struct Seal {
+    pub key_id: u8,
+    nonce: u64,
+    keys: Keys,
+    encrypted: bool,
+}
+
+impl Seal {
+    /// Serializes `reply` into `buffer` in the envelope of the request and returns the number of
+    /// bytes written.
+    pub fn seal(&self, reply: &ControllerReply, buffer: &mut [u8]) -> SerializationResult<usize> {
+        seal_reply(
+            reply,
+            &self.keys,
+            self.key_id,
+            self.nonce,
+            self.encrypted,
+            buffer,
+        )
+    }
+}
+
+/// A key the controller accepts datagrams with.
+struct KeyState {
+    key_id: u8,
+    keys: Keys,
+    guard: ReplayGuard,
+    floor: NonceFloor,
+}
+
+impl KeyState {
+    fn new(key_id: u8, key: &Key, floor: NonceFloor) -> Self {
+        Self {
+            key_id,
+            keys: Keys::new(key),
+            guard: ReplayGuard::new(floor.0),
+            floor,
+        }
+    }
+
+    /// Raises the floor to the end of the newest client run. Returns true if it changed.
+    fn raise_floor(&mut self) -> bool {
+        let mark = self.guard.mark();
+        if mark <= self.floor.0 {
+            return false;
+        }
+
+        info!(
+            "Client run {} of key {} started, raising nonce floor",
+            nonce_run(mark) - 1,
+            self.key_id
+        );
+        self.floor = NonceFloor(mark);
+        true
+    }
+}
+
+/// Checks the signatures of received datagrams and decrypts encrypted ones.
+///
+/// Every key has a [`ReplayGuard`] of its own, which only lives in RAM. To keep datagrams from
+/// being replayed after a reboot, the end of the newest client run is stored in the flash, which
+/// closes every run seen before. It only changes when a client starts a new run, so the flash
+/// doesn't wear out. Clients still sending in a closed run are answered with `RunClosed` and
+/// start a new one.
+pub struct Authenticator {
+    pre_shared: Option<KeyState>,
+    paired: ArrayVec<KeyState, PAIRED_CLIENTS_MAX>,
+    /// Unsigned datagrams are discarded if set.
+    required: bool,
+}
+
+impl Authenticator {
+    pub fn new(
+        pre_shared: Option<Key>,
+        required: bool,
+        floor: NonceFloor,
+        paired: PairedClients,
+    ) -> Self {
+        Self {
+            pre_shared: pre_shared.map(|key| KeyState::new(PRE_SHARED_KEY_ID, &key, floor)),
+            paired: paired
+                .0
+                .iter()
+                .map(|client| KeyState::new(client.key_id, &client.key, client.floor))
+                .collect(),
+            required,
+        }
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    /// Returns true if the controller has a key to check datagrams with.
+    pub fn has_keys(&self) -> bool {
+        self.pre_shared.is_some() || !self.paired.is_empty()
+    }
+
+    /// Returns true if no more clients can be paired.
+    pub fn is_full(&self) -> bool {
+        self.paired.is_full()
+    }
+
+    /// Verifies the signature of a datagram or decrypts it in place, and returns the plain
+    /// datagram inside of it. Plain datagrams are let through with `accept_plain` even if
+    /// signatures are required.
+    pub fn check<'a>(
+        &mut self,
+        datagram: &'a mut [u8],
+        accept_plain: bool,
+        storage: &mut Storage,
+    ) -> Result<Authenticated<'a>, AuthFailure> {
+        let (seal, datagram) = match Envelope::open(datagram) {
+            Ok(Envelope::Plain(_)) if self.required && !accept_plain => {
+                return Err(AuthFailure::Unsigned)
+            }
+            Ok(Envelope::Plain(datagram)) => {
+                return Ok(Authenticated {
+                    seal: None,
+                    datagram,
+                    run_closed: false,
+                })
+            }
+            Ok(Envelope::Signed(signed)) => {
+                let keys = &self.key(signed.key_id)?.keys;
+                if !signed.verify(&keys.signing) {
+                    return Err(AuthFailure::BadTag);
+                }
+                let seal = Seal {
+                    key_id: signed.key_id,
+                    nonce: signed.nonce,
+                    keys: keys.clone(),
+                    encrypted: false,
+                };
+                (seal, signed.datagram)
+            }
+            Ok(Envelope::Encrypted(encrypted)) => {
+                let keys = &self.key(encrypted.key_id)?.keys;
+                let seal = Seal {
+                    key_id: encrypted.key_id,
+                    nonce: encrypted.nonce,
+                    keys: keys.clone(),
+                    encrypted: true,
+                };
+                let datagram = encrypted
+                    .decrypt(&keys.encryption)
+                    .ok_or(AuthFailure::BadCiphertext)?;
+                (seal, datagram)
+            }
+            Err(_) => return Err(AuthFailure::Malformed),
+        };
+
+        let key_id = seal.key_id;
+        let key = self.key(key_id)?;
+        let run_closed = match key.guard.accept(seal.nonce) {
+            Ok(()) => false,
+            Err(Replay::Seen) => return Err(AuthFailure::Replayed),
+            Err(Replay::RunClosed) => true,
+        };
+        if key.raise_floor() {
+            self.save_floor(key_id, storage);
+        }
+
+        Ok(Authenticated {
+            seal: Some(seal),
+            datagram,
+            run_closed,
+        })
+    }
+
+    /// Stores the key of a newly paired client and returns its id.
+    /// Returns `None` if no more clients can be paired.
+    pub fn pair(&mut self, key: Key, storage: &mut Storage) -> Option<u8> {
+        let key_id = (PRE_SHARED_KEY_ID + 1..=u8::MAX)
+            .find(|&key_id| self.paired.iter().all(|paired| paired.key_id != key_id))?;
+        self.paired
+            .try_push(KeyState::new(key_id, &key, NonceFloor::default()))
+            .ok()?;
+        self.save_paired(storage);
+        Some(key_id)
+    }
+
+    /// Removes the key of a paired client. Returns false if no client was paired with the id.
+    pub fn revoke(&mut self, key_id: u8, storage: &mut Storage) -> bool {
+        let Some(index) = self.paired.iter().position(|key| key.key_id == key_id) else {
+            return false;
+        };
+        self.paired.remove(index);
+        self.save_paired(storage);
+        true
+    }
+
+    pub fn paired_key_ids(&self) -> ArrayVec<u8, PAIRED_CLIENTS_MAX> {
+        self.paired.iter().map(|key| key.key_id).collect()
+    }
+
+    fn key(&mut self, key_id: u8) -> Result<&mut KeyState, AuthFailure> {
+        let key = match key_id {
+            PRE_SHARED_KEY_ID => self.pre_shared.as_mut(),
+            _ => self.paired.iter_mut().find(|key| key.key_id == key_id),
+        };
+        key.ok_or(AuthFailure::UnknownKey(key_id))
+    }
+
+    fn save_floor(&mut self, key_id: u8, storage: &mut Storage) {
+        match (key_id, &self.pre_shared) {
+            (PRE_SHARED_KEY_ID, Some(pre_shared)) => {
+                if let Err(e) = storage.store(Slot::NonceFloor, &pre_shared.floor) {
+                    error!("Failed to save nonce floor: {}", e);
+                }
+            }
+            _ => self.save_paired(storage),
+        }
+    }
+
+    fn save_paired(&self, storage: &mut Storage) {
+        let clients = self
+            .paired
+            .iter()
+            .map(|key| PairedClient {
+                key_id: key.key_id,
+                key: key.keys.signing,
+                floor: key.floor,
+            })
+            .collect();
+        if let Err(e) = storage.store(Slot::PairedClients, &PairedClients(clients)) {
+            error!("Failed to save paired clients: {}", e);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Measures signing and encryption of datagrams on the controller itself. Enabled with the
+//! `bench-envelope` feature, the results are logged once at boot.
+
+use arrayvec::ArrayVec;
+use defmt::info;
+use embassy_time::{Duration, Instant};
+use lumen_proto::auth::{encrypt, sign, Envelope, Keys};
+use lumen_proto::message_kind::MessageKind;
+use lumen_proto::rgb8::Rgb8;
+use lumen_proto::{ControllerMessage, Timestamp};
+
+/// The strip the envelopes have to keep up with.
+const LED_COUNT: usize = 400;
+const FRAMES_PER_SECOND: u64 = 60;
+const ITERATIONS: u32 = 100;
+
+pub fn run() {
+    let keys = Keys::new(&[7; 32]);
+    let mut led_values = ArrayVec::new();
+    for i in 0..LED_COUNT {
+        led_values.push(Rgb8 {
+            r: i as u8,
+            g: (i >> 8) as u8,
+            b: 0x80,
+        });
+    }
+    let message = ControllerMessage {
+        timestamp: Timestamp::new(0),
+        sequence: None,
+        kind: MessageKind::LedState {
+            led_values,
+            transition: false,
+        },
+    };
+
+    let mut buffer = [0; 2048];
+    let mut received = [0; 2048];
+
+    let len = message.encode(&mut buffer).unwrap();
+    report("decode", len, || {
+        ControllerMessage::decode(&buffer[..len]).unwrap();
+    });
+
+    let len = sign(&message, &keys.signing, 0, 1, &mut buffer).unwrap();
+    report("sign", len, || {
+        sign(&message, &keys.signing, 0, 1, &mut received).unwrap();
+    });
+    report("verify", len, || {
+        received[..len].copy_from_slice(&buffer[..len]);
+        let Ok(Envelope::Signed(signed)) = Envelope::open(&mut received[..len]) else {
+            unreachable!();
+        };
+        assert!(signed.verify(&keys.signing));
+    });
+
+    let len = encrypt(&message, &keys.encryption, 0, 1, &mut buffer).unwrap();
+    report("encrypt", len, || {
+        encrypt(&message, &keys.encryption, 0, 1, &mut received).unwrap();
+    });
+    report("decrypt", len, || {
+        received[..len].copy_from_slice(&buffer[..len]);
+        let Ok(Envelope::Encrypted(encrypted)) = Envelope::open(&mut received[..len]) else {
+            unreachable!();
+        };
+        assert!(encrypted.decrypt(&keys.encryption).is_some());
+    });
+}
+
+/// Runs `f` repeatedly and logs its throughput and the share of a frame interval it takes.
+fn report(name: &str, datagram_len: usize, mut f: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_datagram_us = start.elapsed().as_micros() / ITERATIONS as u64;
+
+    let throughput_kbs = datagram_len as u64 * 1000 / per_datagram_us.max(1);
+    let frame_interval_us = Duration::from_hz(FRAMES_PER_SECOND).as_micros();
+    let load_permille = per_datagram_us * 1000 / frame_interval_us;
+    info!(
+        "{}: {} bytes in {} us, {} kB/s, {}.{}% of a frame at {} fps",
+        name,
+        datagram_len,
+        per_datagram_us,
+        throughput_kbs,
+        load_permille / 10,
+        load_permille % 10,
+        FRAMES_PER_SECOND
+    );
+}
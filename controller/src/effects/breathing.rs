@@ -0,0 +1,30 @@
+use super::{blend, Effect};
+use core::f32::consts::TAU;
+use lumen_proto::effect::EffectParams;
+use lumen_proto::rgbw8::Rgbw8;
+use rand::RngCore;
+
+/// Duration of one breath at normal speed.
+const PERIOD_MS: u64 = 4000;
+
+/// The whole strip pulses between `background` and `color`. With a lower `intensity` the
+/// strip stays closer to `color` instead of fading all the way down.
+pub struct Breathing;
+
+impl Effect for Breathing {
+    fn render(
+        &mut self,
+        params: &EffectParams,
+        time_ms: u64,
+        _rng: &mut dyn RngCore,
+        frame: &mut [Rgbw8],
+    ) {
+        let phase = (time_ms % PERIOD_MS) as f32 / PERIOD_MS as f32;
+        let wave = (1.0 - libm::cosf(phase * TAU)) / 2.0;
+        let depth = params.intensity as f32 / 255.0;
+        let amount = (1.0 - depth + depth * wave) * 255.0;
+
+        let color = blend(params.background, params.color, amount as u8);
+        frame.fill(color.into());
+    }
+}
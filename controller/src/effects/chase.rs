@@ -0,0 +1,27 @@
+use super::Effect;
+use lumen_proto::effect::EffectParams;
+use lumen_proto::rgbw8::Rgbw8;
+use rand::RngCore;
+
+/// Time until the pattern moves by one LED at normal speed.
+const STEP_MS: u64 = 50;
+
+/// Segments of `size` LEDs in `color` separated by gaps of `background` moving along the strip.
+pub struct Chase;
+
+impl Effect for Chase {
+    fn render(
+        &mut self,
+        params: &EffectParams,
+        time_ms: u64,
+        _rng: &mut dyn RngCore,
+        frame: &mut [Rgbw8],
+    ) {
+        let size = params.size.max(1) as usize;
+        let offset = (time_ms / STEP_MS) as usize % (2 * size);
+        for (i, led) in frame.iter_mut().enumerate() {
+            let lit = (i + 2 * size - offset) / size % 2 == 0;
+            *led = if lit { params.color } else { params.background }.into();
+        }
+    }
+}
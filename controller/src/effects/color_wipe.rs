@@ -0,0 +1,32 @@
+use super::Effect;
+use lumen_proto::effect::EffectParams;
+use lumen_proto::rgbw8::Rgbw8;
+use rand::RngCore;
+
+/// Time until the next LED changes at normal speed.
+const STEP_MS: u64 = 30;
+
+/// Fills the strip with `color` one LED after another, then wipes it back to `background`.
+pub struct ColorWipe;
+
+impl Effect for ColorWipe {
+    fn render(
+        &mut self,
+        params: &EffectParams,
+        time_ms: u64,
+        _rng: &mut dyn RngCore,
+        frame: &mut [Rgbw8],
+    ) {
+        let len = frame.len().max(1);
+        let position = (time_ms / STEP_MS) as usize % (2 * len);
+        let (filled, first, second) = if position < len {
+            (position, params.color, params.background)
+        } else {
+            (position - len, params.background, params.color)
+        };
+
+        for (i, led) in frame.iter_mut().enumerate() {
+            *led = if i < filled { first } else { second }.into();
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use super::{steps_due, Effect};
+use crate::LED_MAX;
+use lumen_proto::effect::EffectParams;
+use lumen_proto::rgb8::Rgb8;
+use lumen_proto::rgbw8::Rgbw8;
+use rand::Rng;
+use rand::RngCore;
+
+/// Time between two updates of the simulation at normal speed.
+const STEP_MS: u64 = 16;
+/// How fast the flames cool down while they rise.
+const COOLING: usize = 55;
+
+/// Flames rising from the start of the strip, after Mark Kriegsman's Fire2012.
+/// `intensity` sets how often new sparks ignite.
+pub struct Fire {
+    heat: [u8; LED_MAX],
+    last_step_ms: u64,
+}
+
+impl Fire {
+    pub fn new() -> Self {
+        Self {
+            heat: [0; LED_MAX],
+            last_step_ms: 0,
+        }
+    }
+
+    fn step(&mut self, intensity: u8, rng: &mut dyn RngCore, len: usize) {
+        let heat = &mut self.heat[..len];
+
+        // Short strips would cool by more than a cell can hold
+        let max_cooling = (COOLING * 10 / len.max(1) + 2).min(u8::MAX as usize) as u8;
+        for cell in heat.iter_mut() {
+            *cell = cell.saturating_sub(rng.gen_range(0..=max_cooling));
+        }
+
+        for k in (2..len).rev() {
+            heat[k] = ((heat[k - 1] as u16 + 2 * heat[k - 2] as u16) / 3) as u8;
+        }
+
+        if len > 0 && rng.gen::<u8>() < intensity {
+            let y = rng.gen_range(0..len.min(7));
+            heat[y] = heat[y].saturating_add(rng.gen_range(160..=255));
+        }
+    }
+}
+
+impl Default for Fire {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Effect for Fire {
+    fn render(
+        &mut self,
+        params: &EffectParams,
+        time_ms: u64,
+        rng: &mut dyn RngCore,
+        frame: &mut [Rgbw8],
+    ) {
+        for _ in 0..steps_due(&mut self.last_step_ms, time_ms, STEP_MS) {
+            self.step(params.intensity, rng, frame.len());
+        }
+
+        for (led, &heat) in frame.iter_mut().zip(self.heat.iter()) {
+            *led = heat_color(heat).into();
+        }
+    }
+}
+
+/// Maps the heat of a cell to black, red, yellow and white.
+fn heat_color(heat: u8) -> Rgb8 {
+    let scaled = (heat as u16 * 191 / 255) as u8;
+    let ramp = (scaled & 0x3f) << 2;
+    match scaled {
+        0x80.. => Rgb8 {
+            r: 255,
+            g: 255,
+            b: ramp,
+        },
+        0x40.. => Rgb8 {
+            r: 255,
+            g: ramp,
+            b: 0,
+        },
+        _ => Rgb8 {
+            r: ramp,
+            g: 0,
+            b: 0,
+        },
+    }
+}
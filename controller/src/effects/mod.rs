@@ -0,0 +1,251 @@
+pub mod breathing;
+pub mod chase;
+pub mod color_wipe;
+pub mod fire;
+pub mod rainbow;
+pub mod twinkle;
+
+use crate::ATOM_EFFECT;
+use crate::LED_MAX;
+use arrayvec::ArrayVec;
+use breathing::Breathing;
+use chase::Chase;
+use color_wipe::ColorWipe;
+use embassy_time::Instant;
+use fire::Fire;
+use lumen_proto::effect::{EffectId, EffectParams};
+use lumen_proto::rgb8::Rgb8;
+use lumen_proto::rgbw8::Rgbw8;
+use rainbow::Rainbow;
+use rand::rngs::SmallRng;
+use rand::RngCore;
+use rand::SeedableRng;
+use twinkle::Twinkle;
+
+/// An animation rendered by the controller itself.
+pub trait Effect {
+    /// Renders the effect at `time_ms` into `frame`. The time runs faster or slower than
+    /// real time depending on the speed parameter.
+    fn render(
+        &mut self,
+        params: &EffectParams,
+        time_ms: u64,
+        rng: &mut dyn RngCore,
+        frame: &mut [Rgbw8],
+    );
+}
+
+/// The effect core 0 wants to run. A config with the generation of the running effect only
+/// updates its parameters, any other generation starts the effect from the beginning.
+#[derive(Debug, Clone, Copy)]
+pub struct EffectConfig {
+    pub id: EffectId,
+    pub led_count: u16,
+    pub params: EffectParams,
+    pub generation: u32,
+}
+
+/// What changed with a call to `EffectEngine::update`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectUpdate {
+    Unchanged,
+    Started,
+    ParamsChanged,
+    Stopped,
+}
+
+/// There is no allocator on the controller, so the effects are stored inline
+#[allow(clippy::large_enum_variant)]
+enum BuiltinEffect {
+    Rainbow(Rainbow),
+    Breathing(Breathing),
+    ColorWipe(ColorWipe),
+    Chase(Chase),
+    Twinkle(Twinkle),
+    Fire(Fire),
+}
+
+impl BuiltinEffect {
+    fn new(id: EffectId) -> Self {
+        match id {
+            EffectId::Rainbow => BuiltinEffect::Rainbow(Rainbow),
+            EffectId::Breathing => BuiltinEffect::Breathing(Breathing),
+            EffectId::ColorWipe => BuiltinEffect::ColorWipe(ColorWipe),
+            EffectId::Chase => BuiltinEffect::Chase(Chase),
+            EffectId::Twinkle => BuiltinEffect::Twinkle(Twinkle::new()),
+            EffectId::Fire => BuiltinEffect::Fire(Fire::new()),
+        }
+    }
+
+    fn as_effect(&mut self) -> &mut dyn Effect {
+        match self {
+            BuiltinEffect::Rainbow(effect) => effect,
+            BuiltinEffect::Breathing(effect) => effect,
+            BuiltinEffect::ColorWipe(effect) => effect,
+            BuiltinEffect::Chase(effect) => effect,
+            BuiltinEffect::Twinkle(effect) => effect,
+            BuiltinEffect::Fire(effect) => effect,
+        }
+    }
+}
+
+struct RunningEffect {
+    effect: BuiltinEffect,
+    /// `None` for effects started by the idle behavior instead of a client.
+    generation: Option<u32>,
+    params: EffectParams,
+    led_count: usize,
+    /// Effect time in microseconds, advanced by the elapsed time scaled with the speed.
+    time_us: u64,
+    last_render: Instant,
+}
+
+/// Runs the built-in effects on core 1. While an effect runs it replaces the received frames.
+pub struct EffectEngine {
+    running: Option<RunningEffect>,
+    rng: SmallRng,
+}
+
+impl EffectEngine {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            running: None,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Starts, updates or stops an effect if requested.
+    pub async fn update(&mut self) -> EffectUpdate {
+        match ATOM_EFFECT.recv().await {
+            Some(Some(config)) => {
+                if let Some(running) = &mut self.running {
+                    if running.generation == Some(config.generation) {
+                        // The new parameters are picked up by the next rendered frame
+                        running.params = config.params;
+                        return EffectUpdate::ParamsChanged;
+                    }
+                }
+
+                self.start(
+                    config.id,
+                    config.led_count,
+                    config.params,
+                    Some(config.generation),
+                );
+                EffectUpdate::Started
+            }
+            Some(None) => match self.running.take() {
+                Some(_) => EffectUpdate::Stopped,
+                None => EffectUpdate::Unchanged,
+            },
+            None => EffectUpdate::Unchanged,
+        }
+    }
+
+    /// Starts an effect for the idle behavior. Core 0 doesn't know about it, so the
+    /// next frame from a client has to stop it with `stop`.
+    pub fn start_idle(&mut self, id: EffectId, led_count: u16, params: EffectParams) {
+        self.start(id, led_count, params, None);
+    }
+
+    pub fn stop(&mut self) {
+        self.running = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Returns true if the running effect was started by the idle behavior.
+    pub fn is_idle(&self) -> bool {
+        self.running
+            .as_ref()
+            .is_some_and(|running| running.generation.is_none())
+    }
+
+    fn start(
+        &mut self,
+        id: EffectId,
+        led_count: u16,
+        params: EffectParams,
+        generation: Option<u32>,
+    ) {
+        self.running = Some(RunningEffect {
+            effect: BuiltinEffect::new(id),
+            generation,
+            params,
+            led_count: led_count as usize,
+            time_us: 0,
+            last_render: Instant::now(),
+        });
+    }
+
+    /// Renders the next frame of the running effect into `frame`.
+    pub fn render(&mut self, frame: &mut ArrayVec<Rgbw8, LED_MAX>) {
+        let Some(running) = &mut self.running else {
+            return;
+        };
+
+        let now = Instant::now();
+        let elapsed_us = now.duration_since(running.last_render).as_micros();
+        running.time_us += elapsed_us * running.params.speed as u64 / 64;
+        running.last_render = now;
+
+        frame.clear();
+        frame.extend((0..running.led_count).map(|_| Rgbw8::default()));
+        running.effect.as_effect().render(
+            &running.params,
+            running.time_us / 1000,
+            &mut self.rng,
+            frame,
+        );
+    }
+}
+
+/// Returns the number of fixed steps of a stateful effect that are due at `time_ms`.
+/// At most `MAX_STEPS` are returned, so an effect that was slowed down doesn't stall the output.
+fn steps_due(last_step_ms: &mut u64, time_ms: u64, step_ms: u64) -> u64 {
+    const MAX_STEPS: u64 = 4;
+
+    let steps = time_ms.saturating_sub(*last_step_ms) / step_ms;
+    *last_step_ms += steps * step_ms;
+    steps.min(MAX_STEPS)
+}
+
+/// Maps a hue to a fully saturated color, the hues are spread evenly over all 256 values.
+fn hue_to_rgb(hue: u8) -> Rgb8 {
+    let sector = hue / 86;
+    let rising = (hue % 86) as u16 * 255 / 85;
+    let rising = rising as u8;
+    match sector {
+        0 => Rgb8 {
+            r: 255 - rising,
+            g: rising,
+            b: 0,
+        },
+        1 => Rgb8 {
+            r: 0,
+            g: 255 - rising,
+            b: rising,
+        },
+        _ => Rgb8 {
+            r: rising,
+            g: 0,
+            b: 255 - rising,
+        },
+    }
+}
+
+/// Mixes `from` and `to`, an amount of 255 returns `to`.
+fn blend(from: Rgb8, to: Rgb8, amount: u8) -> Rgb8 {
+    let mix = |a: u8, b: u8| {
+        let a = a as i32;
+        let b = b as i32;
+        (a + (b - a) * amount as i32 / 255) as u8
+    };
+    Rgb8 {
+        r: mix(from.r, to.r),
+        g: mix(from.g, to.g),
+        b: mix(from.b, to.b),
+    }
+}
@@ -0,0 +1,31 @@
+use super::{hue_to_rgb, Effect};
+use lumen_proto::effect::EffectParams;
+use lumen_proto::rgbw8::Rgbw8;
+use rand::RngCore;
+
+/// Time one color needs to pass through all hues at normal speed.
+const CYCLE_MS: u64 = 5000;
+
+/// Hues moving along the strip. `size` is the length of one rainbow in LEDs,
+/// 0 spreads a single rainbow over the whole strip.
+pub struct Rainbow;
+
+impl Effect for Rainbow {
+    fn render(
+        &mut self,
+        params: &EffectParams,
+        time_ms: u64,
+        _rng: &mut dyn RngCore,
+        frame: &mut [Rgbw8],
+    ) {
+        let length = match params.size {
+            0 => frame.len().max(1),
+            size => size as usize,
+        };
+        let offset = (time_ms % CYCLE_MS * 256 / CYCLE_MS) as usize;
+        for (i, led) in frame.iter_mut().enumerate() {
+            let hue = (i * 256 / length + offset) as u8;
+            *led = hue_to_rgb(hue).into();
+        }
+    }
+}
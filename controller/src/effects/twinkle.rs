@@ -0,0 +1,59 @@
+use super::{blend, steps_due, Effect};
+use crate::LED_MAX;
+use lumen_proto::effect::EffectParams;
+use lumen_proto::rgbw8::Rgbw8;
+use rand::Rng;
+use rand::RngCore;
+
+/// Time between two updates of the twinkles at normal speed.
+const STEP_MS: u64 = 20;
+
+/// LEDs light up in `color` at random and fade back to `background`.
+/// `intensity` sets how many LEDs light up.
+pub struct Twinkle {
+    levels: [u8; LED_MAX],
+    last_step_ms: u64,
+}
+
+impl Twinkle {
+    pub fn new() -> Self {
+        Self {
+            levels: [0; LED_MAX],
+            last_step_ms: 0,
+        }
+    }
+
+    fn step(&mut self, intensity: u8, rng: &mut dyn RngCore, len: usize) {
+        for level in self.levels[..len].iter_mut() {
+            *level = level.saturating_sub(*level / 16 + 1);
+            // On average a LED lights up every 2000 steps at an intensity of 1
+            if rng.gen_range(0..2000u32) < intensity as u32 {
+                *level = u8::MAX;
+            }
+        }
+    }
+}
+
+impl Default for Twinkle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Effect for Twinkle {
+    fn render(
+        &mut self,
+        params: &EffectParams,
+        time_ms: u64,
+        rng: &mut dyn RngCore,
+        frame: &mut [Rgbw8],
+    ) {
+        for _ in 0..steps_due(&mut self.last_step_ms, time_ms, STEP_MS) {
+            self.step(params.intensity, rng, frame.len());
+        }
+
+        for (led, &level) in frame.iter_mut().zip(self.levels.iter()) {
+            *led = blend(params.background, params.color, level).into();
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::LED_MAX;
+use arrayvec::ArrayVec;
+use lumen_proto::bytestreamreader::{ByteStreamReader, MessageDeserializer};
+use lumen_proto::bytestreamwriter::{ByteStreamWriter, MessageSerializer};
+use lumen_proto::rgbw8::Rgbw8;
+use lumen_proto::{DeserializationError, DeserializationResult, SerializationResult};
+
+/// The frame shown by `IdleAction::RestoreScene`, saved by the client with `SaveScene`.
+/// An empty scene turns the strip off.
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    pub values: ArrayVec<Rgbw8, LED_MAX>,
+}
+
+impl MessageDeserializer for Scene {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let count = reader.u16()?;
+        if count as usize > LED_MAX {
+            return Err(DeserializationError::LedCountOverCapacity(count));
+        }
+
+        let mut values = ArrayVec::new();
+        for _ in 0..count {
+            values.push(Rgbw8::deserialize_from(reader)?);
+        }
+        Ok(Scene { values })
+    }
+}
+
+impl MessageSerializer for Scene {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u16(self.values.len() as u16)?;
+        for value in &self.values {
+            value.serialize_into(writer)?;
+        }
+        Ok(())
+    }
+}
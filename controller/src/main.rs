@@ -2,17 +2,30 @@
 #![no_main]
 
 pub mod atomic_channel;
+pub mod auth;
+#[cfg(feature = "bench-envelope")]
+pub mod bench;
+pub mod effects;
+pub mod idle;
 pub mod message_controller;
-pub mod messages;
+pub mod output;
+pub mod pairing;
+pub mod status;
+pub mod storage;
+pub mod telemetry;
 pub mod ws2812;
 
-use crate::messages::rgb8::Rgb8;
 use arrayvec::ArrayVec;
 use atomic_channel::AtomicChannel;
+use auth::Authenticator;
+use controller_core::output::current_limit::CurrentLimit;
 use cyw43::JoinOptions;
 use cyw43_pio::PioSpi;
 use defmt::info;
 use defmt::*;
+use effects::EffectConfig;
+use effects::EffectEngine;
+use effects::EffectUpdate;
 use embassy_executor::Executor;
 use embassy_executor::Spawner;
 use embassy_net::udp::PacketMetadata;
@@ -24,24 +37,44 @@ use embassy_net::StackResources;
 use embassy_net::StaticConfigV4;
 use embassy_rp::bind_interrupts;
 use embassy_rp::clocks::RoscRng;
+use embassy_rp::gpio::Input;
 use embassy_rp::gpio::Level;
 use embassy_rp::gpio::Output;
+use embassy_rp::gpio::Pull;
 use embassy_rp::multicore::{self, spawn_core1};
 use embassy_rp::peripherals::DMA_CH0;
 use embassy_rp::peripherals::PIO0;
 use embassy_rp::peripherals::PIO1;
 use embassy_rp::pio::Pio;
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::with_timeout;
 use embassy_time::Duration;
+use embassy_time::Instant;
 use embassy_time::Timer;
 use heapless::Vec;
+use idle::Scene;
+use lumen_proto::auth::{Key, ENVELOPE_OVERHEAD, KEY_LEN};
+use lumen_proto::error::DeserializationErrorCounters;
+use lumen_proto::idle::{IdleAction, IdleBehavior};
+use lumen_proto::reply::{ControllerReply, ReplyKind};
+use lumen_proto::rgbw8::Rgbw8;
+use lumen_proto::ControllerMessage;
+use lumen_proto::LED_MAX;
 use message_controller::MessageController;
-use messages::bytestreamreader::ByteStreamReader;
-use messages::bytestreamreader::MessageDeserializer;
-use messages::ControllerMessage;
+use output::brightness::BrightnessFade;
+use output::color_correction::ColorCorrection;
+use output::gamma::GammaTables;
+use output::pixel_format::PixelSettings;
+use output::transition::{FrameTransition, TransitionSettings};
+use output::LedFrame;
+use output::OutputPipeline;
+use pairing::{CodeDisplay, CodeOverlay, BUTTON_HOLD};
 use rand::RngCore;
 use static_assertions::const_assert;
-use static_cell::StaticCell;
+use static_cell::{ConstStaticCell, StaticCell};
+use storage::{Slot, Storage};
+use telemetry::Telemetry;
 use ws2812::Ws2812;
 use {defmt_rtt as _, panic_probe as _};
 
@@ -55,8 +88,19 @@ const WIFI_PASSWORD: &str = env!("PASSWORD");
 const RECV_PORT_STR: &str = env!("NET_RECV_PORT");
 const NET_ADDRESS_STR: &str = env!("NET_ADDRESS");
 const NET_GATEWAY_STR: &str = env!("NET_GATEWAY");
+const AUTH_KEY_STR: &str = env!("AUTH_KEY");
+const AUTH_REQUIRED_STR: &str = env!("AUTH_REQUIRED");
 const RECV_PORT: u16 = parse_u16(RECV_PORT_STR);
-const LED_MAX: usize = 400;
+/// Key of signed datagrams, see `lumen_proto::auth`. Clients can be paired instead.
+const AUTH_KEY: Option<Key> = parse_key(AUTH_KEY_STR);
+/// Unsigned datagrams are rejected if set.
+const AUTH_REQUIRED: bool = parse_bool(AUTH_REQUIRED_STR);
+/// How often the output telemetry is logged.
+const TELEMETRY_INTERVAL: Duration = Duration::from_secs(10);
+/// Shortest interval between refreshes of the output while it changes without new frames, e.g.
+/// during a fade. Longer strips are refreshed as fast as they can be written, see
+/// `Ws2812::frame_duration`.
+const OUTPUT_REFRESH_MIN: Duration = Duration::from_millis(1);
 
 // env variables have to be set in .cargo/config.toml
 const_assert!(!WIFI_NETWORK.is_empty());
@@ -74,11 +118,43 @@ static EXECUTOR1: StaticCell<Executor> = StaticCell::new();
 static CYW43_STATE: StaticCell<cyw43::State> = StaticCell::new();
 static NET_STACK_RESOURCES: StaticCell<StackResources<10>> = StaticCell::new();
 
+// The state of the tasks holds several buffers of LED_MAX values each, it is kept out of the
+// task arena, which only has to fit the futures
+static MESSAGE_CONTROLLER: StaticCell<MessageController> = StaticCell::new();
+static OUTPUT_PIPELINE: ConstStaticCell<OutputPipeline> =
+    ConstStaticCell::new(OutputPipeline::new());
+static WS2812: StaticCell<Ws2812<'static, PIO1, 0, LED_MAX>> = StaticCell::new();
+
 pub type MUTEX = CriticalSectionRawMutex;
 
 // Use static channels to communicate between tasks
-static ATOM_LED_STATE: AtomicChannel<MUTEX, ArrayVec<Rgb8, LED_MAX>> = AtomicChannel::new();
+static ATOM_LED_STATE: AtomicChannel<MUTEX, LedFrame> = AtomicChannel::new();
 static ATOM_KEEP_ALIVE: AtomicChannel<MUTEX, Duration> = AtomicChannel::new();
+static ATOM_IDLE_BEHAVIOR: AtomicChannel<MUTEX, IdleBehavior> = AtomicChannel::new();
+/// Sent by the keep alive task when the strip goes idle, the write task applies the behavior.
+static ATOM_IDLE_TIMEOUT: AtomicChannel<MUTEX, IdleBehavior> = AtomicChannel::new();
+static ATOM_BRIGHTNESS: AtomicChannel<MUTEX, BrightnessFade> = AtomicChannel::new();
+static ATOM_GAMMA: AtomicChannel<MUTEX, GammaTables> = AtomicChannel::new();
+static ATOM_COLOR_CORRECTION: AtomicChannel<MUTEX, ColorCorrection> = AtomicChannel::new();
+static ATOM_PIXEL_SETTINGS: AtomicChannel<MUTEX, PixelSettings> = AtomicChannel::new();
+static ATOM_CURRENT_LIMIT: AtomicChannel<MUTEX, CurrentLimit> = AtomicChannel::new();
+static ATOM_TRANSITION: AtomicChannel<MUTEX, TransitionSettings> = AtomicChannel::new();
+/// The smoothing factor of the output, see `Smoothing`.
+static ATOM_SMOOTHING: AtomicChannel<MUTEX, u8> = AtomicChannel::new();
+/// Starts an effect, or stops the running one with `None`.
+static ATOM_EFFECT: AtomicChannel<MUTEX, Option<EffectConfig>> = AtomicChannel::new();
+/// Shows the pairing code on the strip, or hides it with `None`.
+static ATOM_PAIRING_CODE: AtomicChannel<MUTEX, Option<CodeDisplay>> = AtomicChannel::new();
+/// Sent by the button task when the pairing button was held.
+static ATOM_PAIRING_BUTTON: AtomicChannel<MUTEX, Instant> = AtomicChannel::new();
+
+static TELEMETRY: Telemetry = Telemetry::new();
+
+/// The frame that was last written to the strip, used as base for partial updates.
+static LAST_LED_STATE: Mutex<MUTEX, ArrayVec<Rgbw8, LED_MAX>> = Mutex::new(ArrayVec::new_const());
+static SCENE: Mutex<MUTEX, Scene> = Mutex::new(Scene {
+    values: ArrayVec::new_const(),
+});
 
 macro_rules! var_info {
     ($var:ident) => {
@@ -89,12 +165,13 @@ macro_rules! var_info {
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     info!(
-        "Starting with env vars:\n\t- {}\n\t- {}\n\t- {}\n\t- {}\n\t- {}",
+        "Starting with env vars:\n\t- {}\n\t- {}\n\t- {}\n\t- {}\n\t- {}\n\t- {}",
         var_info!(WIFI_NETWORK),
         var_info!(WIFI_PASSWORD),
         var_info!(RECV_PORT),
         var_info!(NET_ADDRESS_STR),
-        var_info!(NET_GATEWAY_STR)
+        var_info!(NET_GATEWAY_STR),
+        var_info!(AUTH_REQUIRED)
     );
 
     let net_address = parse_ip_v4(NET_ADDRESS_STR);
@@ -103,8 +180,32 @@ async fn main(spawner: Spawner) {
     let mut rng = RoscRng;
     let p = embassy_rp::init(Default::default());
 
+    #[cfg(feature = "bench-envelope")]
+    bench::run();
+
+    // Load the persisted settings before core 1 starts, which has to pause for flash access
+    let mut storage = Storage::new(p.FLASH);
+    let idle_behavior = storage.load(Slot::IdleBehavior).unwrap_or_default();
+    ATOM_IDLE_BEHAVIOR.send(idle_behavior).await;
+    if let Some(scene) = storage.load(Slot::Scene) {
+        *SCENE.lock().await = scene;
+    }
+    let nonce_floor = storage.load(Slot::NonceFloor).unwrap_or_default();
+    let paired_clients = storage.load(Slot::PairedClients).unwrap_or_default();
+    let authenticator = Authenticator::new(AUTH_KEY, AUTH_REQUIRED, nonce_floor, paired_clients);
+    let msg_controller = MESSAGE_CONTROLLER.init(MessageController::new(
+        storage,
+        idle_behavior,
+        authenticator,
+    ));
+
     let mut pio_leds = Pio::new(p.PIO1, Irqs);
-    let ws2812 = Ws2812::new(&mut pio_leds.common, pio_leds.sm0, p.DMA_CH1, p.PIN_12);
+    let ws2812 = WS2812.init(Ws2812::new(
+        &mut pio_leds.common,
+        pio_leds.sm0,
+        p.DMA_CH1,
+        p.PIN_12,
+    ));
 
     spawn_core1(
         p.CORE1,
@@ -160,7 +261,10 @@ async fn main(spawner: Spawner) {
     spawner.must_spawn(net_task(net_runner));
 
     // Start the Lumen UDP message handler
-    spawner.must_spawn(handle_udp_messages_task(net_stack));
+    spawner.must_spawn(handle_udp_messages_task(net_stack, msg_controller));
+
+    spawner.must_spawn(telemetry_task());
+    spawner.must_spawn(pairing_button_task(Input::new(p.PIN_15, Pull::Up)));
 
     info!("Finished spawning tasks for core 0");
 
@@ -179,11 +283,14 @@ async fn main(spawner: Spawner) {
 }
 
 #[embassy_executor::task]
-async fn handle_udp_messages_task(stack: Stack<'static>) -> ! {
+async fn handle_udp_messages_task(
+    stack: Stack<'static>,
+    msg_controller: &'static mut MessageController,
+) -> ! {
     let mut rx_buffer = [0; 4096];
     let mut rx_meta = [PacketMetadata::EMPTY; 16];
-    let mut tx_buffer = [0; 0];
-    let mut tx_meta = [PacketMetadata::EMPTY; 0];
+    let mut tx_buffer = [0; 256];
+    let mut tx_meta = [PacketMetadata::EMPTY; 4];
 
     let mut udp_socket = UdpSocket::new(
         stack,
@@ -195,56 +302,223 @@ async fn handle_udp_messages_task(stack: Stack<'static>) -> ! {
 
     udp_socket.bind(RECV_PORT).unwrap();
 
-    let mut msg_controller = MessageController::new();
+    let mut error_counters = DeserializationErrorCounters::new();
     let mut message_buffer = [0; 2048];
+    let mut reply_buffer = [0; 128 + ENVELOPE_OVERHEAD];
     loop {
         match udp_socket.recv_from(&mut message_buffer).await {
             Err(e) => {
                 warn!("error receiving message {}", e);
             }
-            Ok((n, _)) => {
-                let read = &message_buffer[0..n];
-                let mut reader = ByteStreamReader::new(read);
-                let decoded = ControllerMessage::deserialize_from(&mut reader);
-                if decoded.is_err() {
-                    error!("Error deserializing message");
+            Ok((n, sender)) => {
+                let Some(authenticated) =
+                    msg_controller.authenticate(&mut message_buffer[0..n]).await
+                else {
                     continue;
+                };
+                match ControllerMessage::decode(authenticated.datagram) {
+                    Ok(decoded) => {
+                        let in_reply_to = decoded.timestamp;
+                        let kind = match authenticated.run_closed {
+                            // The datagram may be replayed, so it is only answered, with a
+                            // reply that is signed under a key of its own
+                            true => Some(ReplyKind::RunClosed),
+                            false => {
+                                let key_id = authenticated.key_id();
+                                msg_controller
+                                    .handle_msg_lumen(decoded, sender.endpoint, key_id)
+                                    .await
+                            }
+                        };
+                        let Some(kind) = kind else {
+                            continue;
+                        };
+
+                        // Replies are sealed like the request, plain requests get plain replies
+                        let reply = ControllerReply { in_reply_to, kind };
+                        let encoded = match &authenticated.seal {
+                            Some(seal) => seal.seal(&reply, &mut reply_buffer),
+                            None => reply.encode(&mut reply_buffer),
+                        };
+                        match encoded {
+                            Ok(len) => {
+                                if let Err(e) =
+                                    udp_socket.send_to(&reply_buffer[..len], sender).await
+                                {
+                                    warn!("error sending reply {}", e);
+                                }
+                            }
+                            Err(e) => error!("Error serializing reply: {}", e),
+                        }
+                    }
+                    Err(e) => {
+                        error_counters.record(e);
+                        error!("Error deserializing message: {} ({})", e, error_counters);
+                    }
                 }
-                msg_controller.handle_msg_lumen(decoded.unwrap()).await;
             }
         }
     }
 }
 
 #[embassy_executor::task]
-async fn write_led_strip_task(mut ws: Ws2812<'static, PIO1, 0, LED_MAX>) -> ! {
+async fn write_led_strip_task(ws: &'static mut Ws2812<'static, PIO1, 0, LED_MAX>) -> ! {
+    let pipeline = OUTPUT_PIPELINE.take();
+    let mut effects = EffectEngine::new(RoscRng.next_u64());
+    let mut code_overlay = CodeOverlay::default();
+    let mut frame: ArrayVec<Rgbw8, LED_MAX> = ArrayVec::new();
     loop {
-        let buffer = ATOM_LED_STATE.recv_item().await;
-        ws.write(&buffer).await;
+        let refresh_interval = ws.frame_duration(frame.len()).max(OUTPUT_REFRESH_MIN);
+        let mut new_frame = ATOM_LED_STATE.recv_with_timeout(refresh_interval).await;
+
+        // The pairing code covers the strip, frames keep coming in below it
+        if let Some(display) = ATOM_PAIRING_CODE.recv().await {
+            code_overlay.set(display);
+        }
+        let now = Instant::now();
+        if code_overlay.is_shown(now) {
+            if let Some(buffer) = new_frame.take() {
+                frame = buffer.values;
+                *LAST_LED_STATE.lock().await = frame.clone();
+            }
+            if let Some(code_frame) = code_overlay.render(frame.len(), now) {
+                ws.write(&code_frame).await;
+            }
+            continue;
+        }
+        let code_hidden = code_overlay.hide();
+
+        let settings_changed = pipeline.update_settings().await;
+        let mut effect_update = effects.update().await;
+
+        // Core 0 doesn't know about effects started by the idle behavior, frames stop them here
+        if new_frame.is_some() && effects.is_idle() {
+            effects.stop();
+            effect_update = EffectUpdate::Stopped;
+        }
+
+        let mut transition = match effect_update {
+            EffectUpdate::Started => FrameTransition::Configured,
+            _ => new_frame
+                .as_ref()
+                .map_or(FrameTransition::Cut, |frame| frame.transition),
+        };
+
+        // An effect started by the client doesn't need keep alives and keeps running
+        let timeout = ATOM_IDLE_TIMEOUT.recv().await;
+        if let Some(behavior) = timeout.filter(|_| !effects.is_running()) {
+            let fade = FrameTransition::Fade(Duration::from_millis(behavior.fade_millis as u64));
+            match behavior.action {
+                IdleAction::Black => new_frame = Some(blank_frame()),
+                IdleAction::HoldLastFrame => {}
+                IdleAction::RunEffect {
+                    id,
+                    led_count,
+                    params,
+                } => {
+                    effects.start_idle(id, led_count, params);
+                    effect_update = EffectUpdate::Started;
+                }
+                IdleAction::RestoreScene => {
+                    let scene = SCENE.lock().await;
+                    new_frame = match scene.values.is_empty() {
+                        true => Some(blank_frame()),
+                        false => Some(LedFrame {
+                            values: scene.values.clone(),
+                            transition: FrameTransition::Cut,
+                        }),
+                    };
+                }
+            }
+            if behavior.action != IdleAction::HoldLastFrame {
+                transition = fade;
+            }
+        }
+        pipeline.start_transition(transition);
+
+        match new_frame {
+            // A running effect replaces the received frames until it is stopped
+            _ if effects.is_running() => effects.render(&mut frame),
+            Some(buffer) => {
+                frame = buffer.values;
+                *LAST_LED_STATE.lock().await = frame.clone();
+            }
+            // Output settings can change while the client sends no frames, e.g. during a fade
+            None if settings_changed
+                || code_hidden
+                || effect_update != EffectUpdate::Unchanged
+                || pipeline.is_animating() => {}
+            None => continue,
+        }
+
+        if ws.pixel_format() != pipeline.pixel_format() {
+            ws.set_pixel_format(pipeline.pixel_format());
+        }
+        ws.set_color_order(pipeline.color_order());
+        ws.write(pipeline.process(&frame)).await;
+        TELEMETRY.record_output(pipeline.current_estimate());
     }
 }
 
-/// The controller expects a KEEP_ALIVE message in intervals to keep the strip on or else it will turn off the LED strip.
 #[embassy_executor::task]
-async fn keep_alive_task() -> ! {
-    let mut blank_buffer: ArrayVec<Rgb8, LED_MAX> = ArrayVec::new();
-    for _ in 0..blank_buffer.capacity() {
-        blank_buffer.push(Rgb8 { r: 0, g: 0, b: 0 })
+async fn telemetry_task() -> ! {
+    loop {
+        Timer::after(TELEMETRY_INTERVAL).await;
+        info!("Output telemetry: {}", TELEMETRY.snapshot());
     }
-    let wait_for = Duration::from_millis(800);
+}
+
+/// Opens the pairing mode when the button between GPIO 15 and ground is held.
+#[embassy_executor::task]
+async fn pairing_button_task(mut button: Input<'static>) -> ! {
     loop {
-        let keepalive = ATOM_KEEP_ALIVE.recv_with_timeout(wait_for).await;
+        button.wait_for_low().await;
+        if with_timeout(BUTTON_HOLD, button.wait_for_high())
+            .await
+            .is_err()
+        {
+            ATOM_PAIRING_BUTTON.send(Instant::now()).await;
+            button.wait_for_high().await;
+        }
+    }
+}
+
+/// The controller expects a KEEP_ALIVE message in intervals to keep the strip on.
+/// Once they stop for longer than the grace period, the strip goes idle as configured by the client.
+#[embassy_executor::task]
+async fn keep_alive_task() -> ! {
+    let mut behavior = IdleBehavior::default();
+    let mut idle = false;
+    loop {
+        if let Some(new_behavior) = ATOM_IDLE_BEHAVIOR.recv().await {
+            behavior = new_behavior;
+        }
+
+        let grace = Duration::from_millis(behavior.grace_millis as u64);
+        let keepalive = ATOM_KEEP_ALIVE.recv_with_timeout(grace).await;
         match keepalive {
             Some(alive_duration) => {
+                idle = false;
                 Timer::after(alive_duration).await;
             }
-            None => {
-                ATOM_LED_STATE.send(blank_buffer.clone()).await;
+            // Going idle once is enough, repeating it would restart the idle effect
+            None if !idle => {
+                idle = true;
+                ATOM_IDLE_TIMEOUT.send(behavior).await;
             }
+            None => {}
         }
     }
 }
 
+/// An all black frame covering every LED the strip can have.
+fn blank_frame() -> LedFrame {
+    LedFrame {
+        values: (0..LED_MAX).map(|_| Rgbw8::default()).collect(),
+        transition: FrameTransition::Cut,
+    }
+}
+
 #[embassy_executor::task]
 async fn cyw43_task(
     runner: cyw43::Runner<'static, Output<'static>, PioSpi<'static, PIO0, 0, DMA_CH0>>,
@@ -292,6 +566,40 @@ const fn parse_u16(s: &'static str) -> u16 {
     val
 }
 
+/// Parses a key given as hex string, `None` if the string is empty.
+const fn parse_key(s: &'static str) -> Option<Key> {
+    if s.is_empty() {
+        return None;
+    }
+    core::assert!(s.len() == 2 * KEY_LEN, "key must have 64 hex digits");
+
+    let bytes = s.as_bytes();
+    let mut key = [0; KEY_LEN];
+    let mut i = 0;
+    while i < KEY_LEN {
+        key[i] = (parse_hex_digit(bytes[2 * i]) << 4) | parse_hex_digit(bytes[2 * i + 1]);
+        i += 1;
+    }
+    Some(key)
+}
+
+const fn parse_hex_digit(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        b'A'..=b'F' => digit - b'A' + 10,
+        _ => core::panic!("invalid hex digit"),
+    }
+}
+
+const fn parse_bool(s: &'static str) -> bool {
+    match s.as_bytes() {
+        b"true" => true,
+        b"false" => false,
+        _ => core::panic!("expected true or false"),
+    }
+}
+
 fn parse_ip_v4(s: &str) -> Ipv4Address {
     let mut bytes = s.split('.').map(|b| b.parse::<u8>().unwrap());
     Ipv4Address::new(
@@ -1,59 +1,717 @@
-use crate::messages::message_id::MessageId;
-use crate::messages::message_kind::MessageKind;
-use crate::messages::ControllerMessage;
-use crate::messages::Timestamp;
+use crate::auth::{AuthFailure, AuthFailureCounters, Authenticated, Authenticator};
+use crate::effects::EffectConfig;
+use crate::idle::Scene;
+use crate::output::brightness::BrightnessFade;
+use crate::output::color_correction::ColorCorrection;
+use crate::output::gamma::GammaTables;
+use crate::output::pixel_format::PixelSettings;
+use crate::output::transition::{FrameTransition, TransitionSettings};
+use crate::output::LedFrame;
+use crate::pairing::{Pairing, PairingFailure, PAIRING_WINDOW};
+use crate::status::{free_memory, FIRMWARE_VERSION};
+use crate::storage::{Slot, Storage};
+use crate::ATOM_BRIGHTNESS;
+use crate::ATOM_COLOR_CORRECTION;
+use crate::ATOM_CURRENT_LIMIT;
+use crate::ATOM_EFFECT;
+use crate::ATOM_GAMMA;
+use crate::ATOM_IDLE_BEHAVIOR;
 use crate::ATOM_KEEP_ALIVE;
 use crate::ATOM_LED_STATE;
+use crate::ATOM_PAIRING_BUTTON;
+use crate::ATOM_PAIRING_CODE;
+use crate::ATOM_PIXEL_SETTINGS;
+use crate::ATOM_SMOOTHING;
+use crate::ATOM_TRANSITION;
+use crate::LAST_LED_STATE;
+use crate::LED_MAX;
+use crate::SCENE;
+use arrayvec::ArrayVec;
+use controller_core::keys::may_revoke;
+use controller_core::output::current_limit::CurrentLimit;
+use controller_core::output::smoothing::DEFAULT_SMOOTHING_FACTOR;
+use controller_core::session::{Accepted, Rejection, RejectionCounters, Sessions};
+use defmt::error;
+use defmt::info;
 use defmt::warn;
-use heapless::FnvIndexMap;
+use embassy_net::IpEndpoint;
+use embassy_time::Duration;
+use embassy_time::Instant;
+use lumen_proto::color_channel::ColorChannel;
+use lumen_proto::compression::apply_frame_delta;
+use lumen_proto::fragment::FragmentOutcome;
+use lumen_proto::fragment::FrameAssembler;
+use lumen_proto::fragment::LedFragment;
+use lumen_proto::fragment::LedFragmentRgbw;
+use lumen_proto::idle::IdleBehavior;
+use lumen_proto::message_id::MessageId;
+use lumen_proto::message_kind::MessageKind;
+use lumen_proto::reply::{ActiveSession, ControllerStatus, NackReason, ReplyKind};
+use lumen_proto::rgb8::Rgb8;
+use lumen_proto::rgbw8::Rgbw8;
+use lumen_proto::ControllerMessage;
 
-#[derive(Clone, Default)]
 pub struct MessageController {
-    message_timestamp_map: FnvIndexMap<MessageId, Timestamp, 64>,
+    authenticator: Authenticator,
+    auth_failures: AuthFailureCounters,
+    pairing: Pairing,
+    sessions: Sessions<IpEndpoint>,
+    rejections: RejectionCounters,
+    /// The newest frame sent to core 1. Ranges are patched onto it, so they can't land on a frame
+    /// core 1 already replaced.
+    frame: ArrayVec<Rgbw8, LED_MAX>,
+    /// Sequence number and content of the last compressed frame, deltas are applied to it.
+    delta_base: Option<(u16, ArrayVec<Rgb8, LED_MAX>)>,
+    frame_assembler: FrameAssembler,
+    frame_assembler_rgbw: FrameAssembler<Rgbw8>,
+    /// Gamma tables of the output, kept here so single channels can be updated.
+    gamma: GammaTables,
+    /// White balance of the output, kept here for the same reason.
+    color_correction: ColorCorrection,
+    /// Pixel format and color order of the output, kept here for the same reason.
+    pixel_settings: PixelSettings,
+    /// The effect running on core 1, streamed frames stop it.
+    effect: Option<EffectConfig>,
+    /// Incremented for every started effect, so core 1 can tell a restart from a parameter update.
+    effect_generation: u32,
+    /// The persisted idle behavior, so unchanged settings don't wear out the flash.
+    idle_behavior: IdleBehavior,
+    storage: Storage,
 }
 
+/// Incomplete fragmented frames are thrown away after this time.
+const FRAGMENT_TIMEOUT: Duration = Duration::from_millis(250);
+
 impl MessageController {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn new(
+        storage: Storage,
+        idle_behavior: IdleBehavior,
+        authenticator: Authenticator,
+    ) -> Self {
+        // Without any key the controller can only be reached by pairing
+        let first_boot = !authenticator.has_keys();
+        if first_boot {
+            info!("No client is paired yet, pairing until the first one is");
+        }
+
+        Self {
+            authenticator,
+            auth_failures: AuthFailureCounters::default(),
+            pairing: Pairing::new(first_boot),
+            sessions: Sessions::new(DEFAULT_SMOOTHING_FACTOR),
+            rejections: RejectionCounters::default(),
+            frame: ArrayVec::new(),
+            delta_base: None,
+            frame_assembler: FrameAssembler::default(),
+            frame_assembler_rgbw: FrameAssembler::default(),
+            gamma: GammaTables::default(),
+            color_correction: ColorCorrection::default(),
+            pixel_settings: PixelSettings::default(),
+            effect: None,
+            effect_generation: 0,
+            idle_behavior,
+            storage,
+        }
+    }
+
+    /// Verifies the signature of a received datagram or decrypts it, and returns the plain
+    /// datagram inside. Runs before the datagram is parsed, so unauthenticated senders can't
+    /// reach the parser. While pairing is possible plain datagrams get through, the client
+    /// doesn't have a key yet.
+    pub async fn authenticate<'a>(&mut self, datagram: &'a mut [u8]) -> Option<Authenticated<'a>> {
+        if let Some(held_at) = ATOM_PAIRING_BUTTON.recv().await {
+            info!(
+                "Pairing button held, pairing for {} s",
+                PAIRING_WINDOW.as_secs()
+            );
+            self.pairing.open(held_at);
+            ATOM_PAIRING_CODE.send(None).await;
+        }
+
+        let accept_plain = self.pairing.is_open(Instant::now());
+        match self
+            .authenticator
+            .check(datagram, accept_plain, &mut self.storage)
+        {
+            Ok(authenticated) => {
+                if authenticated.run_closed {
+                    self.auth_failures.record(AuthFailure::RunClosed);
+                    warn!(
+                        "Asking for a new run of nonces: {} ({})",
+                        AuthFailure::RunClosed,
+                        self.auth_failures
+                    );
+                }
+                Some(authenticated)
+            }
+            Err(failure) => {
+                self.auth_failures.record(failure);
+                warn!("Discarding datagram: {} ({})", failure, self.auth_failures);
+                None
+            }
+        }
     }
 
     /// Handles the application logic for the received message.
-    /// The message is only processed if the received message is newer than the last one.
+    /// The message is only processed if the received message is newer than the last one of the
+    /// sender and the sender drives the strip.
+    /// `key_id` is the key the datagram was signed or encrypted with, `None` if it was plain.
+    /// Returns the reply for messages that ask the controller for information, and the `Ack` or
+    /// `Nack` of control messages if the sender enabled them.
     pub async fn handle_msg_lumen(
         &mut self,
-        ControllerMessage { timestamp, kind }: ControllerMessage,
-    ) {
+        ControllerMessage {
+            timestamp,
+            sequence,
+            kind,
+        }: ControllerMessage,
+        sender: IpEndpoint,
+        key_id: Option<u8>,
+    ) -> Option<ReplyKind> {
+        let now = Instant::now();
         let message_id = MessageId::from(&kind);
-        let is_new_value = self.update_message_timestamp(message_id, timestamp);
-        if !is_new_value {
-            warn!("Discarding old message {:?}", message_id);
-            return;
+
+        // Plain datagrams only get through for pairing while signatures are required
+        let requires_key = match kind {
+            MessageKind::PairRequest { .. }
+            | MessageKind::PairCommit { .. }
+            | MessageKind::PairReveal { .. } => false,
+            MessageKind::RevokeClient { .. } | MessageKind::GetPairedClients => true,
+            _ => self.authenticator.is_required(),
+        };
+        if requires_key && key_id.is_none() {
+            self.auth_failures.record(AuthFailure::Unsigned);
+            warn!(
+                "Discarding message {:?} from {}: {} ({})",
+                message_id,
+                sender,
+                AuthFailure::Unsigned,
+                self.auth_failures
+            );
+            return None;
         }
 
+        // Clients pair before they open a session
+        if is_pairing(&kind) {
+            return self.handle_pairing(kind, sender, key_id, now).await;
+        }
+
+        let now_ms = now.as_millis();
+        for address in self.sessions.expire(now_ms) {
+            info!("Session of {} expired", address);
+        }
+
+        // Liveness and status queries don't take a session, they are meant for every client that
+        // passed the key check above. The reply goes out in the envelope of the query
         match kind {
-            MessageKind::Empty => {}
-            MessageKind::KeepAlive { duration } => ATOM_KEEP_ALIVE.send(duration).await,
-            MessageKind::LedState { led_values } => ATOM_LED_STATE.send(led_values).await,
+            MessageKind::Ping => return Some(ReplyKind::Pong),
+            MessageKind::GetStatus => return Some(ReplyKind::Status(self.status(sender).await)),
+            _ => {}
+        }
+
+        let session = match kind {
+            MessageKind::OpenSession {
+                client_id,
+                priority,
+                lease_millis,
+            } => {
+                let lease_ms = lease_millis as u64;
+                self.sessions
+                    .open(sender, client_id, priority, lease_ms, now_ms)
+            }
+            _ => self.sessions.get_or_open(sender, now_ms),
+        };
+        let Some(session) = session else {
+            self.rejections.record(Rejection::SessionsFull);
+            warn!(
+                "Discarding message from {}, all sessions are taken ({})",
+                sender, self.rejections
+            );
+            return None;
+        };
+
+        match session.accept(message_id, timestamp, sequence, now_ms) {
+            Ok(Accepted::InOrder) => {}
+            Ok(Accepted::Restarted) => info!("{} restarted", sender),
+            Err(rejection) => {
+                self.rejections.record(rejection);
+                warn!(
+                    "Discarding message {:?} from {}: {} ({})",
+                    message_id, sender, rejection, self.rejections
+                );
+                let acks = session.acks && !carries_frame(&kind);
+                return rejection
+                    .nack_reason()
+                    .and_then(|reason| nack(acks, reason));
+            }
+        }
+
+        // Session settings are kept for clients that don't drive the strip yet
+        match kind {
+            MessageKind::KeepAlive { .. } => session.renew(now_ms),
+            MessageKind::SetSmoothing { factor } => session.smoothing = factor,
+            MessageKind::SetAcks { enabled } => session.acks = enabled,
+            _ => {}
+        }
+        // Frames come too often to answer each of them
+        let acks = session.acks && !carries_frame(&kind);
+
+        if let Some(driver) = self.sessions.elect() {
+            info!("{} drives the strip now", driver.address);
+            let smoothing = driver.smoothing;
+            self.take_over(smoothing).await;
+        }
+
+        // Queries and the acks setting are handled for every client
+        let for_every_client = matches!(
+            kind,
+            MessageKind::GetEffectParams | MessageKind::SetAcks { .. }
+        );
+        if !self.sessions.is_driver(sender) && !for_every_client {
+            // Not logged, clients that lost the strip keep streaming until their lease runs out
+            self.rejections.record(Rejection::NotDriver);
+            return nack(acks, NackReason::NotDriver);
+        }
+
+        if self.effect.is_some() && carries_frame(&kind) {
+            self.effect = None;
+            ATOM_EFFECT.send(None).await;
+        }
+
+        match kind {
+            MessageKind::Empty | MessageKind::OpenSession { .. } => {}
+            MessageKind::KeepAlive { millis } => {
+                ATOM_KEEP_ALIVE
+                    .send(Duration::from_millis(millis as u64))
+                    .await
+            }
+            MessageKind::LedState {
+                led_values,
+                transition,
+            } => {
+                self.delta_base = None;
+                let mut frame = rgbw_frame(&led_values);
+                if transition {
+                    frame.transition = FrameTransition::Configured;
+                }
+                self.send_frame(frame).await
+            }
+            MessageKind::LedStateRgbw { led_values } => {
+                self.delta_base = None;
+                self.send_frame(LedFrame {
+                    values: led_values,
+                    transition: FrameTransition::Cut,
+                })
+                .await
+            }
+            MessageKind::LedRange { offset, values } => {
+                self.delta_base = None;
+                self.apply_led_range(offset, &values).await
+            }
+            MessageKind::LedStateRle {
+                sequence,
+                led_values,
+            } => {
+                self.send_frame(rgbw_frame(&led_values)).await;
+                self.delta_base = Some((sequence, led_values));
+            }
+            MessageKind::LedStateDelta {
+                sequence,
+                base_sequence,
+                delta,
+            } => self.apply_led_delta(sequence, base_sequence, &delta).await,
+            MessageKind::LedFragment(fragment) => self.apply_led_fragment(&fragment).await,
+            MessageKind::LedFragmentRgbw(fragment) => self.apply_led_fragment_rgbw(&fragment).await,
+            MessageKind::SetBrightness {
+                brightness,
+                fade_millis,
+            } => {
+                let fade = BrightnessFade {
+                    target: brightness,
+                    duration: Duration::from_millis(fade_millis as u64),
+                };
+                ATOM_BRIGHTNESS.send(fade).await
+            }
+            MessageKind::SetGamma { red, green, blue } => {
+                if red == 0 || green == 0 || blue == 0 {
+                    warn!("Discarding gamma exponent of zero");
+                    return nack(acks, NackReason::InvalidValue);
+                }
+                for (channel, exponent) in ColorChannel::ALL.into_iter().zip([red, green, blue]) {
+                    self.gamma.set_exponent(channel, exponent as f32 / 100.0);
+                }
+                ATOM_GAMMA.send(self.gamma.clone()).await
+            }
+            MessageKind::SetGammaTable { channel, table } => {
+                self.gamma.set_table(channel, table);
+                ATOM_GAMMA.send(self.gamma.clone()).await
+            }
+            MessageKind::SetWhiteBalance { red, green, blue } => {
+                self.color_correction.set_gains([red, green, blue]);
+                ATOM_COLOR_CORRECTION
+                    .send(self.color_correction.clone())
+                    .await
+            }
+            MessageKind::SetColorMatrix { matrix } => {
+                self.color_correction.set_matrix(matrix);
+                ATOM_COLOR_CORRECTION
+                    .send(self.color_correction.clone())
+                    .await
+            }
+            MessageKind::SetColorTemperature { kelvin } => {
+                self.color_correction.set_temperature(kelvin);
+                ATOM_COLOR_CORRECTION
+                    .send(self.color_correction.clone())
+                    .await
+            }
+            MessageKind::SetPixelFormat {
+                format,
+                extract_white,
+            } => {
+                self.pixel_settings.format = format;
+                self.pixel_settings.extract_white = extract_white;
+                ATOM_PIXEL_SETTINGS.send(self.pixel_settings).await
+            }
+            MessageKind::SetColorOrder { order } => {
+                self.pixel_settings.order = order;
+                ATOM_PIXEL_SETTINGS.send(self.pixel_settings).await
+            }
+            MessageKind::SetCurrentLimit {
+                budget_ma,
+                channel_ua,
+                idle_ua,
+            } => {
+                let mut current_limit = CurrentLimit::new();
+                if current_limit
+                    .configure(budget_ma, channel_ua, idle_ua)
+                    .is_err()
+                {
+                    warn!("Discarding current budget without channel currents");
+                    return nack(acks, NackReason::InvalidValue);
+                }
+                ATOM_CURRENT_LIMIT.send(current_limit).await
+            }
+            MessageKind::RunEffect {
+                id,
+                led_count,
+                params,
+            } => {
+                self.effect_generation = self.effect_generation.wrapping_add(1);
+                let config = EffectConfig {
+                    id,
+                    led_count,
+                    params,
+                    generation: self.effect_generation,
+                };
+                self.effect = Some(config);
+                ATOM_EFFECT.send(Some(config)).await
+            }
+            MessageKind::UpdateEffectParams { params } => {
+                let Some(effect) = &mut self.effect else {
+                    warn!("Discarding effect parameters, no effect is running");
+                    return nack(acks, NackReason::NoEffect);
+                };
+                for param in params {
+                    effect.params.apply(param);
+                }
+                ATOM_EFFECT.send(Some(*effect)).await
+            }
+            MessageKind::GetEffectParams => {
+                let effect = self.effect.map(|effect| (effect.id, effect.params));
+                return Some(ReplyKind::EffectParams { effect });
+            }
+            MessageKind::SetTransition {
+                duration_millis,
+                easing,
+            } => {
+                let transition = TransitionSettings {
+                    duration: Duration::from_millis(duration_millis as u64),
+                    easing,
+                };
+                ATOM_TRANSITION.send(transition).await
+            }
+            MessageKind::SetSmoothing { factor } => ATOM_SMOOTHING.send(factor).await,
+            MessageKind::SetIdleBehavior(behavior) => {
+                ATOM_IDLE_BEHAVIOR.send(behavior).await;
+                if behavior != self.idle_behavior {
+                    self.idle_behavior = behavior;
+                    if let Err(e) = self.storage.store(Slot::IdleBehavior, &behavior) {
+                        error!("Failed to save idle behavior: {}", e);
+                    }
+                }
+            }
+            MessageKind::SaveScene => {
+                let scene = Scene {
+                    values: LAST_LED_STATE.lock().await.clone(),
+                };
+                if let Err(e) = self.storage.store(Slot::Scene, &scene) {
+                    error!("Failed to save scene: {}", e);
+                }
+                *SCENE.lock().await = scene;
+            }
+            MessageKind::PairRequest { .. }
+            | MessageKind::PairCommit { .. }
+            | MessageKind::PairReveal { .. }
+            | MessageKind::RevokeClient { .. }
+            | MessageKind::GetPairedClients
+            | MessageKind::Ping
+            | MessageKind::GetStatus
+            | MessageKind::SetAcks { .. } => {}
+        }
+
+        acks.then_some(ReplyKind::Ack)
+    }
+
+    async fn status(&self, sender: IpEndpoint) -> ControllerStatus {
+        ControllerStatus {
+            firmware_version: FIRMWARE_VERSION,
+            led_count: LAST_LED_STATE.lock().await.len() as u16,
+            active_session: self.sessions.driver().map(|session| ActiveSession {
+                client_id: session.client_id,
+                priority: session.priority,
+                is_sender: session.address == sender,
+            }),
+            session_count: self.sessions.count() as u8,
+            uptime_millis: Instant::now().as_millis(),
+            free_memory: free_memory(),
         }
     }
 
-    /// Updates the timestamp of a message if the new timestamp is greater than the current one.
-    /// Returns true if the value was updated.
-    fn update_message_timestamp(&mut self, message_id: MessageId, timestamp: Timestamp) -> bool {
-        let is_new_value = match self.message_timestamp_map.entry(message_id) {
-            heapless::Entry::Occupied(entry) => {
-                if *entry.get() < timestamp {
-                    entry.insert(timestamp);
-                    true
-                } else {
-                    false
+    /// Handles the messages that pair clients and manage their keys.
+    async fn handle_pairing(
+        &mut self,
+        kind: MessageKind,
+        sender: IpEndpoint,
+        key_id: Option<u8>,
+        now: Instant,
+    ) -> Option<ReplyKind> {
+        let message_id = MessageId::from(&kind);
+        let failure = match kind {
+            MessageKind::PairRequest { .. } if self.authenticator.is_full() => {
+                warn!(
+                    "Discarding pairing request from {}, all keys are taken",
+                    sender
+                );
+                return None;
+            }
+            MessageKind::PairRequest { public_key } => {
+                match self.pairing.request(sender, &public_key, now) {
+                    Ok((public_key, display)) => {
+                        info!("Pairing with {}, showing code {}", sender, display.code);
+                        ATOM_PAIRING_CODE.send(Some(display)).await;
+                        return Some(ReplyKind::PairResponse { public_key });
+                    }
+                    Err(failure) => failure,
+                }
+            }
+            MessageKind::PairCommit { commitment } => {
+                match self.pairing.commit(sender, &commitment, now) {
+                    Ok(commitment) => return Some(ReplyKind::PairCommitted { commitment }),
+                    Err(failure) => failure,
+                }
+            }
+            MessageKind::PairReveal { nonce } => match self.pairing.reveal(sender, &nonce, now) {
+                Ok((nonce, None)) => {
+                    return Some(ReplyKind::PairRevealed {
+                        nonce,
+                        key_id: None,
+                    })
                 }
+                Ok((nonce, Some(key))) => {
+                    ATOM_PAIRING_CODE.send(None).await;
+                    let Some(key_id) = self.authenticator.pair(key, &mut self.storage) else {
+                        error!("Failed to pair {}, all keys are taken", sender);
+                        return None;
+                    };
+                    info!("Paired {} with key {}", sender, key_id);
+                    return Some(ReplyKind::PairRevealed {
+                        nonce,
+                        key_id: Some(key_id),
+                    });
+                }
+                Err(failure) => failure,
+            },
+            MessageKind::RevokeClient { key_id: revoked } => {
+                if !may_revoke(key_id, revoked, self.pairing.is_open(now)) {
+                    warn!(
+                        "Discarding revocation of key {} signed with key {}",
+                        revoked, key_id
+                    );
+                    return Some(ReplyKind::Nack {
+                        reason: NackReason::NotPermitted,
+                    });
+                }
+                if !self.authenticator.revoke(revoked, &mut self.storage) {
+                    warn!("Discarding revocation of unknown key {}", revoked);
+                    return Some(ReplyKind::Nack {
+                        reason: NackReason::InvalidValue,
+                    });
+                }
+                info!("Revoked key {}", revoked);
+                return Some(ReplyKind::Ack);
             }
-            heapless::Entry::Vacant(entry) => {
-                entry.insert(timestamp).unwrap();
-                true
+            MessageKind::GetPairedClients => {
+                return Some(ReplyKind::PairedClients {
+                    key_ids: self.authenticator.paired_key_ids(),
+                })
             }
+            _ => return None,
+        };
+
+        warn!(
+            "Discarding message {:?} from {}: {}",
+            message_id, sender, failure
+        );
+        // The handshake ended, the code must not be typed in again
+        if failure == PairingFailure::WrongCode {
+            ATOM_PAIRING_CODE.send(None).await;
+        }
+        None
+    }
+
+    /// Prepares the output for a session that just started to drive the strip.
+    /// Partial frames of the previous driver must not be combined with the new ones.
+    async fn take_over(&mut self, smoothing: u8) {
+        self.delta_base = None;
+        self.frame_assembler = FrameAssembler::default();
+        self.frame_assembler_rgbw = FrameAssembler::default();
+        ATOM_SMOOTHING.send(smoothing).await;
+    }
+
+    /// Collects the fragments of a frame and sends the frame to the strip once it is complete.
+    async fn apply_led_fragment(&mut self, fragment: &LedFragment) {
+        let now = Instant::now().as_millis();
+        let outcome = self
+            .frame_assembler
+            .push(fragment, now, FRAGMENT_TIMEOUT.as_millis());
+        if let Some(frame) = completed_frame(outcome, fragment.frame_id) {
+            self.delta_base = None;
+            self.send_frame(rgbw_frame(&frame)).await;
+        }
+    }
+
+    /// Same as `apply_led_fragment` for the fragments of RGBW frames.
+    async fn apply_led_fragment_rgbw(&mut self, fragment: &LedFragmentRgbw) {
+        let now = Instant::now().as_millis();
+        let outcome = self
+            .frame_assembler_rgbw
+            .push(fragment, now, FRAGMENT_TIMEOUT.as_millis());
+        if let Some(values) = completed_frame(outcome, fragment.frame_id) {
+            self.delta_base = None;
+            self.send_frame(LedFrame {
+                values,
+                transition: FrameTransition::Cut,
+            })
+            .await;
+        }
+    }
+
+    /// Applies a delta frame if it was computed against the last compressed frame we received.
+    /// Deltas against any other frame are discarded, the client has to send a new full frame.
+    async fn apply_led_delta(&mut self, sequence: u16, base_sequence: u16, delta: &[Rgb8]) {
+        let Some((current_sequence, frame)) = &mut self.delta_base else {
+            warn!("Discarding delta frame {}, no base frame", sequence);
+            return;
         };
-        is_new_value
+
+        if *current_sequence != base_sequence {
+            warn!(
+                "Discarding delta frame {}, expected base {} but have {}",
+                sequence, base_sequence, current_sequence
+            );
+            return;
+        }
+
+        if !apply_frame_delta(frame, delta) {
+            warn!(
+                "Discarding delta frame {}, length does not match base",
+                sequence
+            );
+            return;
+        }
+
+        *current_sequence = sequence;
+        let frame = rgbw_frame(frame);
+        self.send_frame(frame).await;
+    }
+
+    /// Patches the given range into the newest frame and sends the whole frame to the strip.
+    async fn apply_led_range(&mut self, offset: u16, values: &[Rgb8]) {
+        let offset = offset as usize;
+        let end = offset + values.len();
+        while self.frame.len() < end {
+            self.frame.push(Rgbw8::default());
+        }
+        for (led, &value) in self.frame[offset..end].iter_mut().zip(values) {
+            *led = value.into();
+        }
+
+        ATOM_LED_STATE
+            .send(LedFrame {
+                values: self.frame.clone(),
+                transition: FrameTransition::Cut,
+            })
+            .await;
+    }
+
+    /// Sends a frame to the strip and keeps it as the base of later ranges.
+    async fn send_frame(&mut self, frame: LedFrame) {
+        self.frame.clone_from(&frame.values);
+        ATOM_LED_STATE.send(frame).await;
+    }
+}
+
+/// Returns true for messages that pair clients or manage their keys.
+fn is_pairing(kind: &MessageKind) -> bool {
+    matches!(
+        kind,
+        MessageKind::PairRequest { .. }
+            | MessageKind::PairCommit { .. }
+            | MessageKind::PairReveal { .. }
+            | MessageKind::RevokeClient { .. }
+            | MessageKind::GetPairedClients
+    )
+}
+
+/// Returns true for messages that change the LEDs of the strip directly.
+fn carries_frame(kind: &MessageKind) -> bool {
+    matches!(
+        kind,
+        MessageKind::LedState { .. }
+            | MessageKind::LedRange { .. }
+            | MessageKind::LedStateRle { .. }
+            | MessageKind::LedStateDelta { .. }
+            | MessageKind::LedFragment(_)
+            | MessageKind::LedStateRgbw { .. }
+            | MessageKind::LedFragmentRgbw(_)
+    )
+}
+
+/// Returns the frame a fragment completed and logs why a fragment was discarded.
+fn completed_frame<P>(outcome: FragmentOutcome<P>, frame_id: u16) -> Option<ArrayVec<P, LED_MAX>> {
+    match outcome {
+        FragmentOutcome::Pending => None,
+        FragmentOutcome::Complete(frame) => Some(frame),
+        FragmentOutcome::Stale => {
+            warn!("Discarding fragment of old frame {}", frame_id);
+            None
+        }
+        FragmentOutcome::Inconsistent => {
+            warn!("Discarding inconsistent fragment of frame {}", frame_id);
+            None
+        }
+    }
+}
+
+/// The `Nack` of a discarded control message, `None` if the sender didn't enable acks.
+fn nack(acks: bool, reason: NackReason) -> Option<ReplyKind> {
+    acks.then_some(ReplyKind::Nack { reason })
+}
+
+/// Converts a frame of RGB values to the RGBW frames the output works with.
+fn rgbw_frame(values: &[Rgb8]) -> LedFrame {
+    LedFrame {
+        values: values.iter().map(|&rgb| Rgbw8::from(rgb)).collect(),
+        transition: FrameTransition::Cut,
     }
 }
@@ -0,0 +1,64 @@
+use super::Rgbw16;
+use embassy_time::{Duration, Instant};
+
+/// A requested change of the master brightness.
+#[derive(Debug, Clone, Copy)]
+pub struct BrightnessFade {
+    pub target: u8,
+    pub duration: Duration,
+}
+
+/// Master brightness of the strip, fading linearly between levels.
+pub struct Brightness {
+    from: u8,
+    to: u8,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Brightness {
+    pub const fn new() -> Self {
+        Self {
+            from: u8::MAX,
+            to: u8::MAX,
+            started: Instant::from_ticks(0),
+            duration: Duration::from_ticks(0),
+        }
+    }
+
+    /// Starts fading from the current level to the target of `fade`.
+    pub fn fade(&mut self, fade: BrightnessFade) {
+        self.from = self.current();
+        self.to = fade.target;
+        self.started = Instant::now();
+        self.duration = fade.duration;
+    }
+
+    /// Returns the brightness level for this moment of the fade.
+    pub fn current(&self) -> u8 {
+        let elapsed = self.started.elapsed();
+        if elapsed >= self.duration {
+            return self.to;
+        }
+
+        let progress = elapsed.as_ticks() * 256 / self.duration.as_ticks();
+        let delta = (self.to as i64 - self.from as i64) * progress as i64 / 256;
+        (self.from as i64 + delta) as u8
+    }
+
+    /// Returns true while a fade is in progress.
+    pub fn is_fading(&self) -> bool {
+        self.started.elapsed() < self.duration
+    }
+
+    /// Scales a color by the given brightness level. Full brightness leaves the color unchanged.
+    pub fn apply(level: u8, rgbw: Rgbw16) -> Rgbw16 {
+        rgbw.map(|c| (c as u32 * level as u32 / u8::MAX as u32) as u16)
+    }
+}
+
+impl Default for Brightness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,91 @@
+use super::Rgbw16;
+
+/// White balance gains and an optional color matrix, applied to every pixel before output.
+#[derive(Debug, Clone)]
+pub struct ColorCorrection {
+    /// Per-channel gains, 255 leaves the channel unchanged.
+    gains: [u8; 3],
+    /// Row-major 3x3 matrix in 8.8 fixed point.
+    matrix: Option<[i16; 9]>,
+}
+
+impl ColorCorrection {
+    pub const fn new() -> Self {
+        Self {
+            gains: [u8::MAX; 3],
+            matrix: None,
+        }
+    }
+
+    pub fn set_gains(&mut self, gains: [u8; 3]) {
+        self.gains = gains;
+    }
+
+    pub fn set_matrix(&mut self, matrix: Option<[i16; 9]>) {
+        self.matrix = matrix;
+    }
+
+    /// Sets the gains so white is shown at the given color temperature.
+    pub fn set_temperature(&mut self, kelvin: u16) {
+        self.gains = gains_for_temperature(kelvin);
+    }
+
+    /// Mixes the channels through the matrix and scales them by the gains.
+    /// The white channel of RGBW pixels is left untouched.
+    pub fn apply(&self, rgbw: Rgbw16) -> Rgbw16 {
+        let (r, g, b) = match &self.matrix {
+            Some(matrix) => {
+                let input = [rgbw.r as i64, rgbw.g as i64, rgbw.b as i64];
+                let row = |i: usize| {
+                    let sum: i64 = (0..3).map(|j| matrix[i * 3 + j] as i64 * input[j]).sum();
+                    (sum >> 8).clamp(0, u16::MAX as i64) as u16
+                };
+                (row(0), row(1), row(2))
+            }
+            None => (rgbw.r, rgbw.g, rgbw.b),
+        };
+
+        let scale = |c: u16, gain: u8| (c as u32 * gain as u32 / u8::MAX as u32) as u16;
+        let [gr, gg, gb] = self.gains;
+        Rgbw16 {
+            r: scale(r, gr),
+            g: scale(g, gg),
+            b: scale(b, gb),
+            w: rgbw.w,
+        }
+    }
+}
+
+impl Default for ColorCorrection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Approximates the color of a black body at the given temperature, after Tanner Helland.
+/// The result is scaled so the strongest channel stays at full intensity.
+pub fn gains_for_temperature(kelvin: u16) -> [u8; 3] {
+    let temperature = kelvin.clamp(1000, 40000) as f32 / 100.0;
+
+    let red = if temperature <= 66.0 {
+        255.0
+    } else {
+        329.69873 * libm::powf(temperature - 60.0, -0.13320476)
+    };
+    let green = if temperature <= 66.0 {
+        99.4708 * libm::logf(temperature) - 161.11957
+    } else {
+        288.12216 * libm::powf(temperature - 60.0, -0.07551485)
+    };
+    let blue = if temperature >= 66.0 {
+        255.0
+    } else if temperature <= 19.0 {
+        0.0
+    } else {
+        138.51773 * libm::logf(temperature - 10.0) - 305.0448
+    };
+
+    let channels = [red, green, blue].map(|c| c.clamp(0.0, 255.0));
+    let max = channels.iter().copied().fold(1.0, f32::max);
+    channels.map(|c| libm::roundf(c * 255.0 / max) as u8)
+}
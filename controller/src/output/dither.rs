@@ -0,0 +1,88 @@
+use super::Rgbw16;
+use crate::LED_MAX;
+use lumen_proto::rgbw8::Rgbw8;
+
+/// Temporal dithering from the 16 bit pipeline to the 8 bits of the strip.
+/// The part of a value that is lost when it is quantized is carried over to the next refresh,
+/// so the average over several refreshes shows the full precision.
+/// Once a frame was shown for a whole [`ERROR_CYCLE`] the errors only repeat, so the output
+/// stops refreshing until the frame changes.
+pub struct Dither {
+    errors: [[u8; 4]; LED_MAX],
+    /// True if the last frame had values between two 8 bit steps.
+    fractional: bool,
+    /// Checksum of the last frame, tells whether a frame differs from the one before.
+    checksum: u32,
+    /// Number of refreshes the frame stayed the same for.
+    unchanged: u16,
+}
+
+/// Number of refreshes after which the errors of an unchanged frame are back where they started.
+/// Each refresh adds the same fraction of an 8 bit step, which is a multiple of 1/256.
+const ERROR_CYCLE: u16 = 256;
+
+impl Dither {
+    pub const fn new() -> Self {
+        Self {
+            errors: [[0; 4]; LED_MAX],
+            fractional: false,
+            checksum: 0,
+            unchanged: 0,
+        }
+    }
+
+    /// Returns true while the output needs further refreshes to show the full precision.
+    pub fn is_active(&self) -> bool {
+        self.fractional && self.unchanged < ERROR_CYCLE
+    }
+
+    /// Quantizes the colors of a frame and appends them to `output`.
+    pub fn quantize(
+        &mut self,
+        frame: impl Iterator<Item = Rgbw16>,
+        output: &mut impl Extend<Rgbw8>,
+    ) {
+        let mut fractional = false;
+        let mut checksum = FNV_OFFSET;
+        let errors = self.errors.iter_mut();
+        let quantized = frame.zip(errors).map(|(color, [er, eg, eb, ew])| {
+            let Rgbw16 { r, g, b, w } = color;
+            for c in [r, g, b, w] {
+                fractional |= c & 0xff != 0;
+                checksum = (checksum ^ u32::from(c)).wrapping_mul(FNV_PRIME);
+            }
+            Rgbw8 {
+                r: quantize_channel(r, er),
+                g: quantize_channel(g, eg),
+                b: quantize_channel(b, eb),
+                w: quantize_channel(w, ew),
+            }
+        });
+        output.extend(quantized);
+
+        self.unchanged = match checksum == self.checksum {
+            true => self.unchanged.saturating_add(1),
+            false => 0,
+        };
+        self.checksum = checksum;
+        self.fractional = fractional;
+    }
+}
+
+impl Default for Dither {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// FNV-1a, good enough to notice a changed frame
+const FNV_OFFSET: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// Adds the error carried over from the last refresh and keeps the new remainder.
+fn quantize_channel(value: u16, error: &mut u8) -> u8 {
+    let total = value as u32 + *error as u32;
+    let quantized = (total >> 8).min(u8::MAX as u32);
+    *error = (total - (quantized << 8)).min(u8::MAX as u32) as u8;
+    quantized as u8
+}
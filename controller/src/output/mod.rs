@@ -0,0 +1,176 @@
+pub mod brightness;
+pub mod color_correction;
+pub mod dither;
+pub mod gamma;
+pub mod oklab;
+pub mod pixel_format;
+pub mod transition;
+
+use crate::ATOM_BRIGHTNESS;
+use crate::ATOM_COLOR_CORRECTION;
+use crate::ATOM_CURRENT_LIMIT;
+use crate::ATOM_GAMMA;
+use crate::ATOM_PIXEL_SETTINGS;
+use crate::ATOM_SMOOTHING;
+use crate::ATOM_TRANSITION;
+use crate::LED_MAX;
+use arrayvec::ArrayVec;
+use brightness::Brightness;
+use color_correction::ColorCorrection;
+use controller_core::output::current_limit::{CurrentEstimate, CurrentLimit};
+use controller_core::output::smoothing::Smoothing;
+use dither::Dither;
+use embassy_time::Instant;
+use gamma::GammaTables;
+use lumen_proto::color_order::ColorOrder;
+use lumen_proto::pixel_format::PixelFormat;
+use lumen_proto::rgbw8::Rgbw8;
+use pixel_format::PixelSettings;
+use transition::{FrameTransition, Transition};
+
+pub use controller_core::output::Rgbw16;
+
+/// A frame for the strip and how the output changes to it.
+#[derive(Debug, Clone, Default)]
+pub struct LedFrame {
+    pub values: ArrayVec<Rgbw8, LED_MAX>,
+    pub transition: FrameTransition,
+}
+
+/// Transforms the received frames into the values that are written to the strip.
+/// The pipeline runs on core 1 right before the frame is handed to the `Ws2812` driver.
+pub struct OutputPipeline {
+    brightness: Brightness,
+    gamma: GammaTables,
+    color_correction: ColorCorrection,
+    pixel_settings: PixelSettings,
+    current_limit: CurrentLimit,
+    current_estimate: CurrentEstimate,
+    dither: Dither,
+    transition: Transition,
+    smoothing: Smoothing,
+    /// The frame as it is shown before the output stages, the start of the next transition.
+    shown: ArrayVec<Rgbw8, LED_MAX>,
+    precise_buffer: ArrayVec<Rgbw16, LED_MAX>,
+    buffer: ArrayVec<Rgbw8, LED_MAX>,
+}
+
+impl OutputPipeline {
+    pub const fn new() -> Self {
+        Self {
+            brightness: Brightness::new(),
+            gamma: GammaTables::new(),
+            color_correction: ColorCorrection::new(),
+            pixel_settings: PixelSettings::new(),
+            current_limit: CurrentLimit::new(),
+            current_estimate: CurrentEstimate {
+                requested_ma: 0,
+                output_ma: 0,
+            },
+            dither: Dither::new(),
+            transition: Transition::new(),
+            smoothing: Smoothing::new(),
+            shown: ArrayVec::new_const(),
+            precise_buffer: ArrayVec::new_const(),
+            buffer: ArrayVec::new_const(),
+        }
+    }
+
+    /// Applies pending settings changes. Returns true if the output has to be refreshed.
+    pub async fn update_settings(&mut self) -> bool {
+        let mut changed = false;
+        if let Some(fade) = ATOM_BRIGHTNESS.recv().await {
+            self.brightness.fade(fade);
+            changed = true;
+        }
+        if let Some(gamma) = ATOM_GAMMA.recv().await {
+            self.gamma = gamma;
+            changed = true;
+        }
+        if let Some(color_correction) = ATOM_COLOR_CORRECTION.recv().await {
+            self.color_correction = color_correction;
+            changed = true;
+        }
+        if let Some(pixel_settings) = ATOM_PIXEL_SETTINGS.recv().await {
+            self.pixel_settings = pixel_settings;
+            changed = true;
+        }
+        if let Some(current_limit) = ATOM_CURRENT_LIMIT.recv().await {
+            self.current_limit = current_limit;
+            changed = true;
+        }
+        if let Some(transition) = ATOM_TRANSITION.recv().await {
+            self.transition.configure(transition);
+        }
+        if let Some(factor) = ATOM_SMOOTHING.recv().await {
+            self.smoothing.configure(factor);
+        }
+        changed
+    }
+
+    /// Returns true while the output changes over time, even without a new frame.
+    /// Dithered frames are refreshed until the errors evened out, smoothed frames until they
+    /// reached the newest frame.
+    pub fn is_animating(&self) -> bool {
+        self.brightness.is_fading()
+            || self.dither.is_active()
+            || self.transition.is_active()
+            || self.smoothing.is_active()
+    }
+
+    /// Fades from the frame that is currently shown to the frames processed next.
+    pub fn start_transition(&mut self, transition: FrameTransition) {
+        self.transition.start(&self.shown, transition);
+    }
+
+    /// The kind of LEDs the processed frames are meant for.
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_settings.format
+    }
+
+    /// The order the strip expects the color channels in.
+    pub fn color_order(&self) -> ColorOrder {
+        self.pixel_settings.order
+    }
+
+    /// The estimated current draw of the last processed frame.
+    pub fn current_estimate(&self) -> CurrentEstimate {
+        self.current_estimate
+    }
+
+    /// Runs the frame through all stages of the pipeline.
+    pub fn process(&mut self, frame: &[Rgbw8]) -> &[Rgbw8] {
+        if self.transition.is_active() {
+            self.transition.render(frame, &mut self.shown);
+        } else {
+            self.shown.clear();
+            self.shown.extend(frame.iter().copied());
+        }
+
+        self.precise_buffer.clear();
+        self.precise_buffer
+            .extend(self.shown.iter().map(|&rgbw| Rgbw16::from(rgbw)));
+        self.smoothing
+            .apply(&mut self.precise_buffer, Instant::now().as_micros());
+
+        let level = self.brightness.current();
+        for rgbw in self.precise_buffer.iter_mut() {
+            let color = Brightness::apply(level, *rgbw);
+            let color = self.gamma.apply(color);
+            let color = self.color_correction.apply(color);
+            *rgbw = self.pixel_settings.apply(color);
+        }
+        self.current_estimate = self.current_limit.limit(&mut self.precise_buffer);
+
+        self.buffer.clear();
+        self.dither
+            .quantize(self.precise_buffer.iter().copied(), &mut self.buffer);
+        &self.buffer
+    }
+}
+
+impl Default for OutputPipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,52 @@
+use super::Rgbw16;
+use lumen_proto::color_order::ColorOrder;
+use lumen_proto::pixel_format::PixelFormat;
+
+/// The kind of LEDs the output drives, how the white channel is produced and the order
+/// the strip expects the channels in.
+#[derive(Debug, Clone, Copy)]
+pub struct PixelSettings {
+    pub format: PixelFormat,
+    /// Moves the common part of red, green and blue to the white channel.
+    pub extract_white: bool,
+    pub order: ColorOrder,
+}
+
+impl PixelSettings {
+    pub const fn new() -> Self {
+        Self {
+            format: PixelFormat::Rgb,
+            extract_white: false,
+            order: ColorOrder::Grb,
+        }
+    }
+
+    /// Adapts the white channel of a pixel to the connected LEDs.
+    /// RGB strips have no white LED, so white is mixed back into the other channels.
+    pub fn apply(&self, Rgbw16 { r, g, b, w }: Rgbw16) -> Rgbw16 {
+        match self.format {
+            PixelFormat::Rgb => Rgbw16 {
+                r: r.saturating_add(w),
+                g: g.saturating_add(w),
+                b: b.saturating_add(w),
+                w: 0,
+            },
+            PixelFormat::Rgbw if self.extract_white => {
+                let common = r.min(g).min(b);
+                Rgbw16 {
+                    r: r - common,
+                    g: g - common,
+                    b: b - common,
+                    w: w.saturating_add(common),
+                }
+            }
+            PixelFormat::Rgbw => Rgbw16 { r, g, b, w },
+        }
+    }
+}
+
+impl Default for PixelSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,158 @@
+use super::oklab::Oklab;
+use crate::LED_MAX;
+use arrayvec::ArrayVec;
+use embassy_time::{Duration, Instant};
+use lumen_proto::rgb8::Rgb8;
+use lumen_proto::rgbw8::Rgbw8;
+use lumen_proto::transition::Easing;
+
+/// Duration and easing of transitions, a duration of zero switches without a fade.
+#[derive(Debug, Clone, Copy)]
+pub struct TransitionSettings {
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+impl TransitionSettings {
+    pub const fn new() -> Self {
+        Self {
+            duration: Duration::from_ticks(0),
+            easing: Easing::Linear,
+        }
+    }
+}
+
+impl Default for TransitionSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How the output changes to a new frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FrameTransition {
+    /// Shows the frame right away.
+    #[default]
+    Cut,
+    /// Fades with the settings configured by the client.
+    Configured,
+    /// Fades over the given duration with the configured easing.
+    Fade(Duration),
+}
+
+/// Crossfade from the frame that was shown when the transition started to the current frame.
+/// Colors are interpolated in OKLab so the fade looks even, the white channel linearly.
+pub struct Transition {
+    settings: TransitionSettings,
+    /// Duration of the running transition, which may differ from the configured one.
+    duration: Duration,
+    from: [Oklab; LED_MAX],
+    from_white: [u8; LED_MAX],
+    from_len: usize,
+    /// The target colors converted last, the target only changes with new frames.
+    to_rgb: [Rgb8; LED_MAX],
+    to: [Oklab; LED_MAX],
+    started: Instant,
+    active: bool,
+}
+
+impl Transition {
+    pub const fn new() -> Self {
+        Self {
+            settings: TransitionSettings::new(),
+            duration: Duration::from_ticks(0),
+            from: [Oklab::BLACK; LED_MAX],
+            from_white: [0; LED_MAX],
+            from_len: 0,
+            // Black converts to black, so the cache starts out valid
+            to_rgb: [Rgb8 { r: 0, g: 0, b: 0 }; LED_MAX],
+            to: [Oklab::BLACK; LED_MAX],
+            started: Instant::from_ticks(0),
+            active: false,
+        }
+    }
+
+    pub fn configure(&mut self, settings: TransitionSettings) {
+        self.settings = settings;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Starts a fade away from `shown`, the frame that is currently on the strip.
+    /// A cut keeps a running transition going, it then fades to the new frame instead.
+    pub fn start(&mut self, shown: &[Rgbw8], transition: FrameTransition) {
+        let duration = match transition {
+            FrameTransition::Cut => return,
+            FrameTransition::Configured => self.settings.duration,
+            FrameTransition::Fade(duration) => duration,
+        };
+        if duration.as_ticks() == 0 {
+            self.active = false;
+            return;
+        }
+
+        for ((lab, white), rgbw) in self
+            .from
+            .iter_mut()
+            .zip(self.from_white.iter_mut())
+            .zip(shown)
+        {
+            *lab = Oklab::from_rgb(rgb(*rgbw));
+            *white = rgbw.w;
+        }
+        self.from_len = shown.len();
+        self.duration = duration;
+        self.started = Instant::now();
+        self.active = true;
+    }
+
+    /// Writes the mix of the start frame and `target` for this moment of the transition.
+    /// LEDs missing in either frame are treated as black.
+    pub fn render(&mut self, target: &[Rgbw8], output: &mut ArrayVec<Rgbw8, LED_MAX>) {
+        output.clear();
+
+        let elapsed = self.started.elapsed();
+        if elapsed >= self.duration {
+            self.active = false;
+            output.extend(target.iter().copied());
+            return;
+        }
+
+        let linear = (elapsed.as_ticks() << 16) / self.duration.as_ticks();
+        let progress = self.settings.easing.apply(linear as u32);
+        let len = target.len().max(self.from_len);
+        output.extend((0..len).map(|i| {
+            let (from, from_white) = match i < self.from_len {
+                true => (self.from[i], self.from_white[i]),
+                false => (Oklab::BLACK, 0),
+            };
+            let to = target.get(i).copied().unwrap_or_default();
+            if rgb(to) != self.to_rgb[i] {
+                self.to_rgb[i] = rgb(to);
+                self.to[i] = Oklab::from_rgb(rgb(to));
+            }
+
+            let Rgb8 { r, g, b } = from.lerp(self.to[i], progress).to_rgb();
+            let w =
+                from_white as i32 + (((to.w as i32 - from_white as i32) * progress as i32) >> 16);
+            Rgbw8 {
+                r,
+                g,
+                b,
+                w: w as u8,
+            }
+        }));
+    }
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn rgb(Rgbw8 { r, g, b, .. }: Rgbw8) -> Rgb8 {
+    Rgb8 { r, g, b }
+}
@@ -0,0 +1,275 @@
+use crate::LED_MAX;
+use arrayvec::ArrayVec;
+use embassy_net::IpEndpoint;
+use embassy_rp::clocks::RoscRng;
+use embassy_time::{Duration, Instant};
+use lumen_proto::auth::Key;
+use lumen_proto::pairing::{
+    commitment, verify_commitment, Commitment, KeyExchange, PairingCode, PairingNonce, PublicKey,
+    Side, CODE_LEN,
+};
+use lumen_proto::rgb8::Rgb8;
+use lumen_proto::rgbw8::Rgbw8;
+use rand::RngCore;
+
+/// How long pairing stays possible after the pairing button was held.
+pub const PAIRING_WINDOW: Duration = Duration::from_secs(120);
+/// How long the pairing button has to be held.
+pub const BUTTON_HOLD: Duration = Duration::from_secs(3);
+/// A handshake has to be completed within this time, the code disappears afterwards.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Pairing ends after this many wrong codes, so the code can't be guessed by trying.
+const ATTEMPTS_MAX: u8 = 3;
+
+/// LEDs showing one color of the code, followed by a dark one.
+const LEDS_PER_COLOR: usize = 3;
+/// Share of the full brightness the code is shown with, in 1/256.
+const CODE_BRIGHTNESS: u16 = 64;
+const BLINK_PERIOD_MS: u64 = 1000;
+const BLINK_ON_MS: u64 = 750;
+
+/// Reasons why a pairing message was discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum PairingFailure {
+    /// The controller is not in pairing mode.
+    Closed,
+    /// The message doesn't continue a handshake of the sender.
+    NoHandshake,
+    /// The public key of the client is one that yields a known key.
+    WeakKey,
+    /// The client committed to another color than the one on the strip.
+    WrongCode,
+}
+
+/// The code shown on the strip while a handshake runs, sent to core 1.
+#[derive(Debug, Clone, Copy)]
+pub struct CodeDisplay {
+    pub code: PairingCode,
+    pub until: Instant,
+}
+
+struct Handshake {
+    client: IpEndpoint,
+    key: Key,
+    code: PairingCode,
+    /// Index of the color the commitments are about.
+    round: usize,
+    /// Commitment of the client and nonce of the controller in the current round.
+    committed: Option<(Commitment, PairingNonce)>,
+    expires: Instant,
+}
+
+/// The pairing mode of the controller, see `lumen_proto::pairing`.
+///
+/// Pairing is possible until the first client paired if the controller has no key at all, and
+/// for [`PAIRING_WINDOW`] after the pairing button was held. It ends with the first paired
+/// client. One handshake runs at a time, a new request replaces the running one.
+pub struct Pairing {
+    /// End of the pairing mode, `None` if it is closed.
+    open_until: Option<Instant>,
+    failed_attempts: u8,
+    handshake: Option<Handshake>,
+}
+
+impl Pairing {
+    pub fn new(first_boot: bool) -> Self {
+        Self {
+            open_until: first_boot.then_some(Instant::MAX),
+            failed_attempts: 0,
+            handshake: None,
+        }
+    }
+
+    /// Opens the pairing mode for [`PAIRING_WINDOW`] from `at`. The running handshake ends, so
+    /// the next one shows a new code. Clients don't enter a code twice, someone in between would
+    /// learn a color of it with every attempt.
+    pub fn open(&mut self, at: Instant) {
+        self.open_until = Some(at + PAIRING_WINDOW);
+        self.failed_attempts = 0;
+        self.handshake = None;
+    }
+
+    pub fn is_open(&self, now: Instant) -> bool {
+        self.open_until.is_some_and(|until| now < until)
+    }
+
+    /// Starts a handshake with the client of `public_key`. Returns the public key of the
+    /// controller and the code to show on the strip.
+    pub fn request(
+        &mut self,
+        client: IpEndpoint,
+        public_key: &PublicKey,
+        now: Instant,
+    ) -> Result<(PublicKey, CodeDisplay), PairingFailure> {
+        let Some(open_until) = self.open_until.filter(|_| self.is_open(now)) else {
+            return Err(PairingFailure::Closed);
+        };
+
+        let mut secret = [0; 32];
+        RoscRng.fill_bytes(&mut secret);
+        let exchange = KeyExchange::new(secret);
+        let key = exchange
+            .controller_key(public_key)
+            .ok_or(PairingFailure::WeakKey)?;
+
+        let code = PairingCode::new(RoscRng.next_u32() as u16);
+        let expires = (now + HANDSHAKE_TIMEOUT).min(open_until);
+        self.handshake = Some(Handshake {
+            client,
+            key,
+            code,
+            round: 0,
+            committed: None,
+            expires,
+        });
+        Ok((
+            exchange.public_key(),
+            CodeDisplay {
+                code,
+                until: expires,
+            },
+        ))
+    }
+
+    /// Takes the commitment of the client to the current color and returns the one of the
+    /// controller.
+    pub fn commit(
+        &mut self,
+        client: IpEndpoint,
+        client_commitment: &Commitment,
+        now: Instant,
+    ) -> Result<Commitment, PairingFailure> {
+        let handshake = self.handshake(client, now)?;
+        // A second commitment would let the client change its mind
+        if handshake.committed.is_some() {
+            return Err(PairingFailure::NoHandshake);
+        }
+
+        let mut nonce = [0; 16];
+        RoscRng.fill_bytes(&mut nonce);
+        handshake.committed = Some((*client_commitment, nonce));
+        Ok(commitment(
+            &handshake.key,
+            Side::Controller,
+            &handshake.code,
+            handshake.round,
+            &nonce,
+        ))
+    }
+
+    /// Checks the commitment of the client to the current color against the one on the strip.
+    /// Returns the nonce of the controller, and the key once every color matched.
+    pub fn reveal(
+        &mut self,
+        client: IpEndpoint,
+        client_nonce: &PairingNonce,
+        now: Instant,
+    ) -> Result<(PairingNonce, Option<Key>), PairingFailure> {
+        let handshake = self.handshake(client, now)?;
+        let Some((client_commitment, nonce)) = handshake.committed.take() else {
+            return Err(PairingFailure::NoHandshake);
+        };
+
+        let matches = verify_commitment(
+            &handshake.key,
+            Side::Client,
+            &handshake.code,
+            handshake.round,
+            client_nonce,
+            &client_commitment,
+        );
+        if !matches {
+            self.handshake = None;
+            self.failed_attempts += 1;
+            if self.failed_attempts >= ATTEMPTS_MAX {
+                self.open_until = None;
+            }
+            return Err(PairingFailure::WrongCode);
+        }
+
+        handshake.round += 1;
+        if handshake.round < CODE_LEN {
+            return Ok((nonce, None));
+        }
+
+        let key = handshake.key;
+        self.handshake = None;
+        self.open_until = None;
+        Ok((nonce, Some(key)))
+    }
+
+    /// The running handshake with `client`.
+    fn handshake(
+        &mut self,
+        client: IpEndpoint,
+        now: Instant,
+    ) -> Result<&mut Handshake, PairingFailure> {
+        if !self.is_open(now) {
+            return Err(PairingFailure::Closed);
+        }
+        self.handshake
+            .as_mut()
+            .filter(|handshake| handshake.client == client && now < handshake.expires)
+            .ok_or(PairingFailure::NoHandshake)
+    }
+}
+
+/// Shows the pairing code on the strip in place of the frames. The code blinks, so it stands
+/// out from whatever the strip showed before.
+#[derive(Default)]
+pub struct CodeOverlay {
+    display: Option<CodeDisplay>,
+    /// Whether the code was lit in the last frame written, `None` if none was written yet.
+    written: Option<bool>,
+}
+
+impl CodeOverlay {
+    /// Shows a new code, or hides the code with `None`.
+    pub fn set(&mut self, display: Option<CodeDisplay>) {
+        self.display = display;
+        self.written = None;
+    }
+
+    pub fn is_shown(&self, now: Instant) -> bool {
+        self.display.is_some_and(|display| now < display.until)
+    }
+
+    /// Renders the code on a strip of `led_count` LEDs. Returns `None` if the strip already
+    /// shows the frame.
+    pub fn render(&mut self, led_count: usize, now: Instant) -> Option<ArrayVec<Rgbw8, LED_MAX>> {
+        let display = self.display?;
+        let lit = now.as_millis() % BLINK_PERIOD_MS < BLINK_ON_MS;
+        if self.written == Some(lit) {
+            return None;
+        }
+        self.written = Some(lit);
+
+        let mut frame: ArrayVec<Rgbw8, LED_MAX> = (0..led_count
+            .max(CODE_LEN * (LEDS_PER_COLOR + 1)))
+            .map(|_| Rgbw8::default())
+            .collect();
+        if lit {
+            for (i, color) in display.code.colors().into_iter().enumerate() {
+                let start = i * (LEDS_PER_COLOR + 1);
+                frame[start..start + LEDS_PER_COLOR].fill(dim(color).into());
+            }
+        }
+        Some(frame)
+    }
+
+    /// Stops showing the code. Returns true if it was shown, so the frame below it has to be
+    /// written again.
+    pub fn hide(&mut self) -> bool {
+        self.written = None;
+        self.display.take().is_some()
+    }
+}
+
+fn dim(color: Rgb8) -> Rgb8 {
+    let scale = |channel: u8| (channel as u16 * CODE_BRIGHTNESS / 256) as u8;
+    Rgb8 {
+        r: scale(color.r),
+        g: scale(color.g),
+        b: scale(color.b),
+    }
+}
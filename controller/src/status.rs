@@ -0,0 +1,25 @@
+//! What the controller reports about itself in answer to `GetStatus`.
+
+use crate::parse_u16;
+
+/// Major, minor and patch version of the firmware, taken from the package version.
+pub const FIRMWARE_VERSION: [u8; 3] = [
+    parse_u16(env!("CARGO_PKG_VERSION_MAJOR")) as u8,
+    parse_u16(env!("CARGO_PKG_VERSION_MINOR")) as u8,
+    parse_u16(env!("CARGO_PKG_VERSION_PATCH")) as u8,
+];
+
+extern "C" {
+    /// End of the statics in RAM, placed by the linker script of `cortex-m-rt`.
+    static __sheap: u8;
+}
+
+/// RAM between the end of the statics and the stack pointer of core 0, in bytes.
+///
+/// There is no heap, so this is what is left for the stack to grow into. The stack of core 1 is
+/// one of the statics.
+pub fn free_memory() -> u32 {
+    // Only the address of the symbol is taken, it is never read
+    let statics_end = unsafe { core::ptr::addr_of!(__sheap) } as u32;
+    cortex_m::register::msp::read().saturating_sub(statics_end)
+}
@@ -0,0 +1,150 @@
+use defmt::warn;
+use embassy_rp::flash::{Blocking, Flash, ERASE_SIZE};
+use embassy_rp::peripherals::FLASH;
+use lumen_proto::bytestreamreader::{ByteStreamReader, MessageDeserializer};
+use lumen_proto::bytestreamwriter::{ByteStreamWriter, MessageSerializer};
+use lumen_proto::DeserializationResult;
+
+/// Size of the flash of the Pico W.
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+/// The end of the flash that is kept free of firmware in `memory.x`.
+const STORAGE_SIZE: usize = 64 * 1024;
+const STORAGE_OFFSET: usize = FLASH_SIZE - STORAGE_SIZE;
+
+/// Marks a sector that holds a record, erased flash reads as 0xff.
+const RECORD_MAGIC: [u8; 4] = *b"LMNS";
+/// Magic, payload length and checksum in front of every record.
+const RECORD_HEADER_SIZE: usize = 4 + 2 + 4;
+/// Size of the largest slot, the buffer records are assembled in.
+const SLOT_SIZE_MAX: usize = 2 * ERASE_SIZE;
+/// Bytes read at once when a record is compared with the stored one.
+const COMPARE_CHUNK_SIZE: usize = 256;
+
+/// The records kept in the flash. Every slot takes whole sectors, so they can be erased
+/// independently of each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Slot {
+    IdleBehavior,
+    Scene,
+    NonceFloor,
+    PairedClients,
+}
+
+impl Slot {
+    /// Offset from the start of the flash and size of the slot.
+    fn range(self) -> (u32, usize) {
+        let (sector, sectors) = match self {
+            Slot::IdleBehavior => (0, 1),
+            Slot::Scene => (1, 2),
+            Slot::NonceFloor => (3, 1),
+            Slot::PairedClients => (4, 1),
+        };
+        let offset = STORAGE_OFFSET + sector * ERASE_SIZE;
+        (offset as u32, sectors * ERASE_SIZE)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum StorageError {
+    /// The value doesn't fit into its slot.
+    TooLarge,
+    Flash(embassy_rp::flash::Error),
+}
+
+/// Settings that survive a reboot, stored in the flash.
+/// Writing pauses core 1 for the duration of the erase and write, so the strip briefly stalls.
+pub struct Storage {
+    flash: Flash<'static, FLASH, Blocking, FLASH_SIZE>,
+}
+
+impl Storage {
+    pub fn new(flash: FLASH) -> Self {
+        Self {
+            flash: Flash::new_blocking(flash),
+        }
+    }
+
+    /// Reads the value of `slot`. Returns `None` if nothing valid was stored.
+    pub fn load<T>(&mut self, slot: Slot) -> Option<T>
+    where
+        T: MessageDeserializer<Result = DeserializationResult<T>>,
+    {
+        let (offset, size) = slot.range();
+        let mut buffer = [0; SLOT_SIZE_MAX];
+        let record = &mut buffer[..size];
+        self.flash.blocking_read(offset, record).ok()?;
+
+        let (header, payload) = record.split_at(RECORD_HEADER_SIZE);
+        if header[..4] != RECORD_MAGIC {
+            return None;
+        }
+        let len = u16::from_le_bytes([header[4], header[5]]) as usize;
+        let checksum = u32::from_le_bytes([header[6], header[7], header[8], header[9]]);
+        let payload = payload.get(..len)?;
+        if crc32(payload) != checksum {
+            warn!("Discarding corrupted {} record", slot);
+            return None;
+        }
+
+        T::deserialize_from(&mut ByteStreamReader::new(payload)).ok()
+    }
+
+    /// Replaces the value of `slot`. Nothing is written if the slot already holds the value, every
+    /// erase wears the flash.
+    pub fn store<T: MessageSerializer>(
+        &mut self,
+        slot: Slot,
+        value: &T,
+    ) -> Result<(), StorageError> {
+        let (offset, size) = slot.range();
+        let mut buffer = [0; SLOT_SIZE_MAX];
+        let (header, payload) = buffer[..size].split_at_mut(RECORD_HEADER_SIZE);
+
+        let mut writer = ByteStreamWriter::new(payload);
+        value
+            .serialize_into(&mut writer)
+            .map_err(|_| StorageError::TooLarge)?;
+        let len = writer.written();
+
+        header[..4].copy_from_slice(&RECORD_MAGIC);
+        header[4..6].copy_from_slice(&(len as u16).to_le_bytes());
+        header[6..].copy_from_slice(&crc32(&payload[..len]).to_le_bytes());
+
+        let record = &buffer[..RECORD_HEADER_SIZE + len];
+        if self.holds(offset, record) {
+            return Ok(());
+        }
+        self.flash
+            .blocking_erase(offset, offset + size as u32)
+            .map_err(StorageError::Flash)?;
+        self.flash
+            .blocking_write(offset, record)
+            .map_err(StorageError::Flash)
+    }
+
+    /// Returns true if the flash at `offset` holds `record`.
+    fn holds(&mut self, offset: u32, record: &[u8]) -> bool {
+        let mut chunk = [0; COMPARE_CHUNK_SIZE];
+        record
+            .chunks(COMPARE_CHUNK_SIZE)
+            .enumerate()
+            .all(|(index, expected)| {
+                let stored = &mut chunk[..expected.len()];
+                let chunk_offset = offset + (index * COMPARE_CHUNK_SIZE) as u32;
+                self.flash.blocking_read(chunk_offset, stored).is_ok() && *stored == *expected
+            })
+    }
+}
+
+/// CRC-32 as used by zip and ethernet.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
@@ -0,0 +1,82 @@
+use crate::MUTEX;
+use controller_core::output::current_limit::CurrentEstimate;
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_time::Instant;
+
+/// Supply voltage of the strip, used to turn the estimated current into energy.
+const SUPPLY_MILLIVOLTS: u64 = 5000;
+
+/// Measurements of the output, written by core 1 and read by core 0.
+pub struct Telemetry {
+    inner: Mutex<MUTEX, Cell<TelemetryState>>,
+}
+
+#[derive(Clone, Copy)]
+struct TelemetryState {
+    current: CurrentEstimate,
+    /// Energy used up to `updated`, in microjoules.
+    energy_uj: u64,
+    updated: Instant,
+}
+
+/// A copy of the telemetry at one moment.
+#[derive(Debug, Clone, Copy, defmt::Format)]
+pub struct TelemetrySnapshot {
+    pub requested_ma: u32,
+    pub output_ma: u32,
+    pub energy_mwh: u32,
+}
+
+impl Telemetry {
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(Cell::new(TelemetryState {
+                current: CurrentEstimate {
+                    requested_ma: 0,
+                    output_ma: 0,
+                },
+                energy_uj: 0,
+                updated: Instant::from_ticks(0),
+            })),
+        }
+    }
+
+    /// Records the estimate of the frame that was just written to the strip.
+    pub fn record_output(&self, current: CurrentEstimate) {
+        self.inner.lock(|inner| {
+            let now = Instant::now();
+            let state = inner.get();
+            inner.set(TelemetryState {
+                current,
+                energy_uj: state.energy_until(now),
+                updated: now,
+            });
+        })
+    }
+
+    pub fn snapshot(&self) -> TelemetrySnapshot {
+        let state = self.inner.lock(|inner| inner.get());
+        let energy_uj = state.energy_until(Instant::now());
+        TelemetrySnapshot {
+            requested_ma: state.current.requested_ma,
+            output_ma: state.current.output_ma,
+            energy_mwh: (energy_uj / 3_600_000) as u32,
+        }
+    }
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TelemetryState {
+    /// The strip draws the current of the last frame until the next one is written.
+    fn energy_until(&self, now: Instant) -> u64 {
+        let elapsed_ms = now.saturating_duration_since(self.updated).as_millis();
+        let added_uj = self.current.output_ma as u64 * SUPPLY_MILLIVOLTS * elapsed_ms / 1000;
+        self.energy_uj + added_uj
+    }
+}
@@ -1,19 +1,29 @@
-use crate::messages::rgb8::Rgb8;
 use embassy_rp::clocks::{self};
 use embassy_rp::dma::{AnyChannel, Channel};
 use embassy_rp::pio::{
     Common, Config, FifoJoin, Instance, PioPin, ShiftConfig, ShiftDirection, StateMachine,
 };
 use embassy_rp::{Peripheral, PeripheralRef};
-use embassy_time::Timer;
+use embassy_time::{Duration, Timer};
 use fixed::types::U24F8;
 use fixed_macro::fixed;
+use lumen_proto::color_order::ColorOrder;
+use lumen_proto::pixel_format::PixelFormat;
+use lumen_proto::rgbw8::Rgbw8;
 
 use {defmt_rtt as _, panic_probe as _};
 
+/// Time a bit takes on the wire at 800 kHz.
+const BIT_NANOS: u64 = 1250;
+/// The pause after a frame that makes the strip latch it.
+const RESET: Duration = Duration::from_micros(300);
+
 pub struct Ws2812<'d, P: Instance, const SM: usize, const LEDS: usize> {
     dma: PeripheralRef<'d, AnyChannel>,
     sm: StateMachine<'d, P, SM>,
+    cfg: Config<'d, P>,
+    format: PixelFormat,
+    order: ColorOrder,
     buffer: [u32; LEDS],
 }
 
@@ -72,7 +82,7 @@ impl<'d, P: Instance, const SM: usize, const LEDS: usize> Ws2812<'d, P, SM, LEDS
         cfg.fifo_join = FifoJoin::TxOnly;
         cfg.shift_out = ShiftConfig {
             auto_fill: true,
-            threshold: 24,
+            threshold: bits_per_pixel(PixelFormat::Rgb),
             direction: ShiftDirection::Left,
         };
 
@@ -81,14 +91,45 @@ impl<'d, P: Instance, const SM: usize, const LEDS: usize> Ws2812<'d, P, SM, LEDS
 
         Self {
             sm,
+            cfg,
+            format: PixelFormat::Rgb,
+            order: ColorOrder::Grb,
             dma: dma.into_ref().map_into(),
             buffer: [0u32; LEDS],
         }
     }
 
-    pub async fn write(&mut self, colors: &[Rgb8]) {
-        for (w, &Rgb8 { r, g, b }) in self.buffer.iter_mut().zip(colors.iter()) {
-            *w = (u32::from(g) << 24) | (u32::from(r) << 16) | (u32::from(b) << 8);
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// Switches between 24 bit WS2812 and 32 bit SK6812 pixels.
+    pub fn set_pixel_format(&mut self, format: PixelFormat) {
+        self.cfg.shift_out.threshold = bits_per_pixel(format);
+        self.sm.set_enable(false);
+        self.sm.set_config(&self.cfg);
+        self.sm.set_enable(true);
+        self.format = format;
+    }
+
+    /// Time it takes to write `led_count` pixels to the strip, including the reset pause.
+    pub fn frame_duration(&self, led_count: usize) -> Duration {
+        let bits = u64::from(bits_per_pixel(self.format)) * led_count.min(LEDS) as u64;
+        Duration::from_nanos(bits * BIT_NANOS) + RESET
+    }
+
+    /// Sets the order the strip expects the color channels in, takes effect with the next write.
+    pub fn set_color_order(&mut self, order: ColorOrder) {
+        self.order = order;
+    }
+
+    pub async fn write(&mut self, colors: &[Rgbw8]) {
+        for (word, &Rgbw8 { r, g, b, w }) in self.buffer.iter_mut().zip(colors.iter()) {
+            let [c0, c1, c2] = self.order.arrange(r, g, b);
+            *word = (u32::from(c0) << 24) | (u32::from(c1) << 16) | (u32::from(c2) << 8);
+            if self.format == PixelFormat::Rgbw {
+                *word |= u32::from(w);
+            }
         }
 
         let max_elements = self.buffer.len().min(colors.len());
@@ -97,6 +138,14 @@ impl<'d, P: Instance, const SM: usize, const LEDS: usize> Ws2812<'d, P, SM, LEDS
             .dma_push(self.dma.reborrow(), &self.buffer[..max_elements])
             .await;
 
-        Timer::after_micros(300).await;
+        Timer::after(RESET).await;
+    }
+}
+
+/// Number of bits the strip expects for each pixel.
+fn bits_per_pixel(format: PixelFormat) -> u8 {
+    match format {
+        PixelFormat::Rgb => 24,
+        PixelFormat::Rgbw => 32,
     }
 }
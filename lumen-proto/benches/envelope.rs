@@ -0,0 +1,89 @@
+//! Measures how long signing and encrypting take for frames of a 400 LED strip.
+//!
+//! Run with `cargo bench --bench envelope`. The numbers are those of the host, the controller
+//! measures itself with the `bench-envelope` feature of the firmware.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use lumen_proto::auth::{encrypt, sign, Envelope, Keys};
+use lumen_proto::message_kind::MessageKind;
+use lumen_proto::rgb8::Rgb8;
+use lumen_proto::{ControllerMessage, Timestamp};
+
+const LED_COUNT: usize = 400;
+const FRAMES_PER_SECOND: u32 = 60;
+const ITERATIONS: u32 = 20_000;
+
+fn main() {
+    let keys = Keys::new(&[7; 32]);
+    let message = ControllerMessage {
+        timestamp: Timestamp::new(0),
+        sequence: None,
+        kind: MessageKind::LedState {
+            led_values: (0..LED_COUNT)
+                .map(|i| Rgb8 {
+                    r: i as u8,
+                    g: (i >> 8) as u8,
+                    b: 0x80,
+                })
+                .collect(),
+            transition: false,
+        },
+    };
+
+    let mut buffer = [0; 2048];
+    let plain_len = message.encode(&mut buffer).unwrap();
+    report("encode", plain_len, || {
+        message.encode(black_box(&mut buffer)).unwrap();
+    });
+
+    let signed_len = sign(&message, &keys.signing, 0, 1, &mut buffer).unwrap();
+    let signed = buffer[..signed_len].to_vec();
+    report("sign", signed_len, || {
+        sign(&message, &keys.signing, 0, 1, black_box(&mut buffer)).unwrap();
+    });
+    report("verify", signed_len, || {
+        buffer[..signed_len].copy_from_slice(&signed);
+        let Ok(Envelope::Signed(signed)) = Envelope::open(&mut buffer[..signed_len]) else {
+            unreachable!();
+        };
+        assert!(signed.verify(black_box(&keys.signing)));
+    });
+
+    let encrypted_len = encrypt(&message, &keys.encryption, 0, 1, &mut buffer).unwrap();
+    let encrypted = buffer[..encrypted_len].to_vec();
+    report("encrypt", encrypted_len, || {
+        encrypt(&message, &keys.encryption, 0, 1, black_box(&mut buffer)).unwrap();
+    });
+    report("decrypt", encrypted_len, || {
+        buffer[..encrypted_len].copy_from_slice(&encrypted);
+        let Ok(Envelope::Encrypted(encrypted)) = Envelope::open(&mut buffer[..encrypted_len])
+        else {
+            unreachable!();
+        };
+        assert!(encrypted.decrypt(black_box(&keys.encryption)).is_some());
+    });
+}
+
+/// Runs `f` repeatedly and prints its throughput and the share of a frame interval it takes.
+fn report(name: &str, datagram_len: usize, mut f: impl FnMut()) {
+    // Warm up the caches
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_datagram = start.elapsed() / ITERATIONS;
+
+    let throughput = datagram_len as f64 / per_datagram.as_secs_f64() / 1_000_000.0;
+    let frame_interval = Duration::from_secs(1) / FRAMES_PER_SECOND;
+    let load = per_datagram.as_secs_f64() / frame_interval.as_secs_f64() * 100.0;
+    println!(
+        "{name:>8}: {datagram_len} bytes in {per_datagram:>9.2?}, {throughput:>8.1} MB/s, \
+         {load:.3}% of a frame at {FRAMES_PER_SECOND} fps"
+    );
+}
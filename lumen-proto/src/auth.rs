@@ -0,0 +1,429 @@
+//! Datagrams signed or encrypted with a pre-shared key or the key of a paired client.
+//!
+//! Both kinds wrap a plain Lumen datagram in an envelope: the magic, the u8 id of the key and
+//! the u64 nonce, then the datagram and a [`TAG_LEN`] byte tag.
+//!
+//! - Signed datagrams start with [`SIGNED_MAGIC`]. The plain datagram is readable, the tag is
+//!   the truncated HMAC-SHA256 over everything in front of it.
+//! - Encrypted datagrams start with [`ENCRYPTED_MAGIC`]. The plain datagram is encrypted with
+//!   ChaCha20-Poly1305 under the [`Keys::encryption`] key, the magic, key id and nonce are
+//!   authenticated as associated data and the tag is the one of Poly1305.
+//!
+//! Nonces are never reused with a key, no matter the kind. Clients count their datagrams in the
+//! lower [`NONCE_COUNTER_BITS`] and put the id of their run above them: the time the run started,
+//! in seconds since the Unix epoch, in the upper 32 bits, then [`RUN_RANDOM_BITS`] random bits, so
+//! clients sharing a key rarely pick the same run and the nonces keep increasing across restarts.
+//! A client starts a new, higher run when its counter runs out or the controller answers with
+//! [`RunClosed`](crate::reply::ReplyKind::RunClosed). Clients that share the pre-shared key can
+//! still pick the same run in the same second, pairing gives each of them a key of its own.
+//!
+//! Replies of the controller are wrapped like the request they answer, under the reply keys of
+//! the same [`Keys`] and with the nonce of the request, see [`seal_reply`]. Every request is
+//! answered at most once, so the controller never reuses a nonce without keeping any state, and a
+//! reply sent back to the controller doesn't pass as a request. Replayed datagrams are the
+//! exception, they are answered with `RunClosed` as often as they arrive. These replies are always
+//! signed, under a key of their own, so they never reuse the key and nonce of another reply.
+
+use chacha20poly1305::aead::{AeadInPlace, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce, Tag};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use arrayvec::ArrayVec;
+
+use crate::{
+    bytestreamreader::ByteStreamReader,
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    reply::{ControllerReply, ReplyKind},
+    DeserializationError, DeserializationResult, SerializationError, SerializationResult,
+};
+
+/// Signed datagrams start with these bytes instead of the [`MAGIC`](crate::header::MAGIC).
+pub const SIGNED_MAGIC: [u8; 4] = *b"LMAC";
+/// Encrypted datagrams start with these bytes instead of the [`MAGIC`](crate::header::MAGIC).
+pub const ENCRYPTED_MAGIC: [u8; 4] = *b"LMEN";
+pub const KEY_LEN: usize = 32;
+/// Length of the tag at the end of an envelope.
+pub const TAG_LEN: usize = 16;
+/// Magic, key id and nonce in front of the wrapped datagram.
+const ENVELOPE_HEADER_LEN: usize = 4 + 1 + 8;
+/// Bytes an envelope adds to the plain datagram.
+pub const ENVELOPE_OVERHEAD: usize = ENVELOPE_HEADER_LEN + TAG_LEN;
+/// Bits of a nonce that count the datagrams of a client run.
+pub const NONCE_COUNTER_BITS: u32 = 20;
+/// Random bits at the bottom of a run id, below the start time of the run.
+pub const RUN_RANDOM_BITS: u32 = 12;
+/// Number of client runs per key whose nonces are tracked at once.
+pub const RUN_MAX: usize = 4;
+/// Number of nonces below the highest one that are still accepted, so reordered datagrams
+/// aren't lost.
+pub const NONCE_WINDOW: u64 = 64;
+
+pub type Key = [u8; KEY_LEN];
+
+/// The keys used with one pre-shared key. Signing and encryption use different keys, so the
+/// same key never goes into two algorithms, and so do requests and replies.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Keys {
+    /// The pre-shared key itself.
+    pub signing: Key,
+    /// HMAC-SHA256 of `lumen encryption` under the pre-shared key.
+    pub encryption: Key,
+    /// HMAC-SHA256 of `lumen reply signing` under the pre-shared key.
+    pub reply_signing: Key,
+    /// HMAC-SHA256 of `lumen reply encryption` under the pre-shared key.
+    pub reply_encryption: Key,
+    /// HMAC-SHA256 of `lumen run closed` under the pre-shared key, signs `RunClosed` replies.
+    pub run_closed: Key,
+}
+
+impl Keys {
+    pub fn new(key: &Key) -> Self {
+        Self {
+            signing: *key,
+            encryption: derive(key, b"lumen encryption"),
+            reply_signing: derive(key, b"lumen reply signing"),
+            reply_encryption: derive(key, b"lumen reply encryption"),
+            run_closed: derive(key, b"lumen run closed"),
+        }
+    }
+}
+
+fn derive(key: &Key, label: &[u8]) -> Key {
+    let mut mac = hmac(key);
+    mac.update(label);
+    mac.finalize().into_bytes().into()
+}
+
+/// A received datagram, before its envelope is checked.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Envelope<'a> {
+    Plain(&'a [u8]),
+    Signed(SignedDatagram<'a>),
+    Encrypted(EncryptedDatagram<'a>),
+}
+
+impl<'a> Envelope<'a> {
+    /// Tells the kinds of datagrams apart. The wrapped datagram is not parsed yet.
+    /// Takes the datagram mutably, so encrypted ones can be decrypted in place.
+    pub fn open(datagram: &'a mut [u8]) -> DeserializationResult<Self> {
+        let is_signed = datagram.starts_with(&SIGNED_MAGIC);
+        if !is_signed && !datagram.starts_with(&ENCRYPTED_MAGIC) {
+            return Ok(Envelope::Plain(datagram));
+        }
+        if datagram.len() < ENVELOPE_OVERHEAD {
+            return Err(DeserializationError::Truncated);
+        }
+
+        let mut reader = ByteStreamReader::new(&datagram[SIGNED_MAGIC.len()..]);
+        let key_id = reader.u8()?;
+        let nonce = reader.u64()?;
+
+        let tag_start = datagram.len() - TAG_LEN;
+        if is_signed {
+            let (signed, tag) = datagram.split_at(tag_start);
+            return Ok(Envelope::Signed(SignedDatagram {
+                key_id,
+                nonce,
+                datagram: &signed[ENVELOPE_HEADER_LEN..],
+                signed,
+                tag,
+            }));
+        }
+
+        let (header, rest) = datagram.split_at_mut(ENVELOPE_HEADER_LEN);
+        let (ciphertext, tag) = rest.split_at_mut(tag_start - ENVELOPE_HEADER_LEN);
+        Ok(Envelope::Encrypted(EncryptedDatagram {
+            key_id,
+            nonce,
+            header,
+            ciphertext,
+            tag,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedDatagram<'a> {
+    pub key_id: u8,
+    pub nonce: u64,
+    /// The wrapped plain datagram.
+    pub datagram: &'a [u8],
+    /// Everything the tag was computed over.
+    signed: &'a [u8],
+    tag: &'a [u8],
+}
+
+impl SignedDatagram<'_> {
+    /// Returns true if the datagram was signed with `key`. The tag is compared in constant time.
+    pub fn verify(&self, key: &Key) -> bool {
+        let mut mac = hmac(key);
+        mac.update(self.signed);
+        mac.verify_truncated_left(self.tag).is_ok()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct EncryptedDatagram<'a> {
+    pub key_id: u8,
+    pub nonce: u64,
+    /// Magic, key id and nonce, authenticated as associated data.
+    header: &'a [u8],
+    ciphertext: &'a mut [u8],
+    tag: &'a [u8],
+}
+
+impl<'a> EncryptedDatagram<'a> {
+    /// Decrypts the datagram in place and returns the plain datagram.
+    /// Returns `None` if the datagram wasn't encrypted with `key` or was tampered with.
+    pub fn decrypt(self, key: &Key) -> Option<&'a [u8]> {
+        let cipher = ChaCha20Poly1305::new(key.into());
+        cipher
+            .decrypt_in_place_detached(
+                &aead_nonce(self.nonce),
+                self.header,
+                self.ciphertext,
+                Tag::from_slice(self.tag),
+            )
+            .ok()?;
+        Some(self.ciphertext)
+    }
+}
+
+/// Serializes `message` into `buffer` as a signed datagram and returns the number of bytes
+/// written.
+pub fn sign<M: MessageSerializer>(
+    message: &M,
+    key: &Key,
+    key_id: u8,
+    nonce: u64,
+    buffer: &mut [u8],
+) -> SerializationResult<usize> {
+    let len = write_envelope(message, SIGNED_MAGIC, key_id, nonce, buffer)?;
+
+    let mut mac = hmac(key);
+    mac.update(&buffer[..len]);
+    let tag = buffer
+        .get_mut(len..len + TAG_LEN)
+        .ok_or(SerializationError::BufferTooSmall)?;
+    tag.copy_from_slice(&mac.finalize().into_bytes()[..TAG_LEN]);
+    Ok(len + TAG_LEN)
+}
+
+/// Serializes `message` into `buffer` as an encrypted datagram and returns the number of bytes
+/// written. `key` is the [`Keys::encryption`] key.
+pub fn encrypt<M: MessageSerializer>(
+    message: &M,
+    key: &Key,
+    key_id: u8,
+    nonce: u64,
+    buffer: &mut [u8],
+) -> SerializationResult<usize> {
+    let len = write_envelope(message, ENCRYPTED_MAGIC, key_id, nonce, buffer)?;
+    if buffer.len() < len + TAG_LEN {
+        return Err(SerializationError::BufferTooSmall);
+    }
+
+    let (header, rest) = buffer.split_at_mut(ENVELOPE_HEADER_LEN);
+    let (plaintext, tag) = rest.split_at_mut(len - ENVELOPE_HEADER_LEN);
+    let cipher = ChaCha20Poly1305::new(key.into());
+    let computed = cipher
+        .encrypt_in_place_detached(&aead_nonce(nonce), header, plaintext)
+        // Only fails for plaintexts of more than 256 GiB
+        .map_err(|_| SerializationError::BufferTooSmall)?;
+    tag[..TAG_LEN].copy_from_slice(&computed);
+    Ok(len + TAG_LEN)
+}
+
+/// Serializes `reply` into `buffer` wrapped like the request it answers, which came with
+/// `key_id` and `nonce` and was encrypted if `encrypted` is set, and returns the number of bytes
+/// written. `RunClosed` replies are signed with [`Keys::run_closed`] instead.
+pub fn seal_reply(
+    reply: &ControllerReply,
+    keys: &Keys,
+    key_id: u8,
+    nonce: u64,
+    encrypted: bool,
+    buffer: &mut [u8],
+) -> SerializationResult<usize> {
+    match (&reply.kind, encrypted) {
+        (ReplyKind::RunClosed, _) => sign(reply, &keys.run_closed, key_id, nonce, buffer),
+        (_, true) => encrypt(reply, &keys.reply_encryption, key_id, nonce, buffer),
+        (_, false) => sign(reply, &keys.reply_signing, key_id, nonce, buffer),
+    }
+}
+
+/// Writes the envelope header and the plain datagram, returns the number of bytes written.
+fn write_envelope<M: MessageSerializer>(
+    message: &M,
+    magic: [u8; 4],
+    key_id: u8,
+    nonce: u64,
+    buffer: &mut [u8],
+) -> SerializationResult<usize> {
+    let mut writer = ByteStreamWriter::new(buffer);
+    writer.bytes(&magic)?;
+    writer.u8(key_id)?;
+    writer.u64(nonce)?;
+    message.serialize_into(&mut writer)?;
+    Ok(writer.written())
+}
+
+/// The run a nonce of a client belongs to.
+pub const fn nonce_run(nonce: u64) -> u64 {
+    nonce >> NONCE_COUNTER_BITS
+}
+
+fn hmac(key: &Key) -> Hmac<Sha256> {
+    // HMAC takes keys of any length
+    <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap()
+}
+
+/// The 96 bit nonce of ChaCha20-Poly1305, the u64 nonce padded with zeros.
+fn aead_nonce(nonce: u64) -> Nonce {
+    let mut bytes = [0; 12];
+    bytes[4..].copy_from_slice(&nonce.to_le_bytes());
+    bytes.into()
+}
+
+/// Rejects replayed nonces. A nonce is accepted once, if it is higher than the highest one seen
+/// so far or at most [`NONCE_WINDOW`] below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NonceWindow {
+    highest: u64,
+    /// Bit `n` is set if the nonce `highest - n` was seen.
+    seen: u64,
+}
+
+impl NonceWindow {
+    /// Creates a window that rejects `floor` and every nonce below it.
+    pub const fn new(floor: u64) -> Self {
+        Self {
+            highest: floor,
+            seen: u64::MAX,
+        }
+    }
+
+    /// The highest nonce accepted so far.
+    pub fn highest(&self) -> u64 {
+        self.highest
+    }
+
+    /// Returns true if `nonce` wasn't seen before and marks it as seen.
+    /// Only call this for datagrams with a valid tag, forged nonces would move the window.
+    pub fn accept(&mut self, nonce: u64) -> bool {
+        if nonce > self.highest {
+            let shift = nonce - self.highest;
+            self.seen = match shift < NONCE_WINDOW {
+                true => (self.seen << shift) | 1,
+                false => 1,
+            };
+            self.highest = nonce;
+            return true;
+        }
+
+        let age = self.highest - nonce;
+        if age >= NONCE_WINDOW {
+            return false;
+        }
+        let bit = 1 << age;
+        if self.seen & bit != 0 {
+            return false;
+        }
+        self.seen |= bit;
+        true
+    }
+}
+
+impl Default for NonceWindow {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// Reasons why a [`ReplayGuard`] rejects a nonce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Replay {
+    /// The nonce was seen before or is too old for the window of its run.
+    Seen,
+    /// The run of the nonce ended, it was pushed out by newer runs or ran before a reboot. The
+    /// client has to start a new run.
+    RunClosed,
+}
+
+/// Rejects replayed nonces of all clients using one key. Every client run gets a
+/// [`NonceWindow`] of its own, so clients sharing a key don't push each other out of the window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayGuard {
+    /// Tracked runs and their windows, in no particular order.
+    runs: ArrayVec<(u64, NonceWindow), RUN_MAX>,
+    /// Nonces below are rejected, they belong to runs that are no longer tracked.
+    floor: u64,
+}
+
+impl ReplayGuard {
+    /// Creates a guard that rejects every nonce below `floor`, usually the [`mark`](Self::mark)
+    /// stored before a reboot.
+    pub const fn new(floor: u64) -> Self {
+        Self {
+            runs: ArrayVec::new_const(),
+            floor,
+        }
+    }
+
+    /// Accepts `nonce` if it wasn't seen before and marks it as seen. If all [`RUN_MAX`] runs are
+    /// tracked, a newer run closes the oldest one.
+    /// Only call this for datagrams with a valid tag, forged nonces would close runs.
+    pub fn accept(&mut self, nonce: u64) -> Result<(), Replay> {
+        if nonce < self.floor {
+            return Err(Replay::RunClosed);
+        }
+
+        let run = nonce_run(nonce);
+        if let Some((_, window)) = self.runs.iter_mut().find(|(id, _)| *id == run) {
+            return window.accept(nonce).then_some(()).ok_or(Replay::Seen);
+        }
+
+        if self.runs.is_full() {
+            let (oldest, _) = self
+                .runs
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, (id, _))| *id)
+                .unwrap();
+            let closed = self.runs[oldest].0;
+            if run < closed {
+                return Err(Replay::RunClosed);
+            }
+            self.runs.swap_remove(oldest);
+            self.floor = self.floor.max(run_end(closed));
+        }
+
+        let mut window = NonceWindow::new(run << NONCE_COUNTER_BITS);
+        let accepted = window.accept(nonce);
+        self.runs.push((run, window));
+        accepted.then_some(()).ok_or(Replay::Seen)
+    }
+
+    /// The end of the newest run seen so far. Stored across reboots it closes every run seen
+    /// before, including the one still running, without storing every accepted nonce.
+    pub fn mark(&self) -> u64 {
+        self.runs
+            .iter()
+            .map(|(run, _)| run_end(*run))
+            .fold(self.floor, u64::max)
+    }
+}
+
+impl Default for ReplayGuard {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// The first nonce after the run.
+const fn run_end(run: u64) -> u64 {
+    (run + 1) << NONCE_COUNTER_BITS
+}
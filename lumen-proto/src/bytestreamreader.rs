@@ -0,0 +1,78 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{DeserializationError, DeserializationResult};
+
+pub struct ByteStreamReader<'slc> {
+    stream: &'slc [u8],
+}
+
+impl<'slc> ByteStreamReader<'slc> {
+    pub fn new(stream: &'slc [u8]) -> Self {
+        Self { stream }
+    }
+
+    pub fn u8(&mut self) -> DeserializationResult<u8> {
+        let bytes = self.take(size_of::<u8>())?;
+        Ok(bytes[0])
+    }
+
+    pub fn bool(&mut self) -> DeserializationResult<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DeserializationError::InvalidValue),
+        }
+    }
+
+    pub fn u16(&mut self) -> DeserializationResult<u16> {
+        let bytes = self.take(size_of::<u16>())?;
+        Ok(LittleEndian::read_u16(bytes))
+    }
+
+    pub fn i16(&mut self) -> DeserializationResult<i16> {
+        let bytes = self.take(size_of::<i16>())?;
+        Ok(LittleEndian::read_i16(bytes))
+    }
+
+    pub fn u32(&mut self) -> DeserializationResult<u32> {
+        let bytes = self.take(size_of::<u32>())?;
+        Ok(LittleEndian::read_u32(bytes))
+    }
+
+    pub fn u64(&mut self) -> DeserializationResult<u64> {
+        let bytes = self.take(size_of::<u64>())?;
+        Ok(LittleEndian::read_u64(bytes))
+    }
+
+    pub fn array<const N: usize>(&mut self) -> DeserializationResult<[u8; N]> {
+        let bytes = self.take(N)?;
+        let mut array = [0; N];
+        array.copy_from_slice(bytes);
+        Ok(array)
+    }
+
+    /// Returns the number of bytes that have not been read yet.
+    pub fn remaining(&self) -> usize {
+        self.stream.len()
+    }
+
+    pub fn advance_by(&mut self, by: usize) -> DeserializationResult<()> {
+        self.take(by).map(|_| ())
+    }
+
+    /// Splits off the next `len` bytes, failing if the stream is shorter than that.
+    fn take(&mut self, len: usize) -> DeserializationResult<&'slc [u8]> {
+        if self.stream.len() < len {
+            return Err(DeserializationError::Truncated);
+        }
+        let (head, tail) = self.stream.split_at(len);
+        self.stream = tail;
+        Ok(head)
+    }
+}
+
+pub trait MessageDeserializer {
+    type Result;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result;
+}
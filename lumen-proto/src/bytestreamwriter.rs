@@ -0,0 +1,68 @@
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::{SerializationError, SerializationResult};
+
+pub struct ByteStreamWriter<'slc> {
+    stream: &'slc mut [u8],
+    written: usize,
+}
+
+impl<'slc> ByteStreamWriter<'slc> {
+    pub fn new(stream: &'slc mut [u8]) -> Self {
+        Self { stream, written: 0 }
+    }
+
+    pub fn u8(&mut self, value: u8) -> SerializationResult<()> {
+        self.take(size_of::<u8>())?[0] = value;
+        Ok(())
+    }
+
+    pub fn bool(&mut self, value: bool) -> SerializationResult<()> {
+        self.u8(value as u8)
+    }
+
+    pub fn u16(&mut self, value: u16) -> SerializationResult<()> {
+        LittleEndian::write_u16(self.take(size_of::<u16>())?, value);
+        Ok(())
+    }
+
+    pub fn i16(&mut self, value: i16) -> SerializationResult<()> {
+        LittleEndian::write_i16(self.take(size_of::<i16>())?, value);
+        Ok(())
+    }
+
+    pub fn u32(&mut self, value: u32) -> SerializationResult<()> {
+        LittleEndian::write_u32(self.take(size_of::<u32>())?, value);
+        Ok(())
+    }
+
+    pub fn u64(&mut self, value: u64) -> SerializationResult<()> {
+        LittleEndian::write_u64(self.take(size_of::<u64>())?, value);
+        Ok(())
+    }
+
+    pub fn bytes(&mut self, value: &[u8]) -> SerializationResult<()> {
+        self.take(value.len())?.copy_from_slice(value);
+        Ok(())
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Reserves the next `len` bytes of the stream, failing if there is not enough space left.
+    fn take(&mut self, len: usize) -> SerializationResult<&mut [u8]> {
+        let end = self.written + len;
+        if end > self.stream.len() {
+            return Err(SerializationError::BufferTooSmall);
+        }
+        let bytes = &mut self.stream[self.written..end];
+        self.written = end;
+        Ok(bytes)
+    }
+}
+
+pub trait MessageSerializer {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()>;
+}
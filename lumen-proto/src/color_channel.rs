@@ -0,0 +1,52 @@
+use crate::{
+    bytestreamreader::{ByteStreamReader, MessageDeserializer},
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    DeserializationError, DeserializationResult, SerializationResult,
+};
+
+/// A single color channel of a pixel.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ColorChannel {
+    Red = 0,
+    Green = 1,
+    Blue = 2,
+    /// Only present on RGBW strips.
+    White = 3,
+}
+
+impl ColorChannel {
+    pub const ALL: [ColorChannel; 4] = [
+        ColorChannel::Red,
+        ColorChannel::Green,
+        ColorChannel::Blue,
+        ColorChannel::White,
+    ];
+}
+
+impl TryFrom<u8> for ColorChannel {
+    type Error = ();
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        ColorChannel::ALL
+            .into_iter()
+            .find(|channel| *channel as u8 == v)
+            .ok_or(())
+    }
+}
+
+impl MessageDeserializer for ColorChannel {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let channel = reader.u8()?;
+        ColorChannel::try_from(channel).map_err(|_| DeserializationError::InvalidValue)
+    }
+}
+
+impl MessageSerializer for ColorChannel {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u8(*self as u8)
+    }
+}
@@ -0,0 +1,65 @@
+use crate::{
+    bytestreamreader::{ByteStreamReader, MessageDeserializer},
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    DeserializationError, DeserializationResult, SerializationResult,
+};
+
+/// The order in which a strip expects the color channels of a pixel on the wire.
+/// The white channel of RGBW strips always comes last.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ColorOrder {
+    Rgb = 0,
+    Rbg = 1,
+    /// The order of WS2812B and SK6812 strips.
+    Grb = 2,
+    Gbr = 3,
+    Brg = 4,
+    Bgr = 5,
+}
+
+impl ColorOrder {
+    /// Arranges the channels in the order they are sent to the strip.
+    pub fn arrange(self, r: u8, g: u8, b: u8) -> [u8; 3] {
+        match self {
+            ColorOrder::Rgb => [r, g, b],
+            ColorOrder::Rbg => [r, b, g],
+            ColorOrder::Grb => [g, r, b],
+            ColorOrder::Gbr => [g, b, r],
+            ColorOrder::Brg => [b, r, g],
+            ColorOrder::Bgr => [b, g, r],
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorOrder {
+    type Error = ();
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            x if x == ColorOrder::Rgb as u8 => Ok(ColorOrder::Rgb),
+            x if x == ColorOrder::Rbg as u8 => Ok(ColorOrder::Rbg),
+            x if x == ColorOrder::Grb as u8 => Ok(ColorOrder::Grb),
+            x if x == ColorOrder::Gbr as u8 => Ok(ColorOrder::Gbr),
+            x if x == ColorOrder::Brg as u8 => Ok(ColorOrder::Brg),
+            x if x == ColorOrder::Bgr as u8 => Ok(ColorOrder::Bgr),
+            _ => Err(()),
+        }
+    }
+}
+
+impl MessageDeserializer for ColorOrder {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let order = reader.u8()?;
+        ColorOrder::try_from(order).map_err(|_| DeserializationError::InvalidValue)
+    }
+}
+
+impl MessageSerializer for ColorOrder {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u8(*self as u8)
+    }
+}
@@ -0,0 +1,82 @@
+//! Compressed frame encodings.
+//!
+//! Run-length encoded frames consist of the u16 LED count followed by `(run length: u8, Rgb8)`
+//! pairs. Delta frames are the XOR of two consecutive frames, run-length encoded the same way,
+//! so unchanged LEDs collapse into long runs of zero.
+
+use arrayvec::ArrayVec;
+
+use crate::{
+    bytestreamreader::{ByteStreamReader, MessageDeserializer},
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    rgb8::Rgb8,
+    DeserializationError, DeserializationResult, SerializationResult, LED_MAX,
+};
+
+/// Reads a run-length encoded frame.
+pub fn read_rle_frame(
+    reader: &mut ByteStreamReader,
+) -> DeserializationResult<ArrayVec<Rgb8, LED_MAX>> {
+    let led_values_cnt = reader.u16()?;
+    if led_values_cnt as usize > LED_MAX {
+        return Err(DeserializationError::LedCountOverCapacity(led_values_cnt));
+    }
+
+    let mut led_values = ArrayVec::new();
+    while led_values.len() < led_values_cnt as usize {
+        let run_length = reader.u8()? as usize;
+        let rgb = Rgb8::deserialize_from(reader)?;
+        if run_length == 0 || led_values.len() + run_length > led_values_cnt as usize {
+            return Err(DeserializationError::MalformedRle);
+        }
+        for _ in 0..run_length {
+            led_values.push(rgb);
+        }
+    }
+
+    Ok(led_values)
+}
+
+/// Writes a run-length encoded frame.
+pub fn write_rle_frame(
+    writer: &mut ByteStreamWriter,
+    led_values: &[Rgb8],
+) -> SerializationResult<()> {
+    // LED_MAX fits into the u16 count field, see the assertion in lib.rs
+    writer.u16(led_values.len() as u16)?;
+
+    let mut rest = led_values;
+    while let [rgb, ..] = rest {
+        let run_length = rest
+            .iter()
+            .take(u8::MAX as usize)
+            .take_while(|other| *other == rgb)
+            .count();
+        writer.u8(run_length as u8)?;
+        rgb.serialize_into(writer)?;
+        rest = &rest[run_length..];
+    }
+
+    Ok(())
+}
+
+/// Computes the delta that turns `base` into `next`.
+/// Returns `None` if the frames differ in length, those have to be sent as a whole.
+pub fn frame_delta(base: &[Rgb8], next: &[Rgb8]) -> Option<ArrayVec<Rgb8, LED_MAX>> {
+    if base.len() != next.len() {
+        return None;
+    }
+    Some(base.iter().zip(next).map(|(a, b)| *a ^ *b).collect())
+}
+
+/// Applies a delta created by [`frame_delta`] to `frame` in place.
+/// Returns false and leaves `frame` untouched if the lengths don't match.
+pub fn apply_frame_delta(frame: &mut [Rgb8], delta: &[Rgb8]) -> bool {
+    if frame.len() != delta.len() {
+        return false;
+    }
+    for (rgb, mask) in frame.iter_mut().zip(delta) {
+        *rgb = *rgb ^ *mask;
+    }
+    true
+}
@@ -0,0 +1,198 @@
+use crate::{
+    bytestreamreader::{ByteStreamReader, MessageDeserializer},
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    rgb8::Rgb8,
+    DeserializationError, DeserializationResult, SerializationResult,
+};
+
+/// The effects built into the controller.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EffectId {
+    Rainbow = 0,
+    Breathing = 1,
+    ColorWipe = 2,
+    Chase = 3,
+    Twinkle = 4,
+    Fire = 5,
+}
+
+impl EffectId {
+    pub const ALL: [EffectId; 6] = [
+        EffectId::Rainbow,
+        EffectId::Breathing,
+        EffectId::ColorWipe,
+        EffectId::Chase,
+        EffectId::Twinkle,
+        EffectId::Fire,
+    ];
+}
+
+impl TryFrom<u8> for EffectId {
+    type Error = ();
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        EffectId::ALL
+            .into_iter()
+            .find(|effect| *effect as u8 == v)
+            .ok_or(())
+    }
+}
+
+impl MessageDeserializer for EffectId {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let effect = reader.u8()?;
+        EffectId::try_from(effect).map_err(|_| DeserializationError::InvalidValue)
+    }
+}
+
+impl MessageSerializer for EffectId {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u8(*self as u8)
+    }
+}
+
+/// Parameters shared by all effects. Each effect documents which of them it uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EffectParams {
+    /// Pace of the animation in 1/64, 64 plays the effect at its normal speed.
+    pub speed: u8,
+    /// Strength of the effect, e.g. the density of twinkles or the heat of the fire.
+    pub intensity: u8,
+    /// Length of repeating patterns in LEDs.
+    pub size: u8,
+    pub color: Rgb8,
+    pub background: Rgb8,
+}
+
+impl Default for EffectParams {
+    fn default() -> Self {
+        Self {
+            speed: 64,
+            intensity: 128,
+            size: 10,
+            color: Rgb8 {
+                r: 255,
+                g: 255,
+                b: 255,
+            },
+            background: Rgb8::default(),
+        }
+    }
+}
+
+impl MessageDeserializer for EffectParams {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let speed = reader.u8()?;
+        let intensity = reader.u8()?;
+        let size = reader.u8()?;
+        let color = Rgb8::deserialize_from(reader)?;
+        let background = Rgb8::deserialize_from(reader)?;
+        Ok(EffectParams {
+            speed,
+            intensity,
+            size,
+            color,
+            background,
+        })
+    }
+}
+
+impl MessageSerializer for EffectParams {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u8(self.speed)?;
+        writer.u8(self.intensity)?;
+        writer.u8(self.size)?;
+        self.color.serialize_into(writer)?;
+        self.background.serialize_into(writer)
+    }
+}
+
+/// Maximum number of parameters a single `UpdateEffectParams` message can change.
+pub const EFFECT_PARAM_UPDATE_MAX: usize = 8;
+
+/// A single typed parameter of an effect, used to change parameters of a running effect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectParam {
+    Speed(u8),
+    Intensity(u8),
+    Size(u8),
+    Color(Rgb8),
+    Background(Rgb8),
+}
+
+/// Wire ids of the variants of [`EffectParam`].
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EffectParamId {
+    Speed = 0,
+    Intensity = 1,
+    Size = 2,
+    Color = 3,
+    Background = 4,
+}
+
+impl EffectParam {
+    pub fn id(&self) -> EffectParamId {
+        match self {
+            EffectParam::Speed(_) => EffectParamId::Speed,
+            EffectParam::Intensity(_) => EffectParamId::Intensity,
+            EffectParam::Size(_) => EffectParamId::Size,
+            EffectParam::Color(_) => EffectParamId::Color,
+            EffectParam::Background(_) => EffectParamId::Background,
+        }
+    }
+}
+
+impl EffectParams {
+    /// Replaces the value of a single parameter.
+    pub fn apply(&mut self, param: EffectParam) {
+        match param {
+            EffectParam::Speed(speed) => self.speed = speed,
+            EffectParam::Intensity(intensity) => self.intensity = intensity,
+            EffectParam::Size(size) => self.size = size,
+            EffectParam::Color(color) => self.color = color,
+            EffectParam::Background(background) => self.background = background,
+        }
+    }
+}
+
+impl MessageDeserializer for EffectParam {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let param = match reader.u8()? {
+            x if x == EffectParamId::Speed as u8 => EffectParam::Speed(reader.u8()?),
+            x if x == EffectParamId::Intensity as u8 => EffectParam::Intensity(reader.u8()?),
+            x if x == EffectParamId::Size as u8 => EffectParam::Size(reader.u8()?),
+            x if x == EffectParamId::Color as u8 => {
+                EffectParam::Color(Rgb8::deserialize_from(reader)?)
+            }
+            x if x == EffectParamId::Background as u8 => {
+                EffectParam::Background(Rgb8::deserialize_from(reader)?)
+            }
+            _ => return Err(DeserializationError::InvalidValue),
+        };
+        Ok(param)
+    }
+}
+
+impl MessageSerializer for EffectParam {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u8(self.id() as u8)?;
+        match self {
+            EffectParam::Speed(value)
+            | EffectParam::Intensity(value)
+            | EffectParam::Size(value) => writer.u8(*value),
+            EffectParam::Color(color) | EffectParam::Background(color) => {
+                color.serialize_into(writer)
+            }
+        }
+    }
+}
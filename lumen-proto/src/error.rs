@@ -0,0 +1,90 @@
+use crate::header::ProtocolVersion;
+
+/// Reasons why a received datagram could not be turned into a `ControllerMessage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DeserializationError {
+    /// The message id is not known to this firmware.
+    UnknownMessageId(u16),
+    /// The datagram ended before the message was complete.
+    Truncated,
+    /// A `LedState` carried more LEDs than the strip buffer can hold.
+    LedCountOverCapacity(u16),
+    /// A `LedRange` reaches past the end of the strip buffer.
+    LedRangeOutOfBounds { offset: u16, count: u16 },
+    /// A run-length encoded frame has an empty run or runs past its LED count.
+    MalformedRle,
+    /// A fragment does not fit into its frame or the frame does not fit into the strip buffer.
+    MalformedFragment,
+    /// A field holds a value outside of its allowed range.
+    InvalidValue,
+    /// Bytes were left over after the message was fully parsed.
+    TrailingBytes(usize),
+    /// The datagram does not start with the Lumen magic, so it is foreign traffic.
+    BadHeader,
+    /// The sender speaks an incompatible major version of the protocol.
+    UnsupportedVersion(ProtocolVersion),
+    /// The message id is known, but was introduced after the version the sender claims to speak.
+    UnsupportedMessage(u16),
+}
+
+/// Reasons why a message could not be written into a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SerializationError {
+    /// The target buffer is too small to hold the whole message.
+    BufferTooSmall,
+}
+
+/// Running count of deserialization failures, one counter per reason.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeserializationErrorCounters {
+    pub unknown_message_id: u32,
+    pub truncated: u32,
+    pub led_count_over_capacity: u32,
+    pub led_range_out_of_bounds: u32,
+    pub malformed_rle: u32,
+    pub malformed_fragment: u32,
+    pub invalid_value: u32,
+    pub trailing_bytes: u32,
+    pub bad_header: u32,
+    pub unsupported_version: u32,
+    pub unsupported_message: u32,
+}
+
+impl DeserializationErrorCounters {
+    pub const fn new() -> Self {
+        Self {
+            unknown_message_id: 0,
+            truncated: 0,
+            led_count_over_capacity: 0,
+            led_range_out_of_bounds: 0,
+            malformed_rle: 0,
+            malformed_fragment: 0,
+            invalid_value: 0,
+            trailing_bytes: 0,
+            bad_header: 0,
+            unsupported_version: 0,
+            unsupported_message: 0,
+        }
+    }
+
+    /// Increments the counter belonging to the given error.
+    pub fn record(&mut self, error: DeserializationError) {
+        let counter = match error {
+            DeserializationError::UnknownMessageId(_) => &mut self.unknown_message_id,
+            DeserializationError::Truncated => &mut self.truncated,
+            DeserializationError::LedCountOverCapacity(_) => &mut self.led_count_over_capacity,
+            DeserializationError::LedRangeOutOfBounds { .. } => &mut self.led_range_out_of_bounds,
+            DeserializationError::MalformedRle => &mut self.malformed_rle,
+            DeserializationError::MalformedFragment => &mut self.malformed_fragment,
+            DeserializationError::InvalidValue => &mut self.invalid_value,
+            DeserializationError::TrailingBytes(_) => &mut self.trailing_bytes,
+            DeserializationError::BadHeader => &mut self.bad_header,
+            DeserializationError::UnsupportedVersion(_) => &mut self.unsupported_version,
+            DeserializationError::UnsupportedMessage(_) => &mut self.unsupported_message,
+        };
+        *counter = counter.saturating_add(1);
+    }
+}
@@ -0,0 +1,204 @@
+//! Frames with more LEDs than fit into a single datagram are split into fragments.
+//!
+//! Every fragment carries the id of its frame, its index, the total number of fragments and the
+//! LED offset of its values. The receiver only applies a frame once all fragments arrived.
+
+use arrayvec::ArrayVec;
+
+use crate::{rgb8::Rgb8, rgbw8::Rgbw8, LED_MAX};
+
+/// Maximum number of LEDs in a single fragment, chosen so a fragment fits into a 1024 byte datagram.
+pub const FRAGMENT_LED_MAX: usize = 300;
+
+/// Maximum number of LEDs in a single RGBW fragment, the same number of bytes as
+/// [`FRAGMENT_LED_MAX`] RGB values.
+pub const FRAGMENT_RGBW_LED_MAX: usize = FRAGMENT_LED_MAX * 3 / 4;
+
+/// Maximum number of fragments a frame can be split into.
+pub const FRAGMENT_COUNT_MAX: usize = u32::BITS as usize;
+
+const _: () = assert!(FRAGMENT_LED_MAX * FRAGMENT_COUNT_MAX >= LED_MAX);
+const _: () = assert!(FRAGMENT_RGBW_LED_MAX * FRAGMENT_COUNT_MAX >= LED_MAX);
+
+/// A single piece of a fragmented frame of at most `N` values of pixel type `P`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fragment<P, const N: usize> {
+    pub frame_id: u16,
+    pub fragment_index: u8,
+    pub fragment_count: u8,
+    /// Number of LEDs of the whole frame.
+    pub led_count: u16,
+    /// Position of the first value of this fragment in the whole frame.
+    pub offset: u16,
+    pub values: ArrayVec<P, N>,
+}
+
+/// A piece of an RGB frame, see `MessageKind::LedFragment`.
+pub type LedFragment = Fragment<Rgb8, FRAGMENT_LED_MAX>;
+
+/// A piece of an RGBW frame, see `MessageKind::LedFragmentRgbw`.
+pub type LedFragmentRgbw = Fragment<Rgbw8, FRAGMENT_RGBW_LED_MAX>;
+
+impl<P, const N: usize> Fragment<P, N> {
+    /// Checks that the fragment fits into its frame and the frame fits into the strip buffer.
+    pub fn is_valid(&self) -> bool {
+        let fragment_count = self.fragment_count as usize;
+        let end = self.offset as usize + self.values.len();
+        (1..=FRAGMENT_COUNT_MAX).contains(&fragment_count)
+            && self.fragment_index < self.fragment_count
+            && self.led_count as usize <= LED_MAX
+            && end <= self.led_count as usize
+    }
+}
+
+/// Splits a frame into fragments of at most [`FRAGMENT_LED_MAX`] LEDs.
+pub fn split_frame(frame_id: u16, frame: &[Rgb8]) -> impl Iterator<Item = LedFragment> + '_ {
+    split(frame_id, frame)
+}
+
+/// Splits an RGBW frame into fragments of at most [`FRAGMENT_RGBW_LED_MAX`] LEDs.
+pub fn split_frame_rgbw(
+    frame_id: u16,
+    frame: &[Rgbw8],
+) -> impl Iterator<Item = LedFragmentRgbw> + '_ {
+    split(frame_id, frame)
+}
+
+fn split<P: Copy, const N: usize>(
+    frame_id: u16,
+    frame: &[P],
+) -> impl Iterator<Item = Fragment<P, N>> + '_ {
+    let fragment_count = frame.len().div_ceil(N).max(1);
+    (0..fragment_count).map(move |fragment_index| {
+        let offset = fragment_index * N;
+        let end = (offset + N).min(frame.len());
+        Fragment {
+            frame_id,
+            fragment_index: fragment_index as u8,
+            fragment_count: fragment_count as u8,
+            led_count: frame.len() as u16,
+            offset: offset as u16,
+            values: frame[offset..end].iter().copied().collect(),
+        }
+    })
+}
+
+/// What happened to a fragment passed to [`FrameAssembler::push`].
+// There is no allocator on the controller, so frames are stored inline
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum FragmentOutcome<P = Rgb8> {
+    /// The fragment was stored, the frame is still incomplete.
+    Pending,
+    /// The fragment completed its frame.
+    Complete(ArrayVec<P, LED_MAX>),
+    /// The fragment belongs to a frame older than the one being assembled.
+    Stale,
+    /// The fragment contradicts the other fragments of its frame.
+    Inconsistent,
+}
+
+/// Reassembles fragmented frames. Only one frame is assembled at a time, a fragment of a newer
+/// frame discards the incomplete one.
+#[derive(Debug, Clone)]
+pub struct FrameAssembler<P = Rgb8> {
+    current: Option<PartialFrame<P>>,
+    /// Number of frames that were discarded before they were complete.
+    pub discarded: u32,
+}
+
+#[derive(Debug, Clone)]
+struct PartialFrame<P> {
+    frame_id: u16,
+    fragment_count: u8,
+    received: u32,
+    started_at_ms: u64,
+    leds: ArrayVec<P, LED_MAX>,
+}
+
+impl<P: Copy + Default> Default for FrameAssembler<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: Copy + Default> FrameAssembler<P> {
+    pub const fn new() -> Self {
+        Self {
+            current: None,
+            discarded: 0,
+        }
+    }
+
+    /// Adds a fragment received at `now_ms`. An incomplete frame older than `timeout_ms` is
+    /// thrown away first.
+    pub fn push<const N: usize>(
+        &mut self,
+        fragment: &Fragment<P, N>,
+        now_ms: u64,
+        timeout_ms: u64,
+    ) -> FragmentOutcome<P> {
+        if !fragment.is_valid() {
+            return FragmentOutcome::Inconsistent;
+        }
+        self.expire(now_ms, timeout_ms);
+
+        if let Some(current) = &self.current {
+            if current.frame_id != fragment.frame_id {
+                // Wrapping comparison, so the frame id may overflow
+                if (fragment.frame_id.wrapping_sub(current.frame_id) as i16) < 0 {
+                    return FragmentOutcome::Stale;
+                }
+                self.discard();
+            }
+        }
+
+        let current = self.current.get_or_insert_with(|| PartialFrame {
+            frame_id: fragment.frame_id,
+            fragment_count: fragment.fragment_count,
+            received: 0,
+            started_at_ms: now_ms,
+            leds: (0..fragment.led_count).map(|_| P::default()).collect(),
+        });
+
+        if current.fragment_count != fragment.fragment_count
+            || current.leds.len() != fragment.led_count as usize
+        {
+            self.discard();
+            return FragmentOutcome::Inconsistent;
+        }
+
+        let offset = fragment.offset as usize;
+        current.leds[offset..offset + fragment.values.len()].copy_from_slice(&fragment.values);
+        current.received |= 1 << fragment.fragment_index;
+
+        let all_received = u32::MAX >> (u32::BITS - current.fragment_count as u32);
+        if current.received != all_received {
+            return FragmentOutcome::Pending;
+        }
+
+        let frame = self
+            .current
+            .take()
+            .map(|frame| frame.leds)
+            .unwrap_or_default();
+        FragmentOutcome::Complete(frame)
+    }
+
+    /// Throws away the incomplete frame if it is older than `timeout_ms`.
+    pub fn expire(&mut self, now_ms: u64, timeout_ms: u64) {
+        let timed_out = self
+            .current
+            .as_ref()
+            .is_some_and(|current| now_ms.saturating_sub(current.started_at_ms) > timeout_ms);
+        if timed_out {
+            self.discard();
+        }
+    }
+
+    fn discard(&mut self) {
+        if self.current.take().is_some() {
+            self.discarded = self.discarded.saturating_add(1);
+        }
+    }
+}
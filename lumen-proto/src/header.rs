@@ -0,0 +1,59 @@
+use crate::{
+    bytestreamreader::ByteStreamReader, bytestreamwriter::ByteStreamWriter, DeserializationError,
+    DeserializationResult, SerializationResult,
+};
+
+/// Every Lumen datagram starts with these bytes, anything else is foreign traffic.
+pub const MAGIC: [u8; 4] = *b"LUMN";
+
+/// Version of the wire format.
+///
+/// A change of `major` breaks the format and is never accepted. A newer `minor` only adds
+/// message kinds or appends fields to existing ones, so the known part can still be parsed.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ProtocolVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl ProtocolVersion {
+    /// The version this crate reads and writes.
+    pub const CURRENT: ProtocolVersion = ProtocolVersion {
+        major: 1,
+        minor: 21,
+    };
+
+    /// Returns true if the sender uses a newer minor version than this crate knows about.
+    pub fn is_newer_minor(&self) -> bool {
+        self.major == Self::CURRENT.major && self.minor > Self::CURRENT.minor
+    }
+}
+
+/// Reads the magic and version in front of a message and rejects incompatible senders.
+pub fn read_header(reader: &mut ByteStreamReader) -> DeserializationResult<ProtocolVersion> {
+    for expected in MAGIC {
+        if reader.u8()? != expected {
+            return Err(DeserializationError::BadHeader);
+        }
+    }
+
+    let version = ProtocolVersion {
+        major: reader.u8()?,
+        minor: reader.u8()?,
+    };
+    if version.major != ProtocolVersion::CURRENT.major {
+        return Err(DeserializationError::UnsupportedVersion(version));
+    }
+
+    Ok(version)
+}
+
+/// Writes the magic and the current protocol version.
+pub fn write_header(writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+    for byte in MAGIC {
+        writer.u8(byte)?;
+    }
+    writer.u8(ProtocolVersion::CURRENT.major)?;
+    writer.u8(ProtocolVersion::CURRENT.minor)
+}
@@ -0,0 +1,133 @@
+use crate::{
+    bytestreamreader::{ByteStreamReader, MessageDeserializer},
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    effect::{EffectId, EffectParams},
+    DeserializationError, DeserializationResult, SerializationResult, LED_MAX,
+};
+
+/// What the strip shows once the client stopped sending keep alives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleAction {
+    /// Turns the strip off.
+    Black,
+    /// Keeps showing the last frame.
+    HoldLastFrame,
+    /// Runs a built-in effect until the client sends frames again.
+    RunEffect {
+        id: EffectId,
+        led_count: u16,
+        params: EffectParams,
+    },
+    /// Shows the scene saved with `SaveScene`, or turns the strip off if none was saved.
+    RestoreScene,
+}
+
+/// Wire ids of the variants of [`IdleAction`].
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IdleActionId {
+    Black = 0,
+    HoldLastFrame = 1,
+    RunEffect = 2,
+    RestoreScene = 3,
+}
+
+impl IdleAction {
+    pub fn id(&self) -> IdleActionId {
+        match self {
+            IdleAction::Black => IdleActionId::Black,
+            IdleAction::HoldLastFrame => IdleActionId::HoldLastFrame,
+            IdleAction::RunEffect { .. } => IdleActionId::RunEffect,
+            IdleAction::RestoreScene => IdleActionId::RestoreScene,
+        }
+    }
+}
+
+impl MessageDeserializer for IdleAction {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let action = match reader.u8()? {
+            x if x == IdleActionId::Black as u8 => IdleAction::Black,
+            x if x == IdleActionId::HoldLastFrame as u8 => IdleAction::HoldLastFrame,
+            x if x == IdleActionId::RunEffect as u8 => {
+                let id = EffectId::deserialize_from(reader)?;
+                let led_count = reader.u16()?;
+                if led_count as usize > LED_MAX {
+                    return Err(DeserializationError::LedCountOverCapacity(led_count));
+                }
+                let params = EffectParams::deserialize_from(reader)?;
+                IdleAction::RunEffect {
+                    id,
+                    led_count,
+                    params,
+                }
+            }
+            x if x == IdleActionId::RestoreScene as u8 => IdleAction::RestoreScene,
+            _ => return Err(DeserializationError::InvalidValue),
+        };
+        Ok(action)
+    }
+}
+
+impl MessageSerializer for IdleAction {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u8(self.id() as u8)?;
+        if let IdleAction::RunEffect {
+            id,
+            led_count,
+            params,
+        } = self
+        {
+            id.serialize_into(writer)?;
+            writer.u16(*led_count)?;
+            params.serialize_into(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// How the controller behaves when the keep alives of the client stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleBehavior {
+    /// Time after the last keep alive ran out before the strip goes idle,
+    /// long enough to ride out a few lost datagrams.
+    pub grace_millis: u16,
+    /// Duration of the fade to the idle action.
+    pub fade_millis: u16,
+    pub action: IdleAction,
+}
+
+impl Default for IdleBehavior {
+    fn default() -> Self {
+        Self {
+            grace_millis: 2000,
+            fade_millis: 1000,
+            action: IdleAction::Black,
+        }
+    }
+}
+
+impl MessageDeserializer for IdleBehavior {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let grace_millis = reader.u16()?;
+        let fade_millis = reader.u16()?;
+        let action = IdleAction::deserialize_from(reader)?;
+        Ok(IdleBehavior {
+            grace_millis,
+            fade_millis,
+            action,
+        })
+    }
+}
+
+impl MessageSerializer for IdleBehavior {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u16(self.grace_millis)?;
+        writer.u16(self.fade_millis)?;
+        self.action.serialize_into(writer)
+    }
+}
@@ -0,0 +1,124 @@
+//! Wire format of the messages a Lumen client sends to the controller.
+//!
+//! The crate is `no_std` so the controller firmware and host-side tools share the exact same
+//! encoder and decoder. All values are little endian.
+//!
+//! A datagram consists of the [`header`], a u64 timestamp, the [`sequence`] of the message and the
+//! u16 message id followed by the payload of that message. Datagrams can be signed, see [`auth`],
+//! with keys agreed on by [`pairing`].
+
+#![no_std]
+
+pub mod auth;
+pub mod bytestreamreader;
+pub mod bytestreamwriter;
+pub mod color_channel;
+pub mod color_order;
+pub mod compression;
+pub mod effect;
+pub mod error;
+pub mod fragment;
+pub mod header;
+pub mod idle;
+pub mod message_id;
+pub mod message_kind;
+pub mod pairing;
+pub mod pixel_format;
+pub mod reply;
+pub mod rgb8;
+pub mod rgbw8;
+pub mod sequence;
+pub mod transition;
+
+use bytestreamreader::{ByteStreamReader, MessageDeserializer};
+use bytestreamwriter::{ByteStreamWriter, MessageSerializer};
+pub use error::{DeserializationError, SerializationError};
+use header::{read_header, write_header};
+use message_kind::MessageKind;
+use sequence::{read_sequence, write_sequence, Sequence};
+
+/// Maximum number of LEDs of a frame. Frames that don't fit into a single datagram have to be
+/// sent as fragments, see [`fragment`].
+pub const LED_MAX: usize = 1200;
+
+// The LED count is sent as a u16
+const _: () = assert!(LED_MAX <= u16::MAX as usize);
+
+pub type DeserializationResult<T> = Result<T, DeserializationError>;
+pub type SerializationResult<T> = Result<T, SerializationError>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerMessage {
+    pub timestamp: Timestamp,
+    /// Orders the messages of a sender, senders older than minor version 16 send none.
+    pub sequence: Option<Sequence>,
+    pub kind: MessageKind,
+}
+
+impl ControllerMessage {
+    /// Serializes the message into `buffer` and returns the number of bytes written.
+    pub fn encode(&self, buffer: &mut [u8]) -> SerializationResult<usize> {
+        let mut writer = ByteStreamWriter::new(buffer);
+        self.serialize_into(&mut writer)?;
+        Ok(writer.written())
+    }
+
+    /// Deserializes a whole datagram into a message.
+    pub fn decode(datagram: &[u8]) -> DeserializationResult<Self> {
+        Self::deserialize_from(&mut ByteStreamReader::new(datagram))
+    }
+}
+
+impl MessageDeserializer for ControllerMessage {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let version = read_header(reader)?;
+        let timestamp = Timestamp::new(reader.u64()?);
+        let sequence = match version.minor >= 16 {
+            true => read_sequence(reader)?,
+            false => None,
+        };
+        let kind = MessageKind::deserialize_versioned(reader, version)?;
+
+        // Newer minor versions may append fields we don't know about
+        let trailing = reader.remaining();
+        if trailing != 0 && !version.is_newer_minor() {
+            return Err(DeserializationError::TrailingBytes(trailing));
+        }
+
+        Ok(ControllerMessage {
+            timestamp,
+            sequence,
+            kind,
+        })
+    }
+}
+
+impl MessageSerializer for ControllerMessage {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        write_header(writer)?;
+        writer.u64(self.timestamp.get())?;
+        write_sequence(writer, self.sequence)?;
+        self.kind.serialize_into(writer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    pub fn new(timestamp: u64) -> Self {
+        Timestamp(timestamp)
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for Timestamp {
+    fn from(timestamp: u64) -> Self {
+        Timestamp(timestamp)
+    }
+}
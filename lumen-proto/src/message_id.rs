@@ -0,0 +1,206 @@
+use super::message_kind::MessageKind;
+
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageId {
+    Empty = 0,
+    KeepAlive = 1,
+    LedState = 2,
+    LedRange = 3,
+    LedStateRle = 4,
+    LedStateDelta = 5,
+    LedFragment = 6,
+    SetBrightness = 7,
+    SetGamma = 8,
+    SetGammaTable = 9,
+    SetWhiteBalance = 10,
+    SetColorMatrix = 11,
+    SetColorTemperature = 12,
+    LedStateRgbw = 13,
+    SetPixelFormat = 14,
+    SetColorOrder = 15,
+    SetCurrentLimit = 16,
+    RunEffect = 17,
+    UpdateEffectParams = 18,
+    GetEffectParams = 19,
+    SetTransition = 20,
+    SetSmoothing = 21,
+    SetIdleBehavior = 22,
+    SaveScene = 23,
+    OpenSession = 24,
+    PairRequest = 25,
+    PairCommit = 26,
+    PairReveal = 27,
+    RevokeClient = 28,
+    GetPairedClients = 29,
+    Ping = 30,
+    GetStatus = 31,
+    SetAcks = 32,
+    LedFragmentRgbw = 33,
+}
+
+impl MessageId {
+    /// Minor protocol version that introduced this message.
+    pub fn introduced_in_minor(&self) -> u8 {
+        match self {
+            MessageId::Empty | MessageId::KeepAlive | MessageId::LedState => 0,
+            MessageId::LedRange => 1,
+            MessageId::LedStateRle | MessageId::LedStateDelta => 2,
+            MessageId::LedFragment => 3,
+            MessageId::SetBrightness => 4,
+            MessageId::SetGamma => 5,
+            MessageId::SetGammaTable => 5,
+            MessageId::SetWhiteBalance => 6,
+            MessageId::SetColorMatrix => 6,
+            MessageId::SetColorTemperature => 6,
+            MessageId::LedStateRgbw => 7,
+            MessageId::SetPixelFormat => 7,
+            MessageId::SetColorOrder => 8,
+            MessageId::SetCurrentLimit => 9,
+            MessageId::RunEffect => 10,
+            MessageId::UpdateEffectParams => 11,
+            MessageId::GetEffectParams => 11,
+            MessageId::SetTransition => 12,
+            MessageId::SetSmoothing => 13,
+            MessageId::SetIdleBehavior => 14,
+            MessageId::SaveScene => 14,
+            MessageId::OpenSession => 15,
+            MessageId::PairRequest => 17,
+            MessageId::PairCommit => 17,
+            MessageId::PairReveal => 17,
+            MessageId::RevokeClient => 17,
+            MessageId::GetPairedClients => 17,
+            MessageId::Ping => 18,
+            MessageId::GetStatus => 18,
+            MessageId::SetAcks => 18,
+            MessageId::LedFragmentRgbw => 19,
+        }
+    }
+}
+
+impl TryFrom<u16> for MessageId {
+    type Error = ();
+
+    fn try_from(v: u16) -> Result<Self, Self::Error> {
+        match v {
+            x if x == MessageId::Empty as u16 => Ok(MessageId::Empty),
+            x if x == MessageId::KeepAlive as u16 => Ok(MessageId::KeepAlive),
+            x if x == MessageId::LedState as u16 => Ok(MessageId::LedState),
+            x if x == MessageId::LedRange as u16 => Ok(MessageId::LedRange),
+            x if x == MessageId::LedStateRle as u16 => Ok(MessageId::LedStateRle),
+            x if x == MessageId::LedStateDelta as u16 => Ok(MessageId::LedStateDelta),
+            x if x == MessageId::LedFragment as u16 => Ok(MessageId::LedFragment),
+            x if x == MessageId::SetBrightness as u16 => Ok(MessageId::SetBrightness),
+            x if x == MessageId::SetGamma as u16 => Ok(MessageId::SetGamma),
+            x if x == MessageId::SetGammaTable as u16 => Ok(MessageId::SetGammaTable),
+            x if x == MessageId::SetWhiteBalance as u16 => Ok(MessageId::SetWhiteBalance),
+            x if x == MessageId::SetColorMatrix as u16 => Ok(MessageId::SetColorMatrix),
+            x if x == MessageId::SetColorTemperature as u16 => Ok(MessageId::SetColorTemperature),
+            x if x == MessageId::LedStateRgbw as u16 => Ok(MessageId::LedStateRgbw),
+            x if x == MessageId::SetPixelFormat as u16 => Ok(MessageId::SetPixelFormat),
+            x if x == MessageId::SetColorOrder as u16 => Ok(MessageId::SetColorOrder),
+            x if x == MessageId::SetCurrentLimit as u16 => Ok(MessageId::SetCurrentLimit),
+            x if x == MessageId::RunEffect as u16 => Ok(MessageId::RunEffect),
+            x if x == MessageId::UpdateEffectParams as u16 => Ok(MessageId::UpdateEffectParams),
+            x if x == MessageId::GetEffectParams as u16 => Ok(MessageId::GetEffectParams),
+            x if x == MessageId::SetTransition as u16 => Ok(MessageId::SetTransition),
+            x if x == MessageId::SetSmoothing as u16 => Ok(MessageId::SetSmoothing),
+            x if x == MessageId::SetIdleBehavior as u16 => Ok(MessageId::SetIdleBehavior),
+            x if x == MessageId::SaveScene as u16 => Ok(MessageId::SaveScene),
+            x if x == MessageId::OpenSession as u16 => Ok(MessageId::OpenSession),
+            x if x == MessageId::PairRequest as u16 => Ok(MessageId::PairRequest),
+            x if x == MessageId::PairCommit as u16 => Ok(MessageId::PairCommit),
+            x if x == MessageId::PairReveal as u16 => Ok(MessageId::PairReveal),
+            x if x == MessageId::RevokeClient as u16 => Ok(MessageId::RevokeClient),
+            x if x == MessageId::GetPairedClients as u16 => Ok(MessageId::GetPairedClients),
+            x if x == MessageId::Ping as u16 => Ok(MessageId::Ping),
+            x if x == MessageId::GetStatus as u16 => Ok(MessageId::GetStatus),
+            x if x == MessageId::SetAcks as u16 => Ok(MessageId::SetAcks),
+            x if x == MessageId::LedFragmentRgbw as u16 => Ok(MessageId::LedFragmentRgbw),
+            _ => Err(()),
+        }
+    }
+}
+
+impl From<&MessageKind> for MessageId {
+    fn from(value: &MessageKind) -> Self {
+        match value {
+            MessageKind::Empty => MessageId::Empty,
+            MessageKind::KeepAlive { .. } => MessageId::KeepAlive,
+            MessageKind::LedState { .. } => MessageId::LedState,
+            MessageKind::LedRange { .. } => MessageId::LedRange,
+            MessageKind::LedStateRle { .. } => MessageId::LedStateRle,
+            MessageKind::LedStateDelta { .. } => MessageId::LedStateDelta,
+            MessageKind::LedFragment(_) => MessageId::LedFragment,
+            MessageKind::SetBrightness { .. } => MessageId::SetBrightness,
+            MessageKind::SetGamma { .. } => MessageId::SetGamma,
+            MessageKind::SetGammaTable { .. } => MessageId::SetGammaTable,
+            MessageKind::SetWhiteBalance { .. } => MessageId::SetWhiteBalance,
+            MessageKind::SetColorMatrix { .. } => MessageId::SetColorMatrix,
+            MessageKind::SetColorTemperature { .. } => MessageId::SetColorTemperature,
+            MessageKind::LedStateRgbw { .. } => MessageId::LedStateRgbw,
+            MessageKind::SetPixelFormat { .. } => MessageId::SetPixelFormat,
+            MessageKind::SetColorOrder { .. } => MessageId::SetColorOrder,
+            MessageKind::SetCurrentLimit { .. } => MessageId::SetCurrentLimit,
+            MessageKind::RunEffect { .. } => MessageId::RunEffect,
+            MessageKind::UpdateEffectParams { .. } => MessageId::UpdateEffectParams,
+            MessageKind::GetEffectParams => MessageId::GetEffectParams,
+            MessageKind::SetTransition { .. } => MessageId::SetTransition,
+            MessageKind::SetSmoothing { .. } => MessageId::SetSmoothing,
+            MessageKind::SetIdleBehavior(_) => MessageId::SetIdleBehavior,
+            MessageKind::SaveScene => MessageId::SaveScene,
+            MessageKind::OpenSession { .. } => MessageId::OpenSession,
+            MessageKind::PairRequest { .. } => MessageId::PairRequest,
+            MessageKind::PairCommit { .. } => MessageId::PairCommit,
+            MessageKind::PairReveal { .. } => MessageId::PairReveal,
+            MessageKind::RevokeClient { .. } => MessageId::RevokeClient,
+            MessageKind::GetPairedClients => MessageId::GetPairedClients,
+            MessageKind::Ping => MessageId::Ping,
+            MessageKind::GetStatus => MessageId::GetStatus,
+            MessageKind::SetAcks { .. } => MessageId::SetAcks,
+            MessageKind::LedFragmentRgbw(_) => MessageId::LedFragmentRgbw,
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for MessageId {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            MessageId::Empty => defmt::write!(f, "Empty"),
+            MessageId::KeepAlive => defmt::write!(f, "KeepAlive"),
+            MessageId::LedState => defmt::write!(f, "LedState"),
+            MessageId::LedRange => defmt::write!(f, "LedRange"),
+            MessageId::LedStateRle => defmt::write!(f, "LedStateRle"),
+            MessageId::LedStateDelta => defmt::write!(f, "LedStateDelta"),
+            MessageId::LedFragment => defmt::write!(f, "LedFragment"),
+            MessageId::SetBrightness => defmt::write!(f, "SetBrightness"),
+            MessageId::SetGamma => defmt::write!(f, "SetGamma"),
+            MessageId::SetGammaTable => defmt::write!(f, "SetGammaTable"),
+            MessageId::SetWhiteBalance => defmt::write!(f, "SetWhiteBalance"),
+            MessageId::SetColorMatrix => defmt::write!(f, "SetColorMatrix"),
+            MessageId::SetColorTemperature => defmt::write!(f, "SetColorTemperature"),
+            MessageId::LedStateRgbw => defmt::write!(f, "LedStateRgbw"),
+            MessageId::SetPixelFormat => defmt::write!(f, "SetPixelFormat"),
+            MessageId::SetColorOrder => defmt::write!(f, "SetColorOrder"),
+            MessageId::SetCurrentLimit => defmt::write!(f, "SetCurrentLimit"),
+            MessageId::RunEffect => defmt::write!(f, "RunEffect"),
+            MessageId::UpdateEffectParams => defmt::write!(f, "UpdateEffectParams"),
+            MessageId::GetEffectParams => defmt::write!(f, "GetEffectParams"),
+            MessageId::SetTransition => defmt::write!(f, "SetTransition"),
+            MessageId::SetSmoothing => defmt::write!(f, "SetSmoothing"),
+            MessageId::SetIdleBehavior => defmt::write!(f, "SetIdleBehavior"),
+            MessageId::SaveScene => defmt::write!(f, "SaveScene"),
+            MessageId::OpenSession => defmt::write!(f, "OpenSession"),
+            MessageId::PairRequest => defmt::write!(f, "PairRequest"),
+            MessageId::PairCommit => defmt::write!(f, "PairCommit"),
+            MessageId::PairReveal => defmt::write!(f, "PairReveal"),
+            MessageId::RevokeClient => defmt::write!(f, "RevokeClient"),
+            MessageId::GetPairedClients => defmt::write!(f, "GetPairedClients"),
+            MessageId::Ping => defmt::write!(f, "Ping"),
+            MessageId::GetStatus => defmt::write!(f, "GetStatus"),
+            MessageId::SetAcks => defmt::write!(f, "SetAcks"),
+            MessageId::LedFragmentRgbw => defmt::write!(f, "LedFragmentRgbw"),
+        }
+    }
+}
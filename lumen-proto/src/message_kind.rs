@@ -0,0 +1,627 @@
+use arrayvec::ArrayVec;
+
+use super::{
+    bytestreamreader::{ByteStreamReader, MessageDeserializer},
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    color_channel::ColorChannel,
+    color_order::ColorOrder,
+    compression::{read_rle_frame, write_rle_frame},
+    effect::{EffectId, EffectParam, EffectParams, EFFECT_PARAM_UPDATE_MAX},
+    fragment::{Fragment, LedFragment, LedFragmentRgbw},
+    header::ProtocolVersion,
+    idle::IdleBehavior,
+    message_id::MessageId,
+    pairing::{Commitment, PairingNonce, PublicKey},
+    pixel_format::PixelFormat,
+    rgb8::Rgb8,
+    rgbw8::Rgbw8,
+    transition::Easing,
+    DeserializationError, DeserializationResult, SerializationResult, LED_MAX,
+};
+
+// There is no allocator on the controller, so frames are stored inline
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageKind {
+    Empty,
+    KeepAlive {
+        millis: u32,
+    },
+    /// A whole frame. With `transition` the controller fades to it as set by `SetTransition`.
+    LedState {
+        led_values: ArrayVec<Rgb8, LED_MAX>,
+        transition: bool,
+    },
+    /// Replaces `values.len()` LEDs starting at `offset` and leaves the rest of the frame alone.
+    LedRange {
+        offset: u16,
+        values: ArrayVec<Rgb8, LED_MAX>,
+    },
+    /// A whole frame sent run-length encoded. It becomes the base for following deltas.
+    LedStateRle {
+        sequence: u16,
+        led_values: ArrayVec<Rgb8, LED_MAX>,
+    },
+    /// The XOR of the frame `base_sequence` and the new frame `sequence`.
+    /// Only valid if the receiver still holds the base frame.
+    LedStateDelta {
+        sequence: u16,
+        base_sequence: u16,
+        delta: ArrayVec<Rgb8, LED_MAX>,
+    },
+    /// A piece of a frame that is too large for a single datagram.
+    LedFragment(LedFragment),
+    /// Sets the master brightness of the strip, fading from the current one over `fade_millis`.
+    SetBrightness {
+        brightness: u8,
+        fade_millis: u16,
+    },
+    /// Sets a gamma curve per channel. Exponents are given in hundredths, 220 is a gamma of 2.2.
+    SetGamma {
+        red: u16,
+        green: u16,
+        blue: u16,
+    },
+    /// Replaces the gamma lookup table of a single channel.
+    SetGammaTable {
+        channel: ColorChannel,
+        table: [u8; 256],
+    },
+    /// Sets per-channel gains of the output, 255 leaves a channel unchanged.
+    SetWhiteBalance {
+        red: u8,
+        green: u8,
+        blue: u8,
+    },
+    /// Sets a row-major 3x3 color correction matrix in 8.8 fixed point, 256 is 1.0.
+    /// `None` disables the matrix.
+    SetColorMatrix {
+        matrix: Option<[i16; 9]>,
+    },
+    /// Sets the white balance gains to match a color temperature.
+    SetColorTemperature {
+        kelvin: u16,
+    },
+    /// A whole frame for RGBW strips. Frames that don't fit into a single datagram are sent as
+    /// `LedFragmentRgbw`.
+    LedStateRgbw {
+        led_values: ArrayVec<Rgbw8, LED_MAX>,
+    },
+    /// Configures the kind of LEDs connected to the controller. With `extract_white` the white
+    /// channel of RGBW strips is derived from the common part of red, green and blue.
+    SetPixelFormat {
+        format: PixelFormat,
+        extract_white: bool,
+    },
+    /// Sets the order in which the output sends the color channels to the strip.
+    SetColorOrder {
+        order: ColorOrder,
+    },
+    /// Limits the estimated current draw of the strip to `budget_ma`, 0 disables the limit.
+    /// `channel_ua` is the draw of a single LED channel at full intensity in microamps, in the
+    /// order red, green, blue, white, and `idle_ua` the draw of an LED that is off.
+    SetCurrentLimit {
+        budget_ma: u32,
+        channel_ua: [u16; 4],
+        idle_ua: u16,
+    },
+    /// Starts a built-in effect on `led_count` LEDs. It runs until the next frame is received.
+    RunEffect {
+        id: EffectId,
+        led_count: u16,
+        params: EffectParams,
+    },
+    /// Changes parameters of the running effect without restarting it.
+    UpdateEffectParams {
+        params: ArrayVec<EffectParam, EFFECT_PARAM_UPDATE_MAX>,
+    },
+    /// Asks the controller to reply with the running effect and its parameters.
+    GetEffectParams,
+    /// Configures the fades of frames with the transition flag, effect changes and the blackout
+    /// after a keep alive timeout. A duration of 0 switches without a fade.
+    SetTransition {
+        duration_millis: u16,
+        easing: Easing,
+    },
+    /// Smooths the output toward the newest frame, for clients that stream at low or uneven rates.
+    /// `factor` is the share of the remaining difference that is kept every 10 ms, in 1/256.
+    /// A factor of 0 shows every frame exactly as sent.
+    SetSmoothing {
+        factor: u8,
+    },
+    /// Configures what happens when the keep alives stop, persisted across reboots.
+    SetIdleBehavior(IdleBehavior),
+    /// Saves the last frame sent by the client as the scene restored when idle, persisted across
+    /// reboots.
+    SaveScene,
+    /// Opens a session for the client. The live session with the highest priority drives the
+    /// strip, the others take over once its lease runs out. Keep alives renew the lease.
+    /// `client_id` identifies the client across restarts that change its address.
+    OpenSession {
+        client_id: u32,
+        priority: u8,
+        lease_millis: u16,
+    },
+    /// Starts pairing with the X25519 public key of the client, see [`pairing`](crate::pairing).
+    /// Only accepted while the controller is in pairing mode.
+    PairRequest {
+        public_key: PublicKey,
+    },
+    /// Commits the client to the next color of the pairing code shown on the strip.
+    PairCommit {
+        commitment: Commitment,
+    },
+    /// Reveals the nonce of the last commitment. The controller stores the key once every color
+    /// matched.
+    PairReveal {
+        nonce: PairingNonce,
+    },
+    /// Removes the key of a paired client. Only accepted in signed or encrypted datagrams, with the
+    /// pre-shared key or the key that is removed. Answered with `Ack` or `Nack`.
+    RevokeClient {
+        key_id: u8,
+    },
+    /// Asks the controller to reply with the ids of the paired clients. Only accepted in signed
+    /// or encrypted datagrams.
+    GetPairedClients,
+    /// Asks the controller to reply with `Pong`, to tell whether it is alive and accepts the
+    /// datagrams of the client. Like `GetStatus` only answered in signed or encrypted datagrams
+    /// while signatures are required, the reply is wrapped like the query.
+    Ping,
+    /// Asks the controller to reply with its [`ControllerStatus`](crate::reply::ControllerStatus).
+    GetStatus,
+    /// Makes the controller answer the control messages of the sender with `Ack`, or with `Nack`
+    /// and the reason if it discards them. Frames are never acknowledged.
+    SetAcks {
+        enabled: bool,
+    },
+    /// A piece of an RGBW frame that is too large for a single datagram, the RGBW counterpart
+    /// of `LedFragment`.
+    LedFragmentRgbw(LedFragmentRgbw),
+}
+
+impl MessageDeserializer for MessageKind {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        Self::deserialize_versioned(reader, ProtocolVersion::CURRENT)
+    }
+}
+
+impl MessageKind {
+    /// Deserializes a message sent by a peer speaking `version` of the protocol.
+    /// Messages the peer cannot know about yet are rejected.
+    pub fn deserialize_versioned(
+        reader: &mut ByteStreamReader,
+        version: ProtocolVersion,
+    ) -> DeserializationResult<Self> {
+        let kind = reader.u16()?;
+        let Ok(msg_id) = MessageId::try_from(kind) else {
+            return Err(DeserializationError::UnknownMessageId(kind));
+        };
+        if msg_id.introduced_in_minor() > version.minor {
+            return Err(DeserializationError::UnsupportedMessage(kind));
+        }
+
+        let message = match msg_id {
+            MessageId::Empty => MessageKind::Empty,
+            MessageId::KeepAlive => {
+                let millis = reader.u32()?;
+                MessageKind::KeepAlive { millis }
+            }
+            MessageId::LedState => {
+                let led_values_cnt = reader.u16()?;
+                let led_values = read_led_values(reader, led_values_cnt)?;
+                // The transition flag was appended in minor version 12
+                let transition = version.minor >= 12 && reader.bool()?;
+                MessageKind::LedState {
+                    led_values,
+                    transition,
+                }
+            }
+            MessageId::LedRange => {
+                let offset = reader.u16()?;
+                let values_cnt = reader.u16()?;
+                if offset as usize + values_cnt as usize > LED_MAX {
+                    return Err(DeserializationError::LedRangeOutOfBounds {
+                        offset,
+                        count: values_cnt,
+                    });
+                }
+
+                let values = read_led_values(reader, values_cnt)?;
+                MessageKind::LedRange { offset, values }
+            }
+            MessageId::LedStateRle => {
+                let sequence = reader.u16()?;
+                let led_values = read_rle_frame(reader)?;
+                MessageKind::LedStateRle {
+                    sequence,
+                    led_values,
+                }
+            }
+            MessageId::LedStateDelta => {
+                let sequence = reader.u16()?;
+                let base_sequence = reader.u16()?;
+                let delta = read_rle_frame(reader)?;
+                MessageKind::LedStateDelta {
+                    sequence,
+                    base_sequence,
+                    delta,
+                }
+            }
+            MessageId::LedFragment => MessageKind::LedFragment(read_fragment(reader)?),
+            MessageId::SetBrightness => {
+                let brightness = reader.u8()?;
+                let fade_millis = reader.u16()?;
+                MessageKind::SetBrightness {
+                    brightness,
+                    fade_millis,
+                }
+            }
+            MessageId::SetGamma => {
+                let red = reader.u16()?;
+                let green = reader.u16()?;
+                let blue = reader.u16()?;
+                MessageKind::SetGamma { red, green, blue }
+            }
+            MessageId::SetGammaTable => {
+                let channel = ColorChannel::deserialize_from(reader)?;
+                let table = reader.array()?;
+                MessageKind::SetGammaTable { channel, table }
+            }
+            MessageId::SetWhiteBalance => {
+                let red = reader.u8()?;
+                let green = reader.u8()?;
+                let blue = reader.u8()?;
+                MessageKind::SetWhiteBalance { red, green, blue }
+            }
+            MessageId::SetColorMatrix => {
+                let matrix = match reader.u8()? {
+                    0 => None,
+                    1 => {
+                        let mut matrix = [0; 9];
+                        for value in matrix.iter_mut() {
+                            *value = reader.i16()?;
+                        }
+                        Some(matrix)
+                    }
+                    _ => return Err(DeserializationError::InvalidValue),
+                };
+                MessageKind::SetColorMatrix { matrix }
+            }
+            MessageId::SetColorTemperature => {
+                let kelvin = reader.u16()?;
+                MessageKind::SetColorTemperature { kelvin }
+            }
+            MessageId::LedStateRgbw => {
+                let led_values_cnt = reader.u16()?;
+                if led_values_cnt as usize > LED_MAX {
+                    return Err(DeserializationError::LedCountOverCapacity(led_values_cnt));
+                }
+
+                let mut led_values = ArrayVec::new();
+                for _ in 0..led_values_cnt {
+                    led_values.push(Rgbw8::deserialize_from(reader)?);
+                }
+                MessageKind::LedStateRgbw { led_values }
+            }
+            MessageId::SetPixelFormat => {
+                let format = PixelFormat::deserialize_from(reader)?;
+                let extract_white = reader.bool()?;
+                MessageKind::SetPixelFormat {
+                    format,
+                    extract_white,
+                }
+            }
+            MessageId::SetColorOrder => MessageKind::SetColorOrder {
+                order: ColorOrder::deserialize_from(reader)?,
+            },
+            MessageId::SetCurrentLimit => {
+                let budget_ma = reader.u32()?;
+                let mut channel_ua = [0; 4];
+                for value in channel_ua.iter_mut() {
+                    *value = reader.u16()?;
+                }
+                let idle_ua = reader.u16()?;
+                MessageKind::SetCurrentLimit {
+                    budget_ma,
+                    channel_ua,
+                    idle_ua,
+                }
+            }
+            MessageId::RunEffect => {
+                let id = EffectId::deserialize_from(reader)?;
+                let led_count = reader.u16()?;
+                if led_count as usize > LED_MAX {
+                    return Err(DeserializationError::LedCountOverCapacity(led_count));
+                }
+                let params = EffectParams::deserialize_from(reader)?;
+                MessageKind::RunEffect {
+                    id,
+                    led_count,
+                    params,
+                }
+            }
+            MessageId::UpdateEffectParams => {
+                let count = reader.u8()? as usize;
+                if count > EFFECT_PARAM_UPDATE_MAX {
+                    return Err(DeserializationError::InvalidValue);
+                }
+
+                let mut params = ArrayVec::new();
+                for _ in 0..count {
+                    params.push(EffectParam::deserialize_from(reader)?);
+                }
+                MessageKind::UpdateEffectParams { params }
+            }
+            MessageId::GetEffectParams => MessageKind::GetEffectParams,
+            MessageId::SetTransition => {
+                let duration_millis = reader.u16()?;
+                let easing = Easing::deserialize_from(reader)?;
+                MessageKind::SetTransition {
+                    duration_millis,
+                    easing,
+                }
+            }
+            MessageId::SetSmoothing => MessageKind::SetSmoothing {
+                factor: reader.u8()?,
+            },
+            MessageId::SetIdleBehavior => {
+                MessageKind::SetIdleBehavior(IdleBehavior::deserialize_from(reader)?)
+            }
+            MessageId::SaveScene => MessageKind::SaveScene,
+            MessageId::OpenSession => {
+                let client_id = reader.u32()?;
+                let priority = reader.u8()?;
+                let lease_millis = reader.u16()?;
+                MessageKind::OpenSession {
+                    client_id,
+                    priority,
+                    lease_millis,
+                }
+            }
+            MessageId::PairRequest => MessageKind::PairRequest {
+                public_key: reader.array()?,
+            },
+            MessageId::PairCommit => MessageKind::PairCommit {
+                commitment: reader.array()?,
+            },
+            MessageId::PairReveal => MessageKind::PairReveal {
+                nonce: reader.array()?,
+            },
+            MessageId::RevokeClient => MessageKind::RevokeClient {
+                key_id: reader.u8()?,
+            },
+            MessageId::GetPairedClients => MessageKind::GetPairedClients,
+            MessageId::Ping => MessageKind::Ping,
+            MessageId::GetStatus => MessageKind::GetStatus,
+            MessageId::SetAcks => MessageKind::SetAcks {
+                enabled: reader.bool()?,
+            },
+            MessageId::LedFragmentRgbw => MessageKind::LedFragmentRgbw(read_fragment(reader)?),
+        };
+
+        Ok(message)
+    }
+}
+
+/// Reads `count` LED values, rejecting counts that don't fit into a frame.
+fn read_led_values(
+    reader: &mut ByteStreamReader,
+    count: u16,
+) -> DeserializationResult<ArrayVec<Rgb8, LED_MAX>> {
+    if count as usize > LED_MAX {
+        return Err(DeserializationError::LedCountOverCapacity(count));
+    }
+
+    let mut led_values = ArrayVec::new();
+    for _ in 0..count {
+        let rgb = Rgb8::deserialize_from(reader)?;
+        led_values.push(rgb);
+    }
+
+    Ok(led_values)
+}
+
+/// Writes the u16 count followed by the LED values.
+fn write_led_values(writer: &mut ByteStreamWriter, led_values: &[Rgb8]) -> SerializationResult<()> {
+    // LED_MAX fits into the u16 count field, see the assertion in lib.rs
+    writer.u16(led_values.len() as u16)?;
+    for rgb in led_values {
+        rgb.serialize_into(writer)?;
+    }
+    Ok(())
+}
+
+/// Reads a fragment of at most `N` values, rejecting fragments that don't fit into their frame.
+fn read_fragment<P, const N: usize>(
+    reader: &mut ByteStreamReader,
+) -> DeserializationResult<Fragment<P, N>>
+where
+    P: MessageDeserializer<Result = DeserializationResult<P>>,
+{
+    let frame_id = reader.u16()?;
+    let fragment_index = reader.u8()?;
+    let fragment_count = reader.u8()?;
+    let led_count = reader.u16()?;
+    let offset = reader.u16()?;
+    let values_cnt = reader.u16()?;
+    if values_cnt as usize > N {
+        return Err(DeserializationError::MalformedFragment);
+    }
+
+    let mut values = ArrayVec::new();
+    for _ in 0..values_cnt {
+        values.push(P::deserialize_from(reader)?);
+    }
+
+    let fragment = Fragment {
+        frame_id,
+        fragment_index,
+        fragment_count,
+        led_count,
+        offset,
+        values,
+    };
+    if !fragment.is_valid() {
+        return Err(DeserializationError::MalformedFragment);
+    }
+    Ok(fragment)
+}
+
+fn write_fragment<P: MessageSerializer, const N: usize>(
+    writer: &mut ByteStreamWriter,
+    fragment: &Fragment<P, N>,
+) -> SerializationResult<()> {
+    writer.u16(fragment.frame_id)?;
+    writer.u8(fragment.fragment_index)?;
+    writer.u8(fragment.fragment_count)?;
+    writer.u16(fragment.led_count)?;
+    writer.u16(fragment.offset)?;
+    // Fragments hold far fewer values than fit into the u16 count field
+    writer.u16(fragment.values.len() as u16)?;
+    for value in &fragment.values {
+        value.serialize_into(writer)?;
+    }
+    Ok(())
+}
+
+impl MessageSerializer for MessageKind {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u16(MessageId::from(self) as u16)?;
+
+        match self {
+            MessageKind::Empty => {}
+            MessageKind::KeepAlive { millis } => writer.u32(*millis)?,
+            MessageKind::LedState {
+                led_values,
+                transition,
+            } => {
+                write_led_values(writer, led_values)?;
+                writer.bool(*transition)?;
+            }
+            MessageKind::LedRange { offset, values } => {
+                writer.u16(*offset)?;
+                write_led_values(writer, values)?;
+            }
+            MessageKind::LedStateRle {
+                sequence,
+                led_values,
+            } => {
+                writer.u16(*sequence)?;
+                write_rle_frame(writer, led_values)?;
+            }
+            MessageKind::LedStateDelta {
+                sequence,
+                base_sequence,
+                delta,
+            } => {
+                writer.u16(*sequence)?;
+                writer.u16(*base_sequence)?;
+                write_rle_frame(writer, delta)?;
+            }
+            MessageKind::LedFragment(fragment) => write_fragment(writer, fragment)?,
+            MessageKind::SetBrightness {
+                brightness,
+                fade_millis,
+            } => {
+                writer.u8(*brightness)?;
+                writer.u16(*fade_millis)?;
+            }
+            MessageKind::SetGamma { red, green, blue } => {
+                writer.u16(*red)?;
+                writer.u16(*green)?;
+                writer.u16(*blue)?;
+            }
+            MessageKind::SetGammaTable { channel, table } => {
+                channel.serialize_into(writer)?;
+                writer.bytes(table)?;
+            }
+            MessageKind::SetWhiteBalance { red, green, blue } => {
+                writer.u8(*red)?;
+                writer.u8(*green)?;
+                writer.u8(*blue)?;
+            }
+            MessageKind::SetColorMatrix { matrix } => match matrix {
+                None => writer.u8(0)?,
+                Some(matrix) => {
+                    writer.u8(1)?;
+                    for value in matrix {
+                        writer.i16(*value)?;
+                    }
+                }
+            },
+            MessageKind::SetColorTemperature { kelvin } => writer.u16(*kelvin)?,
+            MessageKind::LedStateRgbw { led_values } => {
+                writer.u16(led_values.len() as u16)?;
+                for rgbw in led_values {
+                    rgbw.serialize_into(writer)?;
+                }
+            }
+            MessageKind::SetPixelFormat {
+                format,
+                extract_white,
+            } => {
+                format.serialize_into(writer)?;
+                writer.bool(*extract_white)?;
+            }
+            MessageKind::SetColorOrder { order } => order.serialize_into(writer)?,
+            MessageKind::SetCurrentLimit {
+                budget_ma,
+                channel_ua,
+                idle_ua,
+            } => {
+                writer.u32(*budget_ma)?;
+                for value in channel_ua {
+                    writer.u16(*value)?;
+                }
+                writer.u16(*idle_ua)?;
+            }
+            MessageKind::RunEffect {
+                id,
+                led_count,
+                params,
+            } => {
+                id.serialize_into(writer)?;
+                writer.u16(*led_count)?;
+                params.serialize_into(writer)?;
+            }
+            MessageKind::UpdateEffectParams { params } => {
+                writer.u8(params.len() as u8)?;
+                for param in params {
+                    param.serialize_into(writer)?;
+                }
+            }
+            MessageKind::GetEffectParams => {}
+            MessageKind::SetTransition {
+                duration_millis,
+                easing,
+            } => {
+                writer.u16(*duration_millis)?;
+                easing.serialize_into(writer)?;
+            }
+            MessageKind::SetSmoothing { factor } => writer.u8(*factor)?,
+            MessageKind::SetIdleBehavior(behavior) => behavior.serialize_into(writer)?,
+            MessageKind::SaveScene => {}
+            MessageKind::OpenSession {
+                client_id,
+                priority,
+                lease_millis,
+            } => {
+                writer.u32(*client_id)?;
+                writer.u8(*priority)?;
+                writer.u16(*lease_millis)?;
+            }
+            MessageKind::PairRequest { public_key } => writer.bytes(public_key)?,
+            MessageKind::PairCommit { commitment } => writer.bytes(commitment)?,
+            MessageKind::PairReveal { nonce } => writer.bytes(nonce)?,
+            MessageKind::RevokeClient { key_id } => writer.u8(*key_id)?,
+            MessageKind::GetPairedClients | MessageKind::Ping | MessageKind::GetStatus => {}
+            MessageKind::SetAcks { enabled } => writer.bool(*enabled)?,
+            MessageKind::LedFragmentRgbw(fragment) => write_fragment(writer, fragment)?,
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! Pairing of clients with the controller, so no key has to be compiled into the firmware.
+//!
+//! The client and the controller agree on a key with X25519. To make sure the client talks to
+//! the controller and not to someone in between, the controller shows a random [`PairingCode`]
+//! on the strip and the user types it into the client:
+//!
+//! 1. The client sends `PairRequest` with its public key, the controller answers with its own
+//!    and shows the code.
+//! 2. For every color of the code, the client sends `PairCommit` with a [`commitment`] to the
+//!    color and the controller answers with its own. Then the client sends `PairReveal` with the
+//!    nonce of its commitment, the controller checks it and answers with its nonce.
+//! 3. With the last color the controller stores the key and answers with the id of the key.
+//!
+//! Both sides check the commitments of the other and stop at the first wrong color. Someone in
+//! between has to commit to every color before it is revealed, so they have to guess the whole
+//! code. Revealing the code at once would hand it to them for the other side.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use x25519_dalek::{x25519, X25519_BASEPOINT_BYTES};
+
+use crate::{auth::Key, rgb8::Rgb8};
+
+pub const PUBLIC_KEY_LEN: usize = 32;
+/// Number of colors of a pairing code.
+pub const CODE_LEN: usize = 5;
+pub const NONCE_LEN: usize = 16;
+/// Number of clients the controller keeps keys for.
+pub const PAIRED_CLIENTS_MAX: usize = 8;
+
+/// The colors a pairing code is made of, in the order of their symbols: red, green, blue,
+/// yellow, cyan, magenta, white and orange.
+pub const CODE_COLORS: [Rgb8; 8] = [
+    Rgb8 { r: 255, g: 0, b: 0 },
+    Rgb8 { r: 0, g: 255, b: 0 },
+    Rgb8 { r: 0, g: 0, b: 255 },
+    Rgb8 {
+        r: 255,
+        g: 200,
+        b: 0,
+    },
+    Rgb8 {
+        r: 0,
+        g: 255,
+        b: 255,
+    },
+    Rgb8 {
+        r: 255,
+        g: 0,
+        b: 255,
+    },
+    Rgb8 {
+        r: 255,
+        g: 255,
+        b: 255,
+    },
+    Rgb8 {
+        r: 255,
+        g: 64,
+        b: 0,
+    },
+];
+
+pub type PublicKey = [u8; PUBLIC_KEY_LEN];
+pub type Commitment = [u8; 32];
+pub type PairingNonce = [u8; NONCE_LEN];
+
+/// The code shown on the strip, every symbol is an index into [`CODE_COLORS`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PairingCode([u8; CODE_LEN]);
+
+impl PairingCode {
+    /// Takes the symbols from the lowest 15 bits of `random`.
+    pub fn new(random: u16) -> Self {
+        let mut symbols = [0; CODE_LEN];
+        for (i, symbol) in symbols.iter_mut().enumerate() {
+            *symbol = (random >> (3 * i)) as u8 & 0b111;
+        }
+        Self(symbols)
+    }
+
+    /// The code typed in by the user. Returns `None` for symbols outside of [`CODE_COLORS`].
+    pub fn from_symbols(symbols: [u8; CODE_LEN]) -> Option<Self> {
+        match symbols
+            .iter()
+            .all(|&symbol| (symbol as usize) < CODE_COLORS.len())
+        {
+            true => Some(Self(symbols)),
+            false => None,
+        }
+    }
+
+    pub fn symbols(&self) -> [u8; CODE_LEN] {
+        self.0
+    }
+
+    pub fn colors(&self) -> [Rgb8; CODE_LEN] {
+        self.0.map(|symbol| CODE_COLORS[symbol as usize])
+    }
+}
+
+/// The side a commitment comes from, so the commitment of one side can't be sent back as the
+/// one of the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Client,
+    Controller,
+}
+
+/// One side of the X25519 key exchange.
+pub struct KeyExchange {
+    secret: [u8; 32],
+    public_key: PublicKey,
+}
+
+impl KeyExchange {
+    /// `secret` has to be random and must not be used for anything else.
+    pub fn new(secret: [u8; 32]) -> Self {
+        Self {
+            secret,
+            public_key: x25519(secret, X25519_BASEPOINT_BYTES),
+        }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    /// The key a client shares with the controller of the public key `controller`.
+    /// Returns `None` if the public key is one of the few that yield a known key.
+    pub fn client_key(&self, controller: &PublicKey) -> Option<Key> {
+        self.key(&self.public_key, controller, controller)
+    }
+
+    /// The key the controller shares with the client of the public key `client`.
+    /// Returns `None` if the public key is one of the few that yield a known key.
+    pub fn controller_key(&self, client: &PublicKey) -> Option<Key> {
+        self.key(client, &self.public_key, client)
+    }
+
+    /// HMAC-SHA256 over both public keys under the shared secret.
+    fn key(&self, client: &PublicKey, controller: &PublicKey, peer: &PublicKey) -> Option<Key> {
+        let shared = x25519(self.secret, *peer);
+        // Public keys of low order make the shared secret zero, no matter the own secret
+        if shared == [0; 32] {
+            return None;
+        }
+
+        let mut mac = hmac(&shared);
+        mac.update(b"lumen pairing");
+        mac.update(client);
+        mac.update(controller);
+        Some(mac.finalize().into_bytes().into())
+    }
+}
+
+/// HMAC-SHA256 over the color of the code in `round` and a random nonce under the key of the
+/// pairing. It binds the side to the color without revealing it until the nonce is sent.
+/// Panics if `round` is not below [`CODE_LEN`].
+pub fn commitment(
+    key: &Key,
+    side: Side,
+    code: &PairingCode,
+    round: usize,
+    nonce: &PairingNonce,
+) -> Commitment {
+    commitment_mac(key, side, code, round, nonce)
+        .finalize()
+        .into_bytes()
+        .into()
+}
+
+/// Returns true if `commitment` was made with `key` to the color of the code in `round` and
+/// `nonce`. The commitment is compared in constant time.
+pub fn verify_commitment(
+    key: &Key,
+    side: Side,
+    code: &PairingCode,
+    round: usize,
+    nonce: &PairingNonce,
+    commitment: &Commitment,
+) -> bool {
+    commitment_mac(key, side, code, round, nonce)
+        .verify_slice(commitment)
+        .is_ok()
+}
+
+fn commitment_mac(
+    key: &Key,
+    side: Side,
+    code: &PairingCode,
+    round: usize,
+    nonce: &PairingNonce,
+) -> Hmac<Sha256> {
+    let mut mac = hmac(key);
+    mac.update(match side {
+        Side::Client => b"lumen client commitment".as_slice(),
+        Side::Controller => b"lumen controller commitment".as_slice(),
+    });
+    mac.update(&[round as u8, code.0[round]]);
+    mac.update(nonce);
+    mac
+}
+
+fn hmac(key: &[u8; 32]) -> Hmac<Sha256> {
+    // HMAC takes keys of any length
+    <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap()
+}
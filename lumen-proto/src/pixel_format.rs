@@ -0,0 +1,43 @@
+use crate::{
+    bytestreamreader::{ByteStreamReader, MessageDeserializer},
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    DeserializationError, DeserializationResult, SerializationResult,
+};
+
+/// The kind of LEDs connected to the controller.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PixelFormat {
+    /// Three channels with 24 bits per pixel, e.g. WS2812B.
+    Rgb = 0,
+    /// Four channels with 32 bits per pixel, e.g. SK6812 RGBW.
+    Rgbw = 1,
+}
+
+impl TryFrom<u8> for PixelFormat {
+    type Error = ();
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            x if x == PixelFormat::Rgb as u8 => Ok(PixelFormat::Rgb),
+            x if x == PixelFormat::Rgbw as u8 => Ok(PixelFormat::Rgbw),
+            _ => Err(()),
+        }
+    }
+}
+
+impl MessageDeserializer for PixelFormat {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let format = reader.u8()?;
+        PixelFormat::try_from(format).map_err(|_| DeserializationError::InvalidValue)
+    }
+}
+
+impl MessageSerializer for PixelFormat {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u8(*self as u8)
+    }
+}
@@ -0,0 +1,326 @@
+//! Datagrams the controller sends back to the sender of a message.
+//!
+//! A reply starts with the same [`header`](crate::header) as a message, followed by the
+//! timestamp of the message it answers, the u16 reply id and the payload of the reply.
+
+use arrayvec::ArrayVec;
+
+use crate::{
+    bytestreamreader::{ByteStreamReader, MessageDeserializer},
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    effect::{EffectId, EffectParams},
+    header::{read_header, write_header},
+    pairing::{Commitment, PairingNonce, PublicKey, PAIRED_CLIENTS_MAX},
+    DeserializationError, DeserializationResult, SerializationResult, Timestamp,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerReply {
+    /// Timestamp of the message this reply answers.
+    pub in_reply_to: Timestamp,
+    pub kind: ReplyKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplyKind {
+    /// The running effect and its current parameters, `None` if no effect runs.
+    EffectParams {
+        effect: Option<(EffectId, EffectParams)>,
+    },
+    /// Answers `PairRequest` with the X25519 public key of the controller.
+    PairResponse { public_key: PublicKey },
+    /// Answers `PairCommit` with the commitment of the controller to the same color.
+    PairCommitted { commitment: Commitment },
+    /// Answers `PairReveal` with the nonce of the commitment of the controller. After the last
+    /// color it carries the id of the stored key, the client signs its datagrams with it.
+    PairRevealed {
+        nonce: PairingNonce,
+        key_id: Option<u8>,
+    },
+    /// The ids of the keys of the paired clients.
+    PairedClients {
+        key_ids: ArrayVec<u8, PAIRED_CLIENTS_MAX>,
+    },
+    /// Answers `Ping`.
+    Pong,
+    /// Answers `GetStatus`.
+    Status(ControllerStatus),
+    /// The control message was handled, sent to clients that enabled it with `SetAcks`. Always
+    /// answers `RevokeClient`.
+    Ack,
+    /// The control message was discarded, sent to clients that enabled it with `SetAcks`. Always
+    /// answers `RevokeClient`.
+    Nack { reason: NackReason },
+    /// The datagram is authentic, but the run of nonces it belongs to ended, the client has to
+    /// start a new one. Sent in the envelope of the datagram, see [`crate::auth`].
+    RunClosed,
+}
+
+/// The state of the controller, see `GetStatus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControllerStatus {
+    /// Major, minor and patch version of the firmware.
+    pub firmware_version: [u8; 3],
+    /// Number of LEDs of the frame on the strip.
+    pub led_count: u16,
+    /// The session that drives the strip, `None` if no client does.
+    pub active_session: Option<ActiveSession>,
+    /// Number of open sessions, including the active one.
+    pub session_count: u8,
+    pub uptime_millis: u64,
+    /// RAM that is neither taken by statics nor by the stack, in bytes.
+    pub free_memory: u32,
+}
+
+/// The session that drives the strip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveSession {
+    /// Id of the client if it opened the session with `OpenSession`.
+    pub client_id: Option<u32>,
+    pub priority: u8,
+    /// Set if the client that asked for the status drives the strip.
+    pub is_sender: bool,
+}
+
+/// Reasons why the controller discarded a control message.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NackReason {
+    /// The client already sent a newer message of the same kind.
+    Outdated = 0,
+    /// The message was sent before the client restarted.
+    PreviousEpoch = 1,
+    /// Another client drives the strip. Settings of the session are kept until the client does.
+    NotDriver = 2,
+    /// A value of the message is out of range.
+    InvalidValue = 3,
+    /// The message changes the effect, but no effect runs.
+    NoEffect = 4,
+    /// The key of the datagram doesn't allow the message, e.g. revoking the key of another client.
+    NotPermitted = 5,
+}
+
+impl NackReason {
+    pub const ALL: [NackReason; 6] = [
+        NackReason::Outdated,
+        NackReason::PreviousEpoch,
+        NackReason::NotDriver,
+        NackReason::InvalidValue,
+        NackReason::NoEffect,
+        NackReason::NotPermitted,
+    ];
+}
+
+impl TryFrom<u8> for NackReason {
+    type Error = ();
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        NackReason::ALL
+            .into_iter()
+            .find(|reason| *reason as u8 == v)
+            .ok_or(())
+    }
+}
+
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReplyId {
+    EffectParams = 0,
+    PairResponse = 1,
+    PairCommitted = 2,
+    PairRevealed = 3,
+    PairedClients = 4,
+    Pong = 5,
+    Status = 6,
+    Ack = 7,
+    Nack = 8,
+    RunClosed = 9,
+}
+
+impl From<&ReplyKind> for ReplyId {
+    fn from(kind: &ReplyKind) -> Self {
+        match kind {
+            ReplyKind::EffectParams { .. } => ReplyId::EffectParams,
+            ReplyKind::PairResponse { .. } => ReplyId::PairResponse,
+            ReplyKind::PairCommitted { .. } => ReplyId::PairCommitted,
+            ReplyKind::PairRevealed { .. } => ReplyId::PairRevealed,
+            ReplyKind::PairedClients { .. } => ReplyId::PairedClients,
+            ReplyKind::Pong => ReplyId::Pong,
+            ReplyKind::Status(_) => ReplyId::Status,
+            ReplyKind::Ack => ReplyId::Ack,
+            ReplyKind::Nack { .. } => ReplyId::Nack,
+            ReplyKind::RunClosed => ReplyId::RunClosed,
+        }
+    }
+}
+
+impl ControllerReply {
+    /// Serializes the reply into `buffer` and returns the number of bytes written.
+    pub fn encode(&self, buffer: &mut [u8]) -> SerializationResult<usize> {
+        let mut writer = ByteStreamWriter::new(buffer);
+        self.serialize_into(&mut writer)?;
+        Ok(writer.written())
+    }
+
+    /// Deserializes a whole datagram into a reply.
+    pub fn decode(datagram: &[u8]) -> DeserializationResult<Self> {
+        Self::deserialize_from(&mut ByteStreamReader::new(datagram))
+    }
+}
+
+impl MessageDeserializer for ControllerReply {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let version = read_header(reader)?;
+        let in_reply_to = Timestamp::new(reader.u64()?);
+        let kind = match reader.u16()? {
+            x if x == ReplyId::EffectParams as u16 => {
+                let effect = match reader.bool()? {
+                    false => None,
+                    true => Some((
+                        EffectId::deserialize_from(reader)?,
+                        EffectParams::deserialize_from(reader)?,
+                    )),
+                };
+                ReplyKind::EffectParams { effect }
+            }
+            x if x == ReplyId::PairResponse as u16 => ReplyKind::PairResponse {
+                public_key: reader.array()?,
+            },
+            x if x == ReplyId::PairCommitted as u16 => ReplyKind::PairCommitted {
+                commitment: reader.array()?,
+            },
+            x if x == ReplyId::PairRevealed as u16 => ReplyKind::PairRevealed {
+                nonce: reader.array()?,
+                key_id: match reader.bool()? {
+                    false => None,
+                    true => Some(reader.u8()?),
+                },
+            },
+            x if x == ReplyId::PairedClients as u16 => {
+                let count = reader.u8()?;
+                if count as usize > PAIRED_CLIENTS_MAX {
+                    return Err(DeserializationError::InvalidValue);
+                }
+                let mut key_ids = ArrayVec::new();
+                for _ in 0..count {
+                    key_ids.push(reader.u8()?);
+                }
+                ReplyKind::PairedClients { key_ids }
+            }
+            x if x == ReplyId::Pong as u16 => ReplyKind::Pong,
+            x if x == ReplyId::Status as u16 => {
+                ReplyKind::Status(ControllerStatus::deserialize_from(reader)?)
+            }
+            x if x == ReplyId::Ack as u16 => ReplyKind::Ack,
+            x if x == ReplyId::Nack as u16 => ReplyKind::Nack {
+                reason: NackReason::try_from(reader.u8()?)
+                    .map_err(|_| DeserializationError::InvalidValue)?,
+            },
+            x if x == ReplyId::RunClosed as u16 => ReplyKind::RunClosed,
+            id => return Err(DeserializationError::UnknownMessageId(id)),
+        };
+
+        let trailing = reader.remaining();
+        if trailing != 0 && !version.is_newer_minor() {
+            return Err(DeserializationError::TrailingBytes(trailing));
+        }
+
+        Ok(ControllerReply { in_reply_to, kind })
+    }
+}
+
+impl MessageSerializer for ControllerReply {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        write_header(writer)?;
+        writer.u64(self.in_reply_to.get())?;
+        writer.u16(ReplyId::from(&self.kind) as u16)?;
+        match &self.kind {
+            ReplyKind::EffectParams { effect } => match effect {
+                None => writer.bool(false)?,
+                Some((id, params)) => {
+                    writer.bool(true)?;
+                    id.serialize_into(writer)?;
+                    params.serialize_into(writer)?;
+                }
+            },
+            ReplyKind::PairResponse { public_key } => writer.bytes(public_key)?,
+            ReplyKind::PairCommitted { commitment } => writer.bytes(commitment)?,
+            ReplyKind::PairRevealed { nonce, key_id } => {
+                writer.bytes(nonce)?;
+                match key_id {
+                    None => writer.bool(false)?,
+                    Some(key_id) => {
+                        writer.bool(true)?;
+                        writer.u8(*key_id)?;
+                    }
+                }
+            }
+            ReplyKind::PairedClients { key_ids } => {
+                writer.u8(key_ids.len() as u8)?;
+                writer.bytes(key_ids)?;
+            }
+            ReplyKind::Pong | ReplyKind::Ack | ReplyKind::RunClosed => {}
+            ReplyKind::Status(status) => status.serialize_into(writer)?,
+            ReplyKind::Nack { reason } => writer.u8(*reason as u8)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl MessageDeserializer for ControllerStatus {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let firmware_version = reader.array()?;
+        let led_count = reader.u16()?;
+        let active_session = match reader.bool()? {
+            false => None,
+            true => Some(ActiveSession {
+                client_id: match reader.bool()? {
+                    false => None,
+                    true => Some(reader.u32()?),
+                },
+                priority: reader.u8()?,
+                is_sender: reader.bool()?,
+            }),
+        };
+        Ok(ControllerStatus {
+            firmware_version,
+            led_count,
+            active_session,
+            session_count: reader.u8()?,
+            uptime_millis: reader.u64()?,
+            free_memory: reader.u32()?,
+        })
+    }
+}
+
+impl MessageSerializer for ControllerStatus {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.bytes(&self.firmware_version)?;
+        writer.u16(self.led_count)?;
+        match &self.active_session {
+            None => writer.bool(false)?,
+            Some(session) => {
+                writer.bool(true)?;
+                match session.client_id {
+                    None => writer.bool(false)?,
+                    Some(client_id) => {
+                        writer.bool(true)?;
+                        writer.u32(client_id)?;
+                    }
+                }
+                writer.u8(session.priority)?;
+                writer.bool(session.is_sender)?;
+            }
+        }
+        writer.u8(self.session_count)?;
+        writer.u64(self.uptime_millis)?;
+        writer.u32(self.free_memory)
+    }
+}
@@ -0,0 +1,45 @@
+use core::ops::BitXor;
+
+use super::{
+    bytestreamreader::{ByteStreamReader, MessageDeserializer},
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    DeserializationResult, SerializationResult,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rgb8 {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl MessageDeserializer for Rgb8 {
+    type Result = DeserializationResult<Rgb8>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let r = reader.u8()?;
+        let g = reader.u8()?;
+        let b = reader.u8()?;
+        Ok(Rgb8 { r, g, b })
+    }
+}
+
+impl MessageSerializer for Rgb8 {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u8(self.r)?;
+        writer.u8(self.g)?;
+        writer.u8(self.b)
+    }
+}
+
+impl BitXor for Rgb8 {
+    type Output = Rgb8;
+
+    fn bitxor(self, rhs: Rgb8) -> Rgb8 {
+        Rgb8 {
+            r: self.r ^ rhs.r,
+            g: self.g ^ rhs.g,
+            b: self.b ^ rhs.b,
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use super::{
+    bytestreamreader::{ByteStreamReader, MessageDeserializer},
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    rgb8::Rgb8,
+    DeserializationResult, SerializationResult,
+};
+
+/// A pixel of an RGBW strip with a dedicated white LED, e.g. SK6812 RGBW.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rgbw8 {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub w: u8,
+}
+
+impl From<Rgb8> for Rgbw8 {
+    fn from(Rgb8 { r, g, b }: Rgb8) -> Self {
+        Rgbw8 { r, g, b, w: 0 }
+    }
+}
+
+impl MessageDeserializer for Rgbw8 {
+    type Result = DeserializationResult<Rgbw8>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let r = reader.u8()?;
+        let g = reader.u8()?;
+        let b = reader.u8()?;
+        let w = reader.u8()?;
+        Ok(Rgbw8 { r, g, b, w })
+    }
+}
+
+impl MessageSerializer for Rgbw8 {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u8(self.r)?;
+        writer.u8(self.g)?;
+        writer.u8(self.b)?;
+        writer.u8(self.w)
+    }
+}
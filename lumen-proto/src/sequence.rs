@@ -0,0 +1,45 @@
+use core::num::NonZeroU32;
+
+use crate::{
+    bytestreamreader::ByteStreamReader, bytestreamwriter::ByteStreamWriter, DeserializationResult,
+    SerializationResult,
+};
+
+/// Position of a message in the stream of its sender.
+///
+/// The sender picks a random epoch whenever it starts, so the controller can tell a restart
+/// from reordered datagrams. On the wire an epoch of 0 marks a message without a sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sequence {
+    pub epoch: NonZeroU32,
+    /// Incremented for every message, wrapping around after `u32::MAX`.
+    pub number: u32,
+}
+
+impl Sequence {
+    /// Returns true if `number` was sent after `last`. Numbers are compared with serial number
+    /// arithmetic (RFC 1982), so the order holds across the wraparound.
+    pub fn is_after(number: u32, last: u32) -> bool {
+        (number.wrapping_sub(last) as i32) > 0
+    }
+}
+
+/// Reads the sequence of a message, `None` for senders that don't number their messages.
+pub fn read_sequence(reader: &mut ByteStreamReader) -> DeserializationResult<Option<Sequence>> {
+    let epoch = reader.u32()?;
+    let number = reader.u32()?;
+    Ok(NonZeroU32::new(epoch).map(|epoch| Sequence { epoch, number }))
+}
+
+pub fn write_sequence(
+    writer: &mut ByteStreamWriter,
+    sequence: Option<Sequence>,
+) -> SerializationResult<()> {
+    let Sequence { epoch, number } = match sequence {
+        Some(sequence) => sequence,
+        None => return writer.u64(0),
+    };
+    writer.u32(epoch.get())?;
+    writer.u32(number)
+}
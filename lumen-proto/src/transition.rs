@@ -0,0 +1,69 @@
+use crate::{
+    bytestreamreader::{ByteStreamReader, MessageDeserializer},
+    bytestreamwriter::{ByteStreamWriter, MessageSerializer},
+    DeserializationError, DeserializationResult, SerializationResult,
+};
+
+/// How the progress of a transition is shaped over its duration.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Easing {
+    Linear = 0,
+    /// Starts slow and ends fast.
+    EaseIn = 1,
+    /// Starts fast and ends slow.
+    EaseOut = 2,
+    /// Starts and ends slow.
+    EaseInOut = 3,
+}
+
+impl Easing {
+    pub const ALL: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    /// Maps linear progress to eased progress, both in 0.16 fixed point where 65536 is done.
+    pub fn apply(self, progress: u32) -> u32 {
+        const ONE: u64 = 1 << 16;
+
+        let t = (progress as u64).min(ONE);
+        let eased = match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t / ONE,
+            Easing::EaseOut => ONE - (ONE - t) * (ONE - t) / ONE,
+            // Smoothstep, 3t² - 2t³
+            Easing::EaseInOut => t * t * (3 * ONE - 2 * t) / (ONE * ONE),
+        };
+        eased as u32
+    }
+}
+
+impl TryFrom<u8> for Easing {
+    type Error = ();
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        Easing::ALL
+            .into_iter()
+            .find(|easing| *easing as u8 == v)
+            .ok_or(())
+    }
+}
+
+impl MessageDeserializer for Easing {
+    type Result = DeserializationResult<Self>;
+
+    fn deserialize_from(reader: &mut ByteStreamReader) -> Self::Result {
+        let easing = reader.u8()?;
+        Easing::try_from(easing).map_err(|_| DeserializationError::InvalidValue)
+    }
+}
+
+impl MessageSerializer for Easing {
+    fn serialize_into(&self, writer: &mut ByteStreamWriter) -> SerializationResult<()> {
+        writer.u8(*self as u8)
+    }
+}
@@ -0,0 +1,305 @@
+use lumen_proto::auth::{
+    encrypt, seal_reply, sign, Envelope, Key, Keys, NonceWindow, Replay, ReplayGuard,
+    ENVELOPE_OVERHEAD, NONCE_COUNTER_BITS, NONCE_WINDOW, RUN_MAX,
+};
+use lumen_proto::bytestreamwriter::MessageSerializer;
+use lumen_proto::message_kind::MessageKind;
+use lumen_proto::reply::{ControllerReply, ReplyKind};
+use lumen_proto::{ControllerMessage, Timestamp};
+
+const KEY: Key = [7; 32];
+
+fn keep_alive() -> ControllerMessage {
+    ControllerMessage {
+        timestamp: Timestamp::new(42),
+        sequence: None,
+        kind: MessageKind::KeepAlive { millis: 1000 },
+    }
+}
+
+fn signed<M: MessageSerializer>(message: &M, key: &Key, nonce: u64) -> Vec<u8> {
+    let mut buffer = [0; 128];
+    let len = sign(message, key, 3, nonce, &mut buffer).unwrap();
+    buffer[..len].to_vec()
+}
+
+fn encrypted<M: MessageSerializer>(message: &M, key: &Key, nonce: u64) -> Vec<u8> {
+    let mut buffer = [0; 128];
+    let len = encrypt(message, key, 3, nonce, &mut buffer).unwrap();
+    buffer[..len].to_vec()
+}
+
+fn plain(message: &ControllerMessage) -> Vec<u8> {
+    let mut buffer = [0; 128];
+    let len = message.encode(&mut buffer).unwrap();
+    buffer[..len].to_vec()
+}
+
+#[test]
+fn signed_datagrams_wrap_the_plain_one() {
+    let message = keep_alive();
+    let mut datagram = signed(&message, &KEY, 0x1234);
+    assert_eq!(datagram.len(), plain(&message).len() + ENVELOPE_OVERHEAD);
+
+    let Ok(Envelope::Signed(signed)) = Envelope::open(&mut datagram) else {
+        panic!("datagram is not signed");
+    };
+    assert_eq!(signed.key_id, 3);
+    assert_eq!(signed.nonce, 0x1234);
+    assert!(signed.verify(&KEY));
+    assert_eq!(ControllerMessage::decode(signed.datagram), Ok(message));
+}
+
+#[test]
+fn rejects_tampered_datagrams_and_other_keys() {
+    let mut datagram = signed(&keep_alive(), &KEY, 1);
+
+    let Ok(Envelope::Signed(signed)) = Envelope::open(&mut datagram) else {
+        panic!("datagram is not signed");
+    };
+    assert!(!signed.verify(&[8; 32]));
+
+    // Flipping any bit, including those of the nonce and the tag, invalidates the datagram
+    for i in 4..datagram.len() {
+        let mut tampered = datagram.clone();
+        tampered[i] ^= 0x10;
+        let Ok(Envelope::Signed(signed)) = Envelope::open(&mut tampered) else {
+            panic!("datagram is not signed");
+        };
+        assert!(!signed.verify(&KEY), "byte {i} is not covered by the tag");
+    }
+}
+
+#[test]
+fn passes_plain_datagrams_through() {
+    let expected = plain(&keep_alive());
+    let mut datagram = expected.clone();
+
+    assert_eq!(
+        Envelope::open(&mut datagram),
+        Ok(Envelope::Plain(&expected))
+    );
+}
+
+#[test]
+fn rejects_truncated_envelopes() {
+    let mut datagram = signed(&keep_alive(), &KEY, 1);
+    assert!(Envelope::open(&mut datagram[..ENVELOPE_OVERHEAD - 1]).is_err());
+
+    let mut datagram = encrypted(&keep_alive(), &KEY, 1);
+    assert!(Envelope::open(&mut datagram[..ENVELOPE_OVERHEAD - 1]).is_err());
+}
+
+#[test]
+fn encrypted_datagrams_hide_the_plain_one() {
+    let message = keep_alive();
+    let keys = Keys::new(&KEY);
+    let mut datagram = encrypted(&message, &keys.encryption, 0x1234);
+    let plain = plain(&message);
+    assert_eq!(datagram.len(), plain.len() + ENVELOPE_OVERHEAD);
+    assert!(!datagram.windows(plain.len()).any(|window| window == plain));
+
+    let Ok(Envelope::Encrypted(encrypted)) = Envelope::open(&mut datagram) else {
+        panic!("datagram is not encrypted");
+    };
+    assert_eq!(encrypted.key_id, 3);
+    assert_eq!(encrypted.nonce, 0x1234);
+    let decrypted = encrypted.decrypt(&keys.encryption).unwrap();
+    assert_eq!(ControllerMessage::decode(decrypted), Ok(message));
+}
+
+#[test]
+fn rejects_tampered_encrypted_datagrams_and_other_keys() {
+    let keys = Keys::new(&KEY);
+    let datagram = encrypted(&keep_alive(), &keys.encryption, 1);
+
+    // The signing key must not decrypt the datagram
+    let mut copy = datagram.clone();
+    let Ok(Envelope::Encrypted(encrypted)) = Envelope::open(&mut copy) else {
+        panic!("datagram is not encrypted");
+    };
+    assert!(encrypted.decrypt(&keys.signing).is_none());
+
+    // Flipping any bit, including those of the nonce and the tag, invalidates the datagram
+    for i in 4..datagram.len() {
+        let mut tampered = datagram.clone();
+        tampered[i] ^= 0x10;
+        let Ok(Envelope::Encrypted(encrypted)) = Envelope::open(&mut tampered) else {
+            panic!("datagram is not encrypted");
+        };
+        assert!(
+            encrypted.decrypt(&keys.encryption).is_none(),
+            "byte {i} is not authenticated"
+        );
+    }
+}
+
+#[test]
+fn replies_are_sealed_like_requests() {
+    let reply = ControllerReply {
+        in_reply_to: Timestamp::new(42),
+        kind: ReplyKind::Pong,
+    };
+    let keys = Keys::new(&KEY);
+
+    let mut datagram = signed(&reply, &keys.reply_signing, 0x1234);
+    let Ok(Envelope::Signed(signed)) = Envelope::open(&mut datagram) else {
+        panic!("reply is not signed");
+    };
+    assert_eq!(signed.nonce, 0x1234);
+    assert!(signed.verify(&keys.reply_signing));
+    // A reply sent back to the controller doesn't pass as a request
+    assert!(!signed.verify(&keys.signing));
+    assert_eq!(ControllerReply::decode(signed.datagram), Ok(reply.clone()));
+
+    let mut datagram = encrypted(&reply, &keys.reply_encryption, 0x1234);
+    let Ok(Envelope::Encrypted(encrypted)) = Envelope::open(&mut datagram) else {
+        panic!("reply is not encrypted");
+    };
+    assert_eq!(encrypted.nonce, 0x1234);
+    let plain = encrypted.decrypt(&keys.reply_encryption).unwrap();
+    assert_eq!(ControllerReply::decode(plain), Ok(reply));
+}
+
+#[test]
+fn run_closed_replies_to_replays_have_a_key_of_their_own() {
+    let keys = Keys::new(&KEY);
+    let pong = ControllerReply {
+        in_reply_to: Timestamp::new(42),
+        kind: ReplyKind::Pong,
+    };
+    let run_closed = ControllerReply {
+        in_reply_to: Timestamp::new(42),
+        kind: ReplyKind::RunClosed,
+    };
+    let mut buffer = [0; 128];
+
+    // The first reply to an encrypted request
+    let len = seal_reply(&pong, &keys, 3, 0x1234, true, &mut buffer).unwrap();
+    let mut first = buffer[..len].to_vec();
+    let Ok(Envelope::Encrypted(encrypted)) = Envelope::open(&mut first) else {
+        panic!("reply is not encrypted");
+    };
+    assert_eq!(
+        ControllerReply::decode(encrypted.decrypt(&keys.reply_encryption).unwrap()),
+        Ok(pong)
+    );
+
+    // Every replay of the request is answered with the same RunClosed reply, signed under another key
+    for _ in 0..2 {
+        let len = seal_reply(&run_closed, &keys, 3, 0x1234, true, &mut buffer).unwrap();
+        let mut replayed = buffer[..len].to_vec();
+        let Ok(Envelope::Signed(signed)) = Envelope::open(&mut replayed) else {
+            panic!("RunClosed reply is not signed");
+        };
+        assert_eq!(signed.nonce, 0x1234);
+        assert!(signed.verify(&keys.run_closed));
+        assert!(!signed.verify(&keys.reply_signing));
+        assert_eq!(
+            ControllerReply::decode(signed.datagram),
+            Ok(run_closed.clone())
+        );
+    }
+}
+
+#[test]
+fn derives_distinct_keys() {
+    let keys = Keys::new(&KEY);
+
+    assert_eq!(keys.signing, KEY);
+    assert_ne!(keys.encryption, KEY);
+    assert_ne!(keys.reply_signing, KEY);
+    assert_ne!(keys.reply_encryption, keys.encryption);
+    assert_ne!(keys.reply_signing, keys.reply_encryption);
+    assert_ne!(keys.run_closed, keys.reply_signing);
+    assert_ne!(keys.run_closed, keys.reply_encryption);
+    assert_ne!(Keys::new(&[8; 32]).encryption, keys.encryption);
+}
+
+#[test]
+fn accepts_every_nonce_once() {
+    let mut window = NonceWindow::default();
+
+    assert!(window.accept(1));
+    assert!(!window.accept(1));
+    assert!(window.accept(5));
+    // Reordered datagrams inside the window are still accepted
+    assert!(window.accept(3));
+    assert!(window.accept(2));
+    assert!(!window.accept(3));
+    assert_eq!(window.highest(), 5);
+}
+
+#[test]
+fn rejects_nonces_below_the_window() {
+    let mut window = NonceWindow::default();
+
+    assert!(window.accept(1000));
+    assert!(window.accept(1000 - NONCE_WINDOW + 1));
+    assert!(!window.accept(1000 - NONCE_WINDOW));
+    // A jump past the window forgets the old nonces
+    assert!(window.accept(1000 + 10 * NONCE_WINDOW));
+    assert!(!window.accept(1001));
+}
+
+#[test]
+fn rejects_nonces_up_to_the_floor() {
+    let mut window = NonceWindow::new(100);
+
+    assert!(!window.accept(0));
+    assert!(!window.accept(99));
+    assert!(!window.accept(100));
+    assert!(window.accept(101));
+}
+
+/// The nonce of datagram `count` of client run `run`.
+fn run_nonce(run: u64, count: u64) -> u64 {
+    (run << NONCE_COUNTER_BITS) | count
+}
+
+#[test]
+fn runs_sharing_a_key_have_windows_of_their_own() {
+    let mut guard = ReplayGuard::default();
+
+    // A client far ahead doesn't push the other one out of the window
+    assert_eq!(guard.accept(run_nonce(7, 1)), Ok(()));
+    assert_eq!(guard.accept(run_nonce(9, 1000)), Ok(()));
+    assert_eq!(guard.accept(run_nonce(7, 2)), Ok(()));
+    assert_eq!(guard.accept(run_nonce(9, 1001)), Ok(()));
+
+    assert_eq!(guard.accept(run_nonce(7, 2)), Err(Replay::Seen));
+    assert_eq!(guard.accept(run_nonce(9, 1000)), Err(Replay::Seen));
+    assert_eq!(guard.accept(run_nonce(9, 900)), Err(Replay::Seen));
+}
+
+#[test]
+fn newer_runs_close_the_oldest_one() {
+    let mut guard = ReplayGuard::default();
+    for run in 1..=RUN_MAX as u64 {
+        assert_eq!(guard.accept(run_nonce(run, 1)), Ok(()));
+    }
+
+    assert_eq!(guard.accept(run_nonce(10, 1)), Ok(()));
+    assert_eq!(guard.accept(run_nonce(1, 2)), Err(Replay::RunClosed));
+    assert_eq!(guard.accept(run_nonce(2, 2)), Ok(()));
+
+    // A run older than every tracked one would close itself
+    assert_eq!(guard.accept(run_nonce(0, 1)), Err(Replay::RunClosed));
+}
+
+#[test]
+fn mark_closes_every_run_seen_before_a_reboot() {
+    let mut guard = ReplayGuard::default();
+    assert_eq!(guard.mark(), 0);
+    guard.accept(run_nonce(7, 5)).unwrap();
+    guard.accept(run_nonce(5, 100)).unwrap();
+    assert_eq!(guard.mark(), run_nonce(8, 0));
+
+    // Nonces of the current runs can't be replayed after the reboot, not even unseen ones
+    let mut rebooted = ReplayGuard::new(guard.mark());
+    assert_eq!(rebooted.accept(run_nonce(7, 5)), Err(Replay::RunClosed));
+    assert_eq!(rebooted.accept(run_nonce(7, 6)), Err(Replay::RunClosed));
+    assert_eq!(rebooted.accept(run_nonce(5, 101)), Err(Replay::RunClosed));
+    assert_eq!(rebooted.accept(run_nonce(8, 1)), Ok(()));
+    assert_eq!(rebooted.mark(), run_nonce(9, 0));
+}
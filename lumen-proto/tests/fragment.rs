@@ -0,0 +1,150 @@
+use lumen_proto::fragment::{
+    split_frame, split_frame_rgbw, FragmentOutcome, FrameAssembler, LedFragment,
+};
+use lumen_proto::message_kind::MessageKind;
+use lumen_proto::rgb8::Rgb8;
+use lumen_proto::rgbw8::Rgbw8;
+use lumen_proto::{ControllerMessage, Timestamp};
+
+const TIMEOUT_MS: u64 = 100;
+
+fn frame(len: usize, seed: u8) -> Vec<Rgb8> {
+    (0..len)
+        .map(|i| Rgb8 {
+            r: i as u8,
+            g: (i >> 8) as u8,
+            b: seed,
+        })
+        .collect()
+}
+
+#[test]
+fn fragments_fit_into_client_datagrams() {
+    let leds = frame(1000, 0);
+    for fragment in split_frame(1, &leds) {
+        let message = ControllerMessage {
+            timestamp: Timestamp::new(0),
+            sequence: None,
+            kind: MessageKind::LedFragment(fragment),
+        };
+        let mut buffer = [0; 1024];
+        let written = message.encode(&mut buffer).unwrap();
+        assert_eq!(ControllerMessage::decode(&buffer[..written]), Ok(message));
+    }
+}
+
+#[test]
+fn rgbw_fragments_fit_into_client_datagrams() {
+    let leds: Vec<Rgbw8> = frame(1000, 0).into_iter().map(Rgbw8::from).collect();
+    for fragment in split_frame_rgbw(1, &leds) {
+        let message = ControllerMessage {
+            timestamp: Timestamp::new(0),
+            sequence: None,
+            kind: MessageKind::LedFragmentRgbw(fragment),
+        };
+        let mut buffer = [0; 1024];
+        let written = message.encode(&mut buffer).unwrap();
+        assert_eq!(ControllerMessage::decode(&buffer[..written]), Ok(message));
+    }
+}
+
+#[test]
+fn reassembles_fragments_in_any_order() {
+    let leds = frame(1000, 0);
+    let fragments: Vec<LedFragment> = split_frame(1, &leds).collect();
+    assert_eq!(fragments.len(), 4);
+
+    let mut assembler = FrameAssembler::new();
+    for fragment in fragments.iter().rev().skip(1) {
+        assert_eq!(
+            assembler.push(fragment, 0, TIMEOUT_MS),
+            FragmentOutcome::Pending
+        );
+    }
+
+    match assembler.push(&fragments[3], 0, TIMEOUT_MS) {
+        FragmentOutcome::Complete(frame) => assert_eq!(frame.as_slice(), leds.as_slice()),
+        outcome => panic!("unexpected outcome {outcome:?}"),
+    }
+}
+
+#[test]
+fn newer_frame_discards_incomplete_one() {
+    let old: Vec<LedFragment> = split_frame(u16::MAX, &frame(600, 1)).collect();
+    let new: Vec<LedFragment> = split_frame(0, &frame(600, 2)).collect();
+
+    let mut assembler = FrameAssembler::new();
+    assert_eq!(
+        assembler.push(&old[0], 0, TIMEOUT_MS),
+        FragmentOutcome::Pending
+    );
+    assert_eq!(
+        assembler.push(&new[0], 0, TIMEOUT_MS),
+        FragmentOutcome::Pending
+    );
+    assert_eq!(assembler.discarded, 1);
+
+    // The old frame id is older even though the id wrapped around
+    assert_eq!(
+        assembler.push(&old[1], 0, TIMEOUT_MS),
+        FragmentOutcome::Stale
+    );
+    assert!(matches!(
+        assembler.push(&new[1], 0, TIMEOUT_MS),
+        FragmentOutcome::Complete(_)
+    ));
+}
+
+#[test]
+fn discards_incomplete_frame_after_timeout() {
+    let fragments: Vec<LedFragment> = split_frame(1, &frame(600, 0)).collect();
+
+    let mut assembler = FrameAssembler::new();
+    assert_eq!(
+        assembler.push(&fragments[0], 0, TIMEOUT_MS),
+        FragmentOutcome::Pending
+    );
+    assert_eq!(
+        assembler.push(&fragments[1], TIMEOUT_MS + 1, TIMEOUT_MS),
+        FragmentOutcome::Pending
+    );
+    assert_eq!(assembler.discarded, 1);
+}
+
+#[test]
+fn rejects_inconsistent_fragments() {
+    let mut fragments: Vec<LedFragment> = split_frame(1, &frame(600, 0)).collect();
+    fragments[1].led_count = 599;
+    fragments[1].values.pop();
+
+    let mut assembler = FrameAssembler::new();
+    assert_eq!(
+        assembler.push(&fragments[0], 0, TIMEOUT_MS),
+        FragmentOutcome::Pending
+    );
+    assert_eq!(
+        assembler.push(&fragments[1], 0, TIMEOUT_MS),
+        FragmentOutcome::Inconsistent
+    );
+}
+
+#[test]
+fn reassembles_rgbw_frame_up_to_led_max() {
+    let leds: Vec<Rgbw8> = frame(lumen_proto::LED_MAX, 3)
+        .into_iter()
+        .map(|rgb| Rgbw8 {
+            w: rgb.r,
+            ..rgb.into()
+        })
+        .collect();
+
+    let mut assembler = FrameAssembler::new();
+    let mut outcome = FragmentOutcome::Pending;
+    for fragment in split_frame_rgbw(1, &leds) {
+        outcome = assembler.push(&fragment, 0, TIMEOUT_MS);
+    }
+    match outcome {
+        FragmentOutcome::Complete(frame) => assert_eq!(frame.as_slice(), leds.as_slice()),
+        outcome => panic!("unexpected outcome {outcome:?}"),
+    }
+}
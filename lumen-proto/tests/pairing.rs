@@ -0,0 +1,86 @@
+use lumen_proto::pairing::{
+    commitment, verify_commitment, KeyExchange, PairingCode, Side, CODE_COLORS, CODE_LEN,
+};
+
+const CODE: [u8; CODE_LEN] = [0, 3, 7, 1, 5];
+
+#[test]
+fn both_sides_derive_the_same_key() {
+    let client = KeyExchange::new([1; 32]);
+    let controller = KeyExchange::new([2; 32]);
+
+    let client_key = client.client_key(&controller.public_key()).unwrap();
+    let controller_key = controller.controller_key(&client.public_key()).unwrap();
+    assert_eq!(client_key, controller_key);
+
+    let other = KeyExchange::new([3; 32]);
+    assert_ne!(other.client_key(&controller.public_key()), Some(client_key));
+}
+
+#[test]
+fn matches_the_x25519_test_vector() {
+    // RFC 7748, section 6.1
+    let alice = KeyExchange::new(hex(
+        "77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a",
+    ));
+    assert_eq!(
+        alice.public_key(),
+        hex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a")
+    );
+}
+
+#[test]
+fn rejects_public_keys_of_low_order() {
+    let controller = KeyExchange::new([2; 32]);
+
+    assert_eq!(controller.controller_key(&[0; 32]), None);
+    let mut one = [0; 32];
+    one[0] = 1;
+    assert_eq!(controller.controller_key(&one), None);
+}
+
+#[test]
+fn commitments_bind_the_color_and_the_side() {
+    let key = [7; 32];
+    let code = PairingCode::from_symbols(CODE).unwrap();
+    let nonce = [9; 16];
+    let client = commitment(&key, Side::Client, &code, 4, &nonce);
+    let verify =
+        |key, side, code, round, nonce| verify_commitment(key, side, code, round, nonce, &client);
+
+    assert!(verify(&key, Side::Client, &code, 4, &nonce));
+    assert!(!verify(&key, Side::Controller, &code, 4, &nonce));
+    assert!(!verify(&[8; 32], Side::Client, &code, 4, &nonce));
+    assert!(!verify(&key, Side::Client, &code, 4, &[10; 16]));
+    assert!(!verify(&key, Side::Client, &code, 3, &nonce));
+
+    let mut other_color = CODE;
+    other_color[4] = 6;
+    let other_color = PairingCode::from_symbols(other_color).unwrap();
+    assert!(!verify(&key, Side::Client, &other_color, 4, &nonce));
+
+    // Only the color of the round is committed to
+    let mut other_round = CODE;
+    other_round[0] = 6;
+    let other_round = PairingCode::from_symbols(other_round).unwrap();
+    assert!(verify(&key, Side::Client, &other_round, 4, &nonce));
+}
+
+#[test]
+fn codes_only_use_the_palette() {
+    assert_eq!(PairingCode::new(0).symbols(), [0; CODE_LEN]);
+    assert_eq!(PairingCode::new(u16::MAX).symbols(), [7; CODE_LEN]);
+    assert_eq!(PairingCode::new(0b101_001).symbols(), [1, 5, 0, 0, 0]);
+
+    let code = PairingCode::from_symbols(CODE).unwrap();
+    assert_eq!(code.colors()[2], CODE_COLORS[7]);
+    assert_eq!(PairingCode::from_symbols([0, 0, 8, 0, 0]), None);
+}
+
+fn hex(s: &str) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap();
+    }
+    bytes
+}
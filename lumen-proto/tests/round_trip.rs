@@ -0,0 +1,600 @@
+use arrayvec::ArrayVec;
+use core::num::NonZeroU32;
+use lumen_proto::color_channel::ColorChannel;
+use lumen_proto::color_order::ColorOrder;
+use lumen_proto::compression::{apply_frame_delta, frame_delta};
+use lumen_proto::effect::{EffectId, EffectParam, EffectParams};
+use lumen_proto::header::{ProtocolVersion, MAGIC};
+use lumen_proto::idle::{IdleAction, IdleBehavior};
+use lumen_proto::message_kind::MessageKind;
+use lumen_proto::pixel_format::PixelFormat;
+use lumen_proto::reply::{ActiveSession, ControllerReply, ControllerStatus, NackReason, ReplyKind};
+use lumen_proto::rgb8::Rgb8;
+use lumen_proto::rgbw8::Rgbw8;
+use lumen_proto::sequence::Sequence;
+use lumen_proto::transition::Easing;
+use lumen_proto::{
+    ControllerMessage, DeserializationError, SerializationError, Timestamp, LED_MAX,
+};
+
+fn round_trip(message: &ControllerMessage) -> ControllerMessage {
+    let mut buffer = [0; 8192];
+    let written = message.encode(&mut buffer).unwrap();
+    ControllerMessage::decode(&buffer[..written]).unwrap()
+}
+
+/// Builds a datagram by hand: header with the given version, zero timestamp, no sequence,
+/// then `body`.
+fn raw_datagram(version: ProtocolVersion, body: &[u8]) -> Vec<u8> {
+    let mut datagram = MAGIC.to_vec();
+    datagram.extend([version.major, version.minor]);
+    datagram.extend(0u64.to_le_bytes());
+    if version.minor >= 16 {
+        datagram.extend(0u64.to_le_bytes());
+    }
+    datagram.extend(body);
+    datagram
+}
+
+fn led_state(count: usize) -> MessageKind {
+    let led_values = (0..count)
+        .map(|i| Rgb8 {
+            r: i as u8,
+            g: (i >> 8) as u8,
+            b: 0xAA,
+        })
+        .collect::<ArrayVec<_, LED_MAX>>();
+    MessageKind::LedState {
+        led_values,
+        transition: false,
+    }
+}
+
+#[test]
+fn round_trips_every_message_kind() {
+    let kinds = [
+        MessageKind::Empty,
+        MessageKind::KeepAlive { millis: 1000 },
+        led_state(0),
+        led_state(3),
+        led_state(LED_MAX),
+        MessageKind::LedState {
+            led_values: ArrayVec::from_iter([Rgb8 { r: 4, g: 5, b: 6 }; 3]),
+            transition: true,
+        },
+        MessageKind::LedRange {
+            offset: 10,
+            values: ArrayVec::from_iter([Rgb8 { r: 1, g: 2, b: 3 }; 5]),
+        },
+        MessageKind::LedStateRle {
+            sequence: 7,
+            led_values: ArrayVec::from_iter([Rgb8 { r: 9, g: 9, b: 9 }; LED_MAX]),
+        },
+        MessageKind::LedStateDelta {
+            sequence: 8,
+            base_sequence: 7,
+            delta: match led_state(LED_MAX) {
+                MessageKind::LedState { led_values, .. } => led_values,
+                _ => unreachable!(),
+            },
+        },
+        MessageKind::SetBrightness {
+            brightness: 128,
+            fade_millis: 500,
+        },
+        MessageKind::SetGamma {
+            red: 220,
+            green: 250,
+            blue: 100,
+        },
+        MessageKind::SetGammaTable {
+            channel: ColorChannel::Green,
+            table: core::array::from_fn(|i| 255 - i as u8),
+        },
+        MessageKind::SetWhiteBalance {
+            red: 255,
+            green: 230,
+            blue: 200,
+        },
+        MessageKind::SetColorMatrix { matrix: None },
+        MessageKind::SetColorMatrix {
+            matrix: Some([256, 0, 0, 0, 240, -16, 0, 8, 200]),
+        },
+        MessageKind::SetColorTemperature { kelvin: 5000 },
+        MessageKind::LedStateRgbw {
+            led_values: ArrayVec::from_iter(
+                [Rgbw8 {
+                    r: 1,
+                    g: 2,
+                    b: 3,
+                    w: 4,
+                }; 100],
+            ),
+        },
+        MessageKind::SetPixelFormat {
+            format: PixelFormat::Rgbw,
+            extract_white: true,
+        },
+        MessageKind::SetColorOrder {
+            order: ColorOrder::Brg,
+        },
+        MessageKind::SetCurrentLimit {
+            budget_ma: 2500,
+            channel_ua: [12_000, 12_000, 12_000, 18_000],
+            idle_ua: 1000,
+        },
+        MessageKind::RunEffect {
+            id: EffectId::Fire,
+            led_count: 120,
+            params: EffectParams {
+                speed: 32,
+                color: Rgb8 {
+                    r: 255,
+                    g: 80,
+                    b: 0,
+                },
+                ..EffectParams::default()
+            },
+        },
+        MessageKind::UpdateEffectParams {
+            params: ArrayVec::from_iter([
+                EffectParam::Speed(100),
+                EffectParam::Background(Rgb8 { r: 0, g: 0, b: 40 }),
+            ]),
+        },
+        MessageKind::GetEffectParams,
+        MessageKind::SetTransition {
+            duration_millis: 750,
+            easing: Easing::EaseInOut,
+        },
+        MessageKind::SetSmoothing { factor: 160 },
+        MessageKind::SetIdleBehavior(IdleBehavior::default()),
+        MessageKind::SetIdleBehavior(IdleBehavior {
+            grace_millis: 5000,
+            fade_millis: 3000,
+            action: IdleAction::RunEffect {
+                id: EffectId::Breathing,
+                led_count: 60,
+                params: EffectParams::default(),
+            },
+        }),
+        MessageKind::SaveScene,
+        MessageKind::OpenSession {
+            client_id: 0xdead_beef,
+            priority: 10,
+            lease_millis: 3000,
+        },
+        MessageKind::PairRequest {
+            public_key: [1; 32],
+        },
+        MessageKind::PairCommit {
+            commitment: [2; 32],
+        },
+        MessageKind::PairReveal { nonce: [3; 16] },
+        MessageKind::RevokeClient { key_id: 4 },
+        MessageKind::GetPairedClients,
+        MessageKind::Ping,
+        MessageKind::GetStatus,
+        MessageKind::SetAcks { enabled: true },
+    ];
+
+    for kind in kinds {
+        let message = ControllerMessage {
+            timestamp: Timestamp::new(1_700_000_000_000),
+            sequence: None,
+            kind,
+        };
+        assert_eq!(round_trip(&message), message);
+    }
+}
+
+#[test]
+fn encodes_keep_alive_byte_for_byte() {
+    let message = ControllerMessage {
+        timestamp: Timestamp::new(0x0102_0304_0506_0708),
+        sequence: None,
+        kind: MessageKind::KeepAlive { millis: 1000 },
+    };
+
+    let mut buffer = [0; 32];
+    let written = message.encode(&mut buffer).unwrap();
+    let ProtocolVersion { major, minor } = ProtocolVersion::CURRENT;
+
+    assert_eq!(
+        &buffer[..written],
+        &[
+            b'L', b'U', b'M', b'N', major, minor, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 0, 0, 0, 0, 0, 0,
+            1, 0, 0xE8, 0x03, 0, 0
+        ]
+    );
+}
+
+#[test]
+fn rejects_truncated_datagrams() {
+    let message = ControllerMessage {
+        timestamp: Timestamp::new(42),
+        sequence: None,
+        kind: led_state(10),
+    };
+    let mut buffer = [0; 64];
+    let written = message.encode(&mut buffer).unwrap();
+
+    for len in 0..written {
+        assert_eq!(
+            ControllerMessage::decode(&buffer[..len]),
+            Err(DeserializationError::Truncated)
+        );
+    }
+}
+
+#[test]
+fn rejects_trailing_bytes() {
+    let message = ControllerMessage {
+        timestamp: Timestamp::new(42),
+        sequence: None,
+        kind: MessageKind::Empty,
+    };
+    let mut buffer = [0; 32];
+    let written = message.encode(&mut buffer).unwrap();
+
+    assert_eq!(
+        ControllerMessage::decode(&buffer[..written + 2]),
+        Err(DeserializationError::TrailingBytes(2))
+    );
+}
+
+#[test]
+fn rejects_unknown_message_id() {
+    let datagram = raw_datagram(ProtocolVersion::CURRENT, &[0xFF, 0xFF]);
+
+    assert_eq!(
+        ControllerMessage::decode(&datagram),
+        Err(DeserializationError::UnknownMessageId(0xFFFF))
+    );
+}
+
+#[test]
+fn rejects_led_count_over_capacity() {
+    let count = LED_MAX as u16 + 1;
+    let mut body = 2u16.to_le_bytes().to_vec();
+    body.extend(count.to_le_bytes());
+    let datagram = raw_datagram(ProtocolVersion::CURRENT, &body);
+
+    assert_eq!(
+        ControllerMessage::decode(&datagram),
+        Err(DeserializationError::LedCountOverCapacity(count))
+    );
+}
+
+#[test]
+fn fails_to_encode_into_small_buffer() {
+    let message = ControllerMessage {
+        timestamp: Timestamp::new(42),
+        sequence: None,
+        kind: led_state(LED_MAX),
+    };
+    let mut buffer = [0; 1024];
+
+    assert_eq!(
+        message.encode(&mut buffer),
+        Err(SerializationError::BufferTooSmall)
+    );
+}
+
+#[test]
+fn rejects_foreign_traffic() {
+    let mut datagram = raw_datagram(ProtocolVersion::CURRENT, &[0, 0]);
+    datagram[0] = b'X';
+
+    assert_eq!(
+        ControllerMessage::decode(&datagram),
+        Err(DeserializationError::BadHeader)
+    );
+}
+
+#[test]
+fn rejects_other_major_versions() {
+    let version = ProtocolVersion {
+        major: ProtocolVersion::CURRENT.major + 1,
+        minor: 0,
+    };
+    let datagram = raw_datagram(version, &[0, 0]);
+
+    assert_eq!(
+        ControllerMessage::decode(&datagram),
+        Err(DeserializationError::UnsupportedVersion(version))
+    );
+}
+
+#[test]
+fn ignores_appended_fields_from_newer_minor_versions() {
+    let version = ProtocolVersion {
+        major: ProtocolVersion::CURRENT.major,
+        minor: ProtocolVersion::CURRENT.minor + 1,
+    };
+    let datagram = raw_datagram(version, &[1, 0, 0xE8, 0x03, 0, 0, 0xAB, 0xCD]);
+
+    assert_eq!(
+        ControllerMessage::decode(&datagram).map(|message| message.kind),
+        Ok(MessageKind::KeepAlive { millis: 1000 })
+    );
+}
+
+#[test]
+fn rejects_led_range_past_the_end() {
+    let mut body = 3u16.to_le_bytes().to_vec();
+    body.extend((LED_MAX as u16 - 1).to_le_bytes());
+    body.extend(2u16.to_le_bytes());
+    let datagram = raw_datagram(ProtocolVersion::CURRENT, &body);
+
+    assert_eq!(
+        ControllerMessage::decode(&datagram),
+        Err(DeserializationError::LedRangeOutOfBounds {
+            offset: LED_MAX as u16 - 1,
+            count: 2
+        })
+    );
+}
+
+#[test]
+fn rejects_messages_newer_than_the_sender_version() {
+    let version = ProtocolVersion { major: 1, minor: 0 };
+    let datagram = raw_datagram(version, &[3, 0, 0, 0, 0, 0]);
+
+    assert_eq!(
+        ControllerMessage::decode(&datagram),
+        Err(DeserializationError::UnsupportedMessage(3))
+    );
+}
+
+#[test]
+fn compresses_solid_frames() {
+    let message = ControllerMessage {
+        timestamp: Timestamp::new(42),
+        sequence: None,
+        kind: MessageKind::LedStateRle {
+            sequence: 0,
+            led_values: ArrayVec::from_iter([Rgb8 { r: 255, g: 0, b: 0 }; LED_MAX]),
+        },
+    };
+    let mut buffer = [0; 64];
+
+    let runs = LED_MAX.div_ceil(u8::MAX as usize);
+    assert_eq!(
+        message.encode(&mut buffer),
+        Ok(6 + 8 + 8 + 2 + 2 + 2 + runs * 4)
+    );
+}
+
+#[test]
+fn rejects_malformed_rle() {
+    let zero_run = [4, 0, 0, 0, 2, 0, 0, 1, 2, 3];
+    let overrun = [4, 0, 0, 0, 2, 0, 3, 1, 2, 3];
+
+    for body in [zero_run, overrun] {
+        assert_eq!(
+            ControllerMessage::decode(&raw_datagram(ProtocolVersion::CURRENT, &body)),
+            Err(DeserializationError::MalformedRle)
+        );
+    }
+}
+
+#[test]
+fn frame_delta_restores_next_frame() {
+    let base = [Rgb8 { r: 1, g: 2, b: 3 }, Rgb8 { r: 4, g: 5, b: 6 }];
+    let next = [Rgb8 { r: 1, g: 2, b: 3 }, Rgb8 { r: 7, g: 8, b: 9 }];
+
+    let delta = frame_delta(&base, &next).unwrap();
+    assert_eq!(delta[0], Rgb8::default());
+
+    let mut frame = base;
+    assert!(apply_frame_delta(&mut frame, &delta));
+    assert_eq!(frame, next);
+
+    assert!(frame_delta(&base, &next[..1]).is_none());
+    assert!(!apply_frame_delta(&mut frame[..1], &delta));
+}
+
+#[test]
+fn rejects_invalid_color_channel() {
+    let mut body = 9u16.to_le_bytes().to_vec();
+    body.push(7);
+    body.extend([0; 256]);
+    let datagram = raw_datagram(ProtocolVersion::CURRENT, &body);
+
+    assert_eq!(
+        ControllerMessage::decode(&datagram),
+        Err(DeserializationError::InvalidValue)
+    );
+}
+
+#[test]
+fn arranges_channels_in_color_order() {
+    assert_eq!(ColorOrder::Grb.arrange(1, 2, 3), [2, 1, 3]);
+    assert_eq!(ColorOrder::Bgr.arrange(1, 2, 3), [3, 2, 1]);
+
+    let body = [15u16.to_le_bytes().as_slice(), &[6]].concat();
+    let datagram = raw_datagram(ProtocolVersion::CURRENT, &body);
+    assert_eq!(
+        ControllerMessage::decode(&datagram),
+        Err(DeserializationError::InvalidValue)
+    );
+}
+
+#[test]
+fn applies_effect_param_updates() {
+    let mut params = EffectParams::default();
+    params.apply(EffectParam::Intensity(7));
+    params.apply(EffectParam::Color(Rgb8 { r: 1, g: 2, b: 3 }));
+
+    assert_eq!(params.intensity, 7);
+    assert_eq!(params.color, Rgb8 { r: 1, g: 2, b: 3 });
+    assert_eq!(params.speed, EffectParams::default().speed);
+}
+
+#[test]
+fn round_trips_replies() {
+    let replies = [
+        ReplyKind::EffectParams { effect: None },
+        ReplyKind::EffectParams {
+            effect: Some((EffectId::Twinkle, EffectParams::default())),
+        },
+        ReplyKind::PairResponse {
+            public_key: [1; 32],
+        },
+        ReplyKind::PairCommitted {
+            commitment: [2; 32],
+        },
+        ReplyKind::PairRevealed {
+            nonce: [3; 16],
+            key_id: None,
+        },
+        ReplyKind::PairRevealed {
+            nonce: [3; 16],
+            key_id: Some(4),
+        },
+        ReplyKind::PairedClients {
+            key_ids: ArrayVec::from_iter([1, 2, 5]),
+        },
+        ReplyKind::Pong,
+        ReplyKind::Status(ControllerStatus {
+            firmware_version: [0, 1, 0],
+            led_count: 300,
+            active_session: None,
+            session_count: 0,
+            uptime_millis: 1_234_567,
+            free_memory: 96_000,
+        }),
+        ReplyKind::Status(ControllerStatus {
+            firmware_version: [1, 2, 3],
+            led_count: 120,
+            active_session: Some(ActiveSession {
+                client_id: Some(0xdead_beef),
+                priority: 10,
+                is_sender: true,
+            }),
+            session_count: 2,
+            uptime_millis: 42,
+            free_memory: 0,
+        }),
+        ReplyKind::Ack,
+        ReplyKind::Nack {
+            reason: NackReason::NotDriver,
+        },
+        ReplyKind::RunClosed,
+    ];
+
+    for kind in replies {
+        let reply = ControllerReply {
+            in_reply_to: Timestamp::new(42),
+            kind,
+        };
+        let mut buffer = [0; 64];
+        let written = reply.encode(&mut buffer).unwrap();
+        assert_eq!(ControllerReply::decode(&buffer[..written]), Ok(reply));
+    }
+}
+
+#[test]
+fn rejects_unknown_nack_reason() {
+    let reply = ControllerReply {
+        in_reply_to: Timestamp::new(42),
+        kind: ReplyKind::Nack {
+            reason: NackReason::NoEffect,
+        },
+    };
+    let mut buffer = [0; 64];
+    let written = reply.encode(&mut buffer).unwrap();
+    buffer[written - 1] = NackReason::ALL.len() as u8;
+
+    assert_eq!(
+        ControllerReply::decode(&buffer[..written]),
+        Err(DeserializationError::InvalidValue)
+    );
+}
+
+#[test]
+fn reads_led_state_without_transition_flag_from_older_senders() {
+    let version = ProtocolVersion {
+        major: 1,
+        minor: 11,
+    };
+    let mut body = 2u16.to_le_bytes().to_vec();
+    body.extend(1u16.to_le_bytes());
+    body.extend([1, 2, 3]);
+
+    assert_eq!(
+        ControllerMessage::decode(&raw_datagram(version, &body)).map(|message| message.kind),
+        Ok(MessageKind::LedState {
+            led_values: ArrayVec::from_iter([Rgb8 { r: 1, g: 2, b: 3 }]),
+            transition: false,
+        })
+    );
+}
+
+#[test]
+fn easing_starts_at_zero_and_ends_at_one() {
+    for easing in Easing::ALL {
+        assert_eq!(easing.apply(0), 0);
+        assert_eq!(easing.apply(1 << 16), 1 << 16);
+        assert_eq!(easing.apply(u32::MAX), 1 << 16);
+    }
+    assert!(Easing::EaseIn.apply(1 << 15) < 1 << 15);
+    assert!(Easing::EaseOut.apply(1 << 15) > 1 << 15);
+}
+
+#[test]
+fn rejects_unknown_idle_action() {
+    let mut body = 22u16.to_le_bytes().to_vec();
+    body.extend(2000u16.to_le_bytes());
+    body.extend(1000u16.to_le_bytes());
+    body.push(4);
+    let datagram = raw_datagram(ProtocolVersion::CURRENT, &body);
+
+    assert_eq!(
+        ControllerMessage::decode(&datagram),
+        Err(DeserializationError::InvalidValue)
+    );
+}
+
+#[test]
+fn round_trips_sequence() {
+    let message = ControllerMessage {
+        timestamp: Timestamp::new(42),
+        sequence: Some(Sequence {
+            epoch: NonZeroU32::new(0x1234_5678).unwrap(),
+            number: u32::MAX,
+        }),
+        kind: MessageKind::KeepAlive { millis: 1000 },
+    };
+
+    assert_eq!(round_trip(&message), message);
+}
+
+#[test]
+fn reads_messages_without_sequence_from_older_senders() {
+    let version = ProtocolVersion {
+        major: 1,
+        minor: 15,
+    };
+    let datagram = raw_datagram(version, &[1, 0, 0xE8, 0x03, 0, 0]);
+
+    assert_eq!(
+        ControllerMessage::decode(&datagram),
+        Ok(ControllerMessage {
+            timestamp: Timestamp::new(0),
+            sequence: None,
+            kind: MessageKind::KeepAlive { millis: 1000 },
+        })
+    );
+}
+
+#[test]
+fn orders_sequence_numbers_across_the_wraparound() {
+    assert!(Sequence::is_after(2, 1));
+    assert!(!Sequence::is_after(1, 2));
+    assert!(!Sequence::is_after(7, 7));
+    assert!(Sequence::is_after(0, u32::MAX));
+    assert!(Sequence::is_after(5, u32::MAX - 5));
+    assert!(!Sequence::is_after(u32::MAX, 0));
+}